@@ -0,0 +1,161 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use crate::gitignore::wildmatch;
+use crate::location::Location;
+
+/// Per-directory attribute files, read in this order so that
+/// `.ritattributes` lines win over `.gitattributes` lines in the same
+/// directory.
+const ATTRIBUTE_FILES: [&str; 2] = [".gitattributes", ".ritattributes"];
+
+/// How a path's contents are compared, from its `diff` attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiffAttribute {
+    /// `diff`: always line by line, even with NUL bytes in it.
+    Text,
+    /// `-diff` or `binary`: never line by line.
+    Binary,
+    /// Not set, or `!diff`: decided by looking at the contents.
+    #[default]
+    Auto,
+}
+
+/// One line of an attribute file that says something about diffs.
+#[derive(Debug, Clone)]
+struct Line {
+    /// Patterns containing a `/` match the whole path relative to `base`;
+    /// others match the file name at any depth.
+    anchored: bool,
+    glob: Vec<char>,
+    /// Directory of the attribute file, `""` or ending in `/`.
+    base: String,
+    diff: DiffAttribute,
+}
+
+impl Line {
+    /// Parse `pattern attr...`. Lines that don't set `diff` or `binary`,
+    /// blank lines and comments yield `None`.
+    fn parse(text: &str, base: &str) -> Option<Line> {
+        let mut fields = text.split_whitespace();
+        let pattern = fields.next()?;
+        // Negated patterns are not allowed, and directories have no
+        // contents to compare
+        if pattern.starts_with('#') || pattern.starts_with('!') || pattern.ends_with('/') {
+            return None;
+        }
+
+        let mut diff = None;
+        for attribute in fields {
+            match attribute {
+                "diff" => diff = Some(DiffAttribute::Text),
+                "-diff" | "binary" => diff = Some(DiffAttribute::Binary),
+                "!diff" => diff = Some(DiffAttribute::Auto),
+                _ => {}
+            }
+        }
+
+        Some(Line {
+            anchored: pattern.contains('/'),
+            glob: pattern.strip_prefix('/').unwrap_or(pattern).chars().collect(),
+            base: base.to_string(),
+            diff: diff?,
+        })
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        let Some(relative) = path.strip_prefix(&self.base) else {
+            return false;
+        };
+        let text: Vec<char> = if self.anchored {
+            relative.chars().collect()
+        } else {
+            relative.rsplit('/').next().unwrap_or(relative).chars().collect()
+        };
+        wildmatch(&self.glob, &text)
+    }
+}
+
+/// The attributes of one working tree, from every `.gitattributes` and
+/// `.ritattributes` below the root and the repository's own
+/// `info/attributes`. Only the `diff` attribute (and `binary`, which unsets
+/// it) is read. Nested files are read the first time a path below them is
+/// looked up.
+pub struct Attributes {
+    root: PathBuf,
+    /// `info/attributes`, which overrides every file in the tree.
+    info: Vec<Line>,
+    per_dir: RefCell<HashMap<String, Rc<Vec<Line>>>>,
+}
+
+impl Attributes {
+    pub fn load(location: &Location) -> Self {
+        Attributes {
+            root: location.work_tree.clone(),
+            info: read_lines(&location.metadata_dir().join("info/attributes"), ""),
+            per_dir: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// How `path` (relative to the root, `/`-separated) is compared. The
+    /// last matching line wins, with deeper attribute files overriding
+    /// shallower ones.
+    pub fn diff(&self, path: &str) -> DiffAttribute {
+        let mut diff = DiffAttribute::Auto;
+        let parts: Vec<&str> = path.split('/').collect();
+        for depth in 0..parts.len() {
+            let lines = self.lines_in(&parts[..depth].join("/"));
+            if let Some(line) = lines.iter().rev().find(|line| line.matches(path)) {
+                diff = line.diff;
+            }
+        }
+        if let Some(line) = self.info.iter().rev().find(|line| line.matches(path)) {
+            diff = line.diff;
+        }
+        diff
+    }
+
+    /// The lines from the attribute files directly inside `dir`.
+    fn lines_in(&self, dir: &str) -> Rc<Vec<Line>> {
+        if let Some(lines) = self.per_dir.borrow().get(dir) {
+            return lines.clone();
+        }
+
+        let base = if dir.is_empty() { String::new() } else { format!("{}/", dir) };
+        let mut lines = Vec::new();
+        for name in ATTRIBUTE_FILES {
+            lines.extend(read_lines(&self.root.join(format!("{}{}", base, name)), &base));
+        }
+        let lines = Rc::new(lines);
+        self.per_dir.borrow_mut().insert(dir.to_string(), lines.clone());
+        lines
+    }
+}
+
+fn read_lines(path: &Path, base: &str) -> Vec<Line> {
+    let Ok(text) = fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    text.lines().filter_map(|line| Line::parse(line, base)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_repo::TestRepo;
+
+    #[test]
+    fn info_attributes_come_from_the_repository_and_win() {
+        let repo = TestRepo::new();
+        repo.write(".gitattributes", "*.dat diff\n*.txt binary\n");
+        repo.write(".rit/info/attributes", "*.dat binary\n");
+        repo.write(".git/info/attributes", "*.txt diff\n");
+
+        let attributes = Attributes::load(repo.repo.location());
+        assert_eq!(attributes.diff("data/blob.dat"), DiffAttribute::Binary);
+        assert_eq!(attributes.diff("notes.txt"), DiffAttribute::Binary);
+        assert_eq!(attributes.diff("main.rs"), DiffAttribute::Auto);
+    }
+}
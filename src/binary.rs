@@ -0,0 +1,119 @@
+use anyhow::Result;
+use std::cell::OnceCell;
+use std::fs;
+use std::io::Read;
+use crate::attributes::{Attributes, DiffAttribute};
+use crate::index::Index;
+use crate::repository::{Files, Repository};
+
+/// How much of a file is looked at for NUL bytes, as in git.
+const SNIFF_LENGTH: usize = 8000;
+
+/// Whether `data` looks like binary rather than text: git's test of a NUL
+/// byte near the start.
+pub fn looks_binary(data: &[u8]) -> bool {
+    data[..data.len().min(SNIFF_LENGTH)].contains(&0)
+}
+
+/// Whether the contents of `path` should be treated as binary: as its
+/// `diff` attribute says, or else by looking at `data`.
+pub fn is_binary(attributes: &Attributes, path: &str, data: &[u8]) -> bool {
+    match attributes.diff(path) {
+        DiffAttribute::Text => false,
+        DiffAttribute::Binary => true,
+        DiffAttribute::Auto => looks_binary(data),
+    }
+}
+
+/// Tells which files in the working tree are binary, judging from the
+/// start of each file on disk, or from the staged or committed copy of one
+/// that is gone from disk. The index and HEAD are read at most once.
+pub struct BinaryFiles<'a> {
+    repo: &'a dyn Repository,
+    attributes: Attributes,
+    index: OnceCell<Index>,
+    head: OnceCell<Files>,
+}
+
+impl<'a> BinaryFiles<'a> {
+    pub fn new(repo: &'a dyn Repository) -> Self {
+        BinaryFiles {
+            repo,
+            attributes: Attributes::load(repo.location()),
+            index: OnceCell::new(),
+            head: OnceCell::new(),
+        }
+    }
+
+    /// Whether the file at `path` (relative to the root) is binary.
+    /// Symlinks and paths found nowhere are not.
+    pub fn is_binary(&self, path: &str) -> Result<bool> {
+        let full_path = self.repo.location().work_tree.join(path);
+        let data = match fs::symlink_metadata(&full_path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => return Ok(false),
+            Ok(metadata) if metadata.is_file() => {
+                let mut data = Vec::with_capacity(SNIFF_LENGTH);
+                fs::File::open(&full_path)?.take(SNIFF_LENGTH as u64).read_to_end(&mut data)?;
+                data
+            }
+            _ => match self.stored_id(path)? {
+                Some(id) => self.repo.read_blob(&id)?,
+                None => return Ok(false),
+            },
+        };
+        Ok(is_binary(&self.attributes, path, &data))
+    }
+
+    /// The staged blob of `path`, or else the committed one.
+    fn stored_id(&self, path: &str) -> Result<Option<String>> {
+        if self.index.get().is_none() {
+            let _ = self.index.set(self.repo.load_index()?);
+        }
+        if let Some(entry) = self.index.get().and_then(|index| index.get(path)) {
+            return Ok(Some(entry.id.clone()));
+        }
+        if self.head.get().is_none() {
+            let _ = self.head.set(self.repo.head_files()?);
+        }
+        Ok(self.head.get().and_then(|files| files.get(path)).map(|(_, id)| id.clone()))
+    }
+}
+
+/// Width and height of a PNG or JPEG image, read from its header.
+pub fn image_size(data: &[u8]) -> Option<(u32, u32)> {
+    let be16 = |at: usize| -> Option<u32> { Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?) as u32) };
+    let be32 = |at: usize| -> Option<u32> { Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?)) };
+
+    // The signature, then the IHDR chunk's length, type, width and height
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        if data.get(12..16)? != b"IHDR" {
+            return None;
+        }
+        return Some((be32(16)?, be32(20)?));
+    }
+
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    // Walk the segments up to the start-of-frame one, which holds the
+    // precision, then height and width
+    let mut at = 2;
+    loop {
+        if *data.get(at)? != 0xFF {
+            return None;
+        }
+        let marker = *data.get(at + 1)?;
+        match marker {
+            // Padding before a marker
+            0xFF => at += 1,
+            // Markers without a segment
+            0x01 | 0xD0..=0xD7 => at += 2,
+            // Start of scan: the image data follows, with no frame seen
+            0xDA | 0xD9 => return None,
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                return Some((be16(at + 7)?, be16(at + 5)?));
+            }
+            _ => at += 2 + be16(at + 2)? as usize,
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use anyhow::{Result, bail};
+use std::collections::HashSet;
+use crate::refs;
+use crate::repository::{RefKind, Repository};
+
+/// A local branch and the commit it points at.
+#[derive(Debug, Clone)]
+pub struct BranchInfo {
+    pub name: String,
+    pub tip: String,
+    /// First line of the tip commit's message.
+    pub summary: String,
+    pub upstream: Option<String>,
+}
+
+/// Every local branch that points at a commit, sorted by name.
+pub fn list_branches(repo: &dyn Repository) -> Result<Vec<BranchInfo>> {
+    repo.references()?
+        .into_iter()
+        .filter(|reference| reference.kind == RefKind::Branch)
+        .map(|reference| {
+            Ok(BranchInfo {
+                summary: summary(repo, &reference.target)?,
+                upstream: repo.upstream(&reference.name)?,
+                name: reference.name,
+                tip: reference.target,
+            })
+        })
+        .collect()
+}
+
+/// The first line of a commit's message.
+pub fn summary(repo: &dyn Repository, id: &str) -> Result<String> {
+    Ok(repo.read_commit(id)?.message.lines().next().unwrap_or("").to_string())
+}
+
+/// Create a branch at `start_point` (HEAD by default). Returns the commit
+/// it points at.
+pub fn create(repo: &dyn Repository, name: &str, start_point: Option<&str>) -> Result<String> {
+    check_new_name(name, &list_branches(repo)?)?;
+    let id = repo.resolve(start_point.unwrap_or("HEAD"))?;
+    repo.create_branch(name, &id)?;
+    Ok(id)
+}
+
+/// Delete a branch that isn't checked out. Unless `force` is set, the
+/// branch must already be merged into HEAD. Returns the commit it pointed
+/// at.
+pub fn delete(repo: &dyn Repository, name: &str, force: bool) -> Result<String> {
+    let existing = list_branches(repo)?;
+    let Some(branch) = existing.iter().find(|branch| branch.name == name) else {
+        bail!("branch '{}' not found", name);
+    };
+    if repo.head()?.branch_name() == Some(name) {
+        bail!("cannot delete branch '{}' while it is checked out", name);
+    }
+    if !force {
+        let merged = match repo.resolve("HEAD") {
+            Ok(head_id) => repo.is_ancestor(&branch.tip, &head_id)?,
+            Err(_) => false,
+        };
+        if !merged {
+            bail!("branch '{}' is not fully merged into HEAD (use -D to delete it anyway)", name);
+        }
+    }
+    repo.delete_branch(name)?;
+    Ok(branch.tip.clone())
+}
+
+/// Rename `old` (the current branch by default) to `new`. Returns the old
+/// name.
+pub fn rename(repo: &dyn Repository, old: Option<&str>, new: &str) -> Result<String> {
+    let existing = list_branches(repo)?;
+    let old = match old {
+        Some(old) => old.to_string(),
+        None => match repo.head()?.branch_name() {
+            Some(current) => current.to_string(),
+            None => bail!("HEAD is detached; name the branch to rename"),
+        },
+    };
+    if !existing.iter().any(|branch| branch.name == old) {
+        bail!("branch '{}' not found", old);
+    }
+    check_new_name(new, &existing)?;
+    repo.rename_branch(&old, new)?;
+    Ok(old)
+}
+
+/// Set or clear (`None`) the upstream of `branch` (the current branch by
+/// default). Returns the branch name.
+pub fn set_upstream(repo: &dyn Repository, branch: Option<&str>, upstream: Option<&str>) -> Result<String> {
+    let name = match branch {
+        Some(branch) => branch.to_string(),
+        None => match repo.head()?.branch_name() {
+            Some(current) => current.to_string(),
+            None => bail!("HEAD is detached; name the branch to configure"),
+        },
+    };
+    if !list_branches(repo)?.iter().any(|branch| branch.name == name) {
+        bail!("branch '{}' not found", name);
+    }
+    repo.set_upstream(&name, upstream)?;
+    Ok(name)
+}
+
+/// How many commits `local` has that `upstream` doesn't, and how many
+/// `upstream` has that `local` doesn't.
+pub fn ahead_behind(repo: &dyn Repository, local: &str, upstream: &str) -> Result<(usize, usize)> {
+    let ours = ancestors(repo, local)?;
+    let theirs = ancestors(repo, upstream)?;
+    Ok((ours.difference(&theirs).count(), theirs.difference(&ours).count()))
+}
+
+/// A commit and everything reachable from it.
+fn ancestors(repo: &dyn Repository, id: &str) -> Result<HashSet<String>> {
+    let mut seen = HashSet::new();
+    let mut pending = vec![id.to_string()];
+    while let Some(id) = pending.pop() {
+        if seen.insert(id.clone()) {
+            pending.extend(repo.read_commit(&id)?.parents);
+        }
+    }
+    Ok(seen)
+}
+
+fn check_new_name(name: &str, existing: &[BranchInfo]) -> Result<()> {
+    if !refs::is_valid_branch_name(name) {
+        bail!("'{}' is not a valid branch name", name);
+    }
+    for branch in existing {
+        if branch.name == name {
+            bail!("a branch named '{}' already exists", name);
+        }
+        // `a` and `a/b` can't both exist: one would be a file, the other a directory
+        if branch.name.starts_with(&format!("{}/", name)) || name.starts_with(&format!("{}/", branch.name)) {
+            bail!("'{}' conflicts with existing branch '{}'", name, branch.name);
+        }
+    }
+    Ok(())
+}
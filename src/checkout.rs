@@ -0,0 +1,400 @@
+use anyhow::{Result, bail};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use crate::index::{Index, IndexEntry, MODE_EXECUTABLE, MODE_SYMLINK};
+use crate::objects::{self, ObjectFormat, ObjectKind};
+use crate::refs::Head;
+use crate::repository::{Files, RefKind, Repository};
+
+/// What a checkout does to one file in the working tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Created,
+    Changed,
+    Removed,
+}
+
+/// The error returned when local changes stand in the way of a checkout.
+/// Nothing has been touched when it is returned.
+#[derive(Debug, Clone)]
+pub struct Blocked {
+    /// The paths in the way, relative to the top of the working tree.
+    pub paths: Vec<String>,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for Blocked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.reason)
+    }
+}
+
+impl Error for Blocked {}
+
+/// The result of [`switch`].
+#[derive(Debug, Clone)]
+pub struct Switched {
+    pub head: Head,
+    pub id: String,
+    /// First line of the new HEAD commit's message.
+    pub summary: String,
+    /// Files rewritten in the working tree, sorted by path.
+    pub changes: Vec<(String, Change)>,
+}
+
+/// The result of [`restore`].
+#[derive(Debug, Clone)]
+pub struct Restored {
+    pub id: String,
+    pub changes: Vec<(String, Change)>,
+}
+
+/// Move HEAD to the branch named `revision`, or with `allow_detach` to any
+/// commit, rewriting the working tree and index to match. Fails with
+/// [`Blocked`] if that would lose local changes.
+pub fn switch(repo: &dyn Repository, revision: &str, allow_detach: bool) -> Result<Switched> {
+    let location = repo.location();
+    let branch = repo.references()?
+        .into_iter()
+        .find(|reference| reference.kind == RefKind::Branch && reference.name == revision);
+    let (head, id) = match branch {
+        Some(branch) => (Head::Branch(format!("refs/heads/{}", branch.name)), branch.target),
+        None if allow_detach => {
+            let id = repo.resolve(revision)?;
+            (Head::Detached(id.clone()), id)
+        }
+        None => bail!("'{}' is not a branch (use --detach to switch to a commit)", revision),
+    };
+
+    let unresolved = repo.conflicts()?;
+    if !unresolved.is_empty() {
+        let paths = unresolved.into_iter().map(|conflict| conflict.path).collect();
+        return Err(Blocked { paths, reason: "switch aborted; resolve your merge conflicts first" }.into());
+    }
+
+    let mut index = repo.load_index()?;
+    let current = repo.head_files()?;
+    let target = repo.commit_files(&id)?;
+    let changes = tree_changes(&current, &target);
+
+    let conflicts = switch_conflicts(repo, &index, &current, &target, &changes)?;
+    if !conflicts.is_empty() {
+        return Err(Blocked { paths: conflicts, reason: "switch aborted; commit your changes first" }.into());
+    }
+
+    // Removals go first, so a file can take the place of a directory the
+    // switch empties
+    for (path, _) in changes.iter().filter(|(_, change)| *change == Change::Removed) {
+        remove_file(&location.work_tree, path)?;
+        index.remove(path);
+    }
+    for (path, _) in changes.iter().filter(|(_, change)| *change != Change::Removed) {
+        let (mode, id) = &target[path];
+        index.add(write_file(repo, path, *mode, id)?);
+    }
+    repo.save_index(&index)?;
+    repo.set_head(&head)?;
+
+    let summary = repo.read_commit(&id)?.message.lines().next().unwrap_or("").to_string();
+    Ok(Switched { head, id, summary, changes })
+}
+
+/// Paths whose local state would be lost by moving from `current` to
+/// `target`: staged or unstaged edits to files the switch rewrites, and
+/// untracked files standing where the target wants a file. A directory
+/// where the target wants a file is only in the way if anything but files
+/// the switch removes is left in it.
+fn switch_conflicts(
+    repo: &dyn Repository,
+    index: &Index,
+    current: &Files,
+    target: &Files,
+    changes: &[(String, Change)],
+) -> Result<Vec<String>> {
+    let work_tree = &repo.location().work_tree;
+    let removed: HashSet<&str> = changes.iter()
+        .filter(|(_, change)| *change == Change::Removed)
+        .map(|(path, _)| path.as_str())
+        .collect();
+    let mut conflicts = Vec::new();
+    for (path, _) in changes {
+        let full_path = work_tree.join(path);
+        let staged = index.get(path);
+        let conflict = match (staged, current.get(path)) {
+            (Some(entry), Some((mode, id))) => {
+                entry.mode != *mode || entry.id != *id || !entry.matches_file(&full_path, repo.format())?
+            }
+            (Some(_), None) | (None, Some(_)) => true,
+            (None, None) => match full_path.symlink_metadata() {
+                Ok(metadata) if metadata.is_dir() => !holds_only(&full_path, path, &removed)?,
+                Ok(_) => !same_contents(&full_path, repo.format(), target.get(path))?,
+                // An untracked file can also stand where a directory goes
+                Err(_) => path.match_indices('/').any(|(slash, _)| {
+                    let parent = &path[..slash];
+                    !removed.contains(parent) && work_tree.join(parent).symlink_metadata().is_ok_and(|metadata| !metadata.is_dir())
+                }),
+            },
+        };
+        if conflict {
+            conflicts.push(path.clone());
+        }
+    }
+    Ok(conflicts)
+}
+
+/// Restore the files matching `specs` (paths relative to the top of the
+/// working tree; `""` matches everything) from `revision` into the index
+/// and working tree, leaving HEAD alone. Fails with [`Blocked`] if that
+/// would lose edits that were never added, unless `force` is set.
+pub fn restore(repo: &dyn Repository, revision: &str, specs: &[String], force: bool) -> Result<Restored> {
+    let location = repo.location();
+    let id = repo.resolve(revision)?;
+    let target = repo.commit_files(&id)?;
+    let mut index = repo.load_index()?;
+
+    for spec in specs {
+        if !target.keys().any(|file| matches_pathspec(file, spec)) {
+            bail!("pathspec '{}' did not match any file in {}", spec, &id[..7]);
+        }
+    }
+
+    let mut changes = Vec::new();
+    let mut conflicts = Vec::new();
+    for (path, (mode, blob)) in target.iter().filter(|(file, _)| specs.iter().any(|spec| matches_pathspec(file, spec))) {
+        let full_path = location.work_tree.join(path);
+        let staged = index.get(path);
+        if let Some(entry) = staged
+            && entry.mode == *mode
+            && entry.id == *blob
+            && entry.matches_file(&full_path, repo.format())?
+        {
+            continue;
+        }
+
+        // Staged contents stay in the object store, but edits that were
+        // never added would be gone for good
+        let unsaved = match staged {
+            Some(entry) => full_path.exists() && !entry.matches_file(&full_path, repo.format())?,
+            None => full_path.exists() && !same_contents(&full_path, repo.format(), Some(&(*mode, blob.clone())))?,
+        };
+        if unsaved && !force {
+            conflicts.push(path.clone());
+        }
+        let change = if full_path.exists() { Change::Changed } else { Change::Created };
+        changes.push((path.clone(), change));
+    }
+
+    if !conflicts.is_empty() {
+        return Err(Blocked { paths: conflicts, reason: "checkout aborted; add your changes or use --force to discard them" }.into());
+    }
+
+    for (path, _) in &changes {
+        let (mode, blob) = &target[path];
+        index.add(write_file(repo, path, *mode, blob)?);
+    }
+    repo.save_index(&index)?;
+
+    Ok(Restored { id, changes })
+}
+
+fn tree_changes(current: &Files, target: &Files) -> Vec<(String, Change)> {
+    let mut changes = Vec::new();
+    for (path, entry) in target {
+        match current.get(path) {
+            None => changes.push((path.clone(), Change::Created)),
+            Some(old) if old != entry => changes.push((path.clone(), Change::Changed)),
+            Some(_) => {}
+        }
+    }
+    for path in current.keys() {
+        if !target.contains_key(path) {
+            changes.push((path.clone(), Change::Removed));
+        }
+    }
+    changes.sort_by(|a, b| a.0.cmp(&b.0));
+    changes
+}
+
+/// Whether every file below the directory `full_path` (at `path` in the
+/// working tree) is one of `files`.
+fn holds_only(full_path: &Path, path: &str, files: &HashSet<&str>) -> Result<bool> {
+    for entry in walkdir::WalkDir::new(full_path) {
+        let entry = entry?;
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(full_path)?.to_string_lossy().replace('\\', "/");
+        if !files.contains(format!("{}/{}", path, relative).as_str()) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Whether the file on disk already holds the given blob.
+fn same_contents(full_path: &Path, format: ObjectFormat, entry: Option<&(u32, String)>) -> Result<bool> {
+    let Some((_, id)) = entry else {
+        return Ok(false);
+    };
+    if full_path.is_dir() {
+        return Ok(false);
+    }
+    Ok(objects::hash_object(format, ObjectKind::Blob, &fs::read(full_path)?) == *id)
+}
+
+/// Write a blob into the working tree and return its fresh index entry.
+fn write_file(repo: &dyn Repository, path: &str, mode: u32, id: &str) -> Result<IndexEntry> {
+    let data = repo.read_blob(id)?;
+    let full_path = repo.location().work_tree.join(path);
+    if let Some(parent) = full_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    match full_path.symlink_metadata() {
+        Ok(metadata) if metadata.is_dir() => remove_empty_dir(&full_path)?,
+        Ok(_) => fs::remove_file(&full_path)?,
+        Err(_) => {}
+    }
+    if mode == MODE_SYMLINK {
+        return write_symlink(&full_path, path, &data, id);
+    }
+    fs::write(&full_path, data)?;
+    set_executable(&full_path, mode == MODE_EXECUTABLE)?;
+    IndexEntry::from_file(path, &full_path, id.to_string())
+}
+
+/// Delete a file and any directories it leaves empty.
+fn remove_file(base: &Path, path: &str) -> Result<()> {
+    let full_path = base.join(path);
+    if full_path.symlink_metadata().is_ok() {
+        fs::remove_file(&full_path)?;
+    }
+    let mut dir = full_path.parent();
+    while let Some(current) = dir {
+        if current == base || fs::remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+    Ok(())
+}
+
+/// Delete a directory that only empty directories are left in. Fails,
+/// leaving the files alone, if there is anything else.
+fn remove_empty_dir(path: &Path) -> Result<()> {
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            remove_empty_dir(&entry.path())?;
+        }
+    }
+    fs::remove_dir(path)?;
+    Ok(())
+}
+
+/// A symlink's blob holds its target. Its index entry carries no stat
+/// data, so it is always compared by contents.
+#[cfg(unix)]
+fn write_symlink(full_path: &Path, path: &str, target: &[u8], id: &str) -> Result<IndexEntry> {
+    std::os::unix::fs::symlink(String::from_utf8_lossy(target).as_ref(), full_path)?;
+    Ok(IndexEntry {
+        path: path.to_string(),
+        mode: MODE_SYMLINK,
+        size: target.len() as u32,
+        mtime_secs: 0,
+        mtime_nanos: 0,
+        id: id.to_string(),
+    })
+}
+
+/// Without symlinks the link is checked out as a file holding its target,
+/// as git does with `core.symlinks = false`.
+#[cfg(not(unix))]
+fn write_symlink(full_path: &Path, path: &str, target: &[u8], id: &str) -> Result<IndexEntry> {
+    fs::write(full_path, target)?;
+    Ok(IndexEntry { mode: MODE_SYMLINK, ..IndexEntry::from_file(path, full_path, id.to_string())? })
+}
+
+#[cfg(unix)]
+fn set_executable(path: &Path, executable: bool) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut permissions = fs::metadata(path)?.permissions();
+    let mode = permissions.mode();
+    permissions.set_mode(if executable { mode | 0o111 } else { mode & !0o111 });
+    fs::set_permissions(path, permissions)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &Path, _executable: bool) -> Result<()> {
+    Ok(())
+}
+
+fn matches_pathspec(file: &str, spec: &str) -> bool {
+    spec.is_empty() || file == spec || file.strip_prefix(spec).is_some_and(|rest| rest.starts_with('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::branch;
+    use crate::test_repo::TestRepo;
+
+    /// A repository on `main` tracking `a/b`, with a branch `file` that
+    /// tracks a file `a` instead.
+    fn directory_and_file() -> TestRepo {
+        let repo = TestRepo::new();
+        repo.write("a/b", "in a directory\n");
+        repo.add(&["a/b"]);
+        repo.commit("directory", 1000);
+        branch::create(repo.repo.as_ref(), "file", None).unwrap();
+        switch(repo.repo.as_ref(), "file", false).unwrap();
+        fs::remove_dir_all(repo.path("a")).unwrap();
+        repo.write("a", "a file\n");
+        repo.add(&["a", "a/b"]);
+        repo.commit("file", 1001);
+        repo
+    }
+
+    #[test]
+    fn a_file_replaces_a_tracked_directory() {
+        let repo = directory_and_file();
+        switch(repo.repo.as_ref(), "main", false).unwrap();
+        assert_eq!(fs::read_to_string(repo.path("a/b")).unwrap(), "in a directory\n");
+
+        switch(repo.repo.as_ref(), "file", false).unwrap();
+        assert_eq!(fs::read_to_string(repo.path("a")).unwrap(), "a file\n");
+        let index = repo.repo.load_index().unwrap();
+        assert!(index.get("a").is_some());
+        assert!(index.get("a/b").is_none());
+    }
+
+    #[test]
+    fn untracked_files_keep_a_directory_in_the_way() {
+        let repo = directory_and_file();
+        switch(repo.repo.as_ref(), "main", false).unwrap();
+        repo.write("a/c", "untracked\n");
+
+        let error = switch(repo.repo.as_ref(), "file", false).unwrap_err();
+        assert_eq!(error.downcast_ref::<Blocked>().unwrap().paths, ["a"]);
+        assert_eq!(fs::read_to_string(repo.path("a/b")).unwrap(), "in a directory\n");
+    }
+
+    #[test]
+    fn an_untracked_file_in_place_of_a_directory_is_in_the_way() {
+        let repo = directory_and_file();
+        switch(repo.repo.as_ref(), "main", false).unwrap();
+        branch::create(repo.repo.as_ref(), "other", None).unwrap();
+        switch(repo.repo.as_ref(), "other", false).unwrap();
+        fs::remove_dir_all(repo.path("a")).unwrap();
+        repo.write("c", "elsewhere\n");
+        repo.add(&["a/b", "c"]);
+        repo.commit("other", 1002);
+        repo.write("a", "untracked\n");
+
+        let error = switch(repo.repo.as_ref(), "main", false).unwrap_err();
+        assert_eq!(error.downcast_ref::<Blocked>().unwrap().paths, ["a/b"]);
+    }
+}
@@ -1,161 +1,161 @@
-use anyhow::Result;
+use anyhow::{Result, bail};
 use colored::*;
-use std::path::Path;
-use ignore::WalkBuilder;
+use serde::Serialize;
+use rit::binary::BinaryFiles;
+use rit::location::Location;
+use rit::repository;
+use rit::stage::{self, AddOutcome, Staged};
+use rit::tree::glyph;
+use super::output::{self, Format};
 
-pub fn run(files: Vec<String>) -> Result<()> {
-    let current_dir = std::env::current_dir()?;
-    
+pub fn run(files: Vec<String>, format: Format) -> Result<()> {
+    if files.is_empty() && format.is_json() {
+        bail!("nothing specified, nothing added");
+    }
     if files.is_empty() {
         println!("{}", "Nothing specified, nothing added.".yellow());
         println!("{}", "Maybe you wanted to say 'rit add .'?".bright_blue());
         return Ok(());
     }
-    
+
+    let Some(repo) = repository::discover()? else {
+        if format.is_json() {
+            bail!("not a rit repository (or any of the parent directories)");
+        }
+        println!("{}", "fatal: not a rit repository (or any of the parent directories)".red());
+        return Ok(());
+    };
+
+    // Paths are relative to where rit was run from, not the root
+    let location = repo.location();
+    let paths = files.iter()
+        .map(|file| location.repo_path(file))
+        .collect::<Result<Vec<_>>>()?;
+    let outcomes = stage::add(repo.as_ref(), &paths)?;
+    if format.is_json() {
+        return print_json(&outcomes);
+    }
+
     println!("{}", "Adding files to staging area...".bright_green().bold());
     println!();
-    
-    for file in &files {
-        if file == "." {
-            println!("{}", "Adding all files in current directory:".bright_blue());
-            add_all_files(&current_dir)?;
-        } else {
-            add_single_file(&current_dir, file)?;
+
+    let binary = BinaryFiles::new(repo.as_ref());
+    for (file, outcome) in files.iter().zip(&outcomes) {
+        match outcome {
+            AddOutcome::File(staged) => print_staged(location, &binary, staged)?,
+            AddOutcome::Directory { path, staged, ignored } => {
+                if file == "." {
+                    println!("{}", "Adding all files in current directory:".bright_blue());
+                } else {
+                    println!("{} {}/", format!("{} Added directory:", glyph("├──")).green(), location.display_path(path).bright_green());
+                }
+                for staged in staged {
+                    print_staged(location, &binary, staged)?;
+                }
+                print_totals(staged, ignored.len());
+            }
+            AddOutcome::Ignored { decision, .. } => {
+                println!("{} {} {}",
+                    format!("{} Ignored:", glyph("├──")).bright_black(),
+                    file.bright_black(),
+                    format!("(matches {})", decision.describe()).bright_black()
+                );
+            }
+            AddOutcome::NotFound { .. } => {
+                println!("{} {}", "File not found:".red(), file.bright_red());
+            }
         }
     }
-    
+
     println!();
     println!("{}", "Files added successfully!".green());
     println!("{}", "Run 'rit status' to see the changes.".bright_blue());
-    
+
     Ok(())
 }
 
-fn add_single_file(base_path: &Path, file_path: &str) -> Result<()> {
-    let full_path = base_path.join(file_path);
-    
-    if !full_path.exists() {
-        println!("{} {}", "File not found:".red(), file_path.bright_red());
-        return Ok(());
-    }
-    
-    // Check if file should be ignored
-    if is_ignored(base_path, &full_path)? {
-        println!("{} {} {}", 
-            "├── Ignored:".bright_black(), 
-            file_path.bright_black(),
-            "(matches ignore pattern)".bright_black()
-        );
-        return Ok(());
+/// `--format json`: every path that was staged, removed, ignored or not
+/// found, relative to the top of the working tree.
+fn print_json(outcomes: &[AddOutcome]) -> Result<()> {
+    #[derive(Serialize, Default)]
+    struct Json<'a> {
+        added: Vec<Added<'a>>,
+        removed: Vec<&'a str>,
+        ignored: Vec<&'a str>,
+        not_found: Vec<&'a str>,
     }
-    
-    if full_path.is_file() {
-        println!("{} {}", "├── Added:".green(), file_path.bright_green());
-    } else if full_path.is_dir() {
-        println!("{} {}/", "├── Added directory:".green(), file_path.bright_green());
-        // In a real implementation, you'd recursively add all files in the directory
+    #[derive(Serialize)]
+    struct Added<'a> {
+        path: &'a str,
+        id: &'a str,
     }
-    
-    Ok(())
-}
 
-fn add_all_files(base_path: &Path) -> Result<()> {
-    let mut file_count = 0;
-    let mut ignored_count = 0;
-    
-    let mut walker = WalkBuilder::new(base_path);
-    walker.hidden(false); // Show hidden files but respect ignore files
-    
-    // Add custom ignore file for .ritignore
-    let ritignore_path = base_path.join(".ritignore");
-    if ritignore_path.exists() {
-        walker.add_ignore(ritignore_path);
-    }
-    
-    for result in walker.build() {
-        if let Ok(entry) = result {
-            if entry.file_type().map_or(false, |ft| ft.is_file()) {
-                if let Ok(relative_path) = entry.path().strip_prefix(base_path) {
-                    let path_str = relative_path.to_string_lossy();
-                    
-                    // Skip target directory for Rust projects specifically
-                    if path_str.starts_with("target/") {
-                        continue;
-                    }
-                    
-                    println!("{} {}", "├── Added:".green(), path_str.bright_green());
-                    file_count += 1;
-                }
+    let mut json = Json::default();
+    for outcome in outcomes {
+        let staged = match outcome {
+            AddOutcome::File(staged) => std::slice::from_ref(staged),
+            AddOutcome::Directory { staged, ignored, .. } => {
+                json.ignored.extend(ignored.iter().map(String::as_str));
+                staged.as_slice()
+            }
+            AddOutcome::Ignored { path, .. } => {
+                json.ignored.push(path);
+                continue;
+            }
+            AddOutcome::NotFound { path } => {
+                json.not_found.push(path);
+                continue;
+            }
+        };
+        for staged in staged {
+            match staged {
+                Staged::Added { path, id } => json.added.push(Added { path, id }),
+                Staged::Removed { path } => json.removed.push(path),
             }
         }
     }
-    
-    if file_count == 0 && ignored_count == 0 {
-        println!("{}", "No files to add.".yellow());
+    output::print_json(&json)
+}
+
+/// One staged or removed file, with a marker if it is binary.
+fn print_staged(location: &Location, binary: &BinaryFiles, staged: &Staged) -> Result<()> {
+    let path = location.display_path(staged.path());
+    let marker = if binary.is_binary(staged.path())? {
+        format!("{} ", glyph("◆").bright_blue())
     } else {
-        println!();
-        println!("{} {} {}", 
-            "Total:".bright_blue(),
-            file_count.to_string().bright_yellow(),
-            "files added".bright_blue()
-        );
-        
-        if ignored_count > 0 {
-            println!("{} {} {}", 
-                "Ignored:".bright_black(),
-                ignored_count.to_string().bright_black(),
-                "files (matches ignore patterns)".bright_black()
-            );
-        }
+        String::new()
+    };
+    match staged {
+        Staged::Added { id, .. } => println!("{} {}{} {}",
+            format!("{} Added:", glyph("├──")).green(),
+            marker,
+            path.bright_green(),
+            id[..7].bright_black()
+        ),
+        Staged::Removed { .. } => println!("{} {}{}", format!("{} Removed:", glyph("├──")).red(), marker, path.bright_red()),
     }
-    
     Ok(())
 }
 
-fn is_ignored(base_path: &Path, file_path: &Path) -> Result<bool> {
-    // Simple check - in a full implementation, you'd use ignore crate more thoroughly
-    let gitignore_path = base_path.join(".gitignore");
-    let ritignore_path = base_path.join(".ritignore");
-    
-    if let Ok(relative_path) = file_path.strip_prefix(base_path) {
-        let path_str = relative_path.to_string_lossy();
-        
-        // Check common ignore patterns
-        if path_str.starts_with("target/") || 
-           path_str.starts_with(".git/") || 
-           path_str.starts_with(".rit/") ||
-           path_str.contains("node_modules/") {
-            return Ok(true);
-        }
-        
-        // Check .gitignore patterns (simplified)
-        if gitignore_path.exists() {
-            if let Ok(gitignore_content) = std::fs::read_to_string(&gitignore_path) {
-                for line in gitignore_content.lines() {
-                    let line = line.trim();
-                    if !line.is_empty() && !line.starts_with('#') {
-                        if path_str.contains(line) || path_str.starts_with(line) {
-                            return Ok(true);
-                        }
-                    }
-                }
-            }
-        }
-        
-        // Check .ritignore patterns (simplified)
-        if ritignore_path.exists() {
-            if let Ok(ritignore_content) = std::fs::read_to_string(&ritignore_path) {
-                for line in ritignore_content.lines() {
-                    let line = line.trim();
-                    if !line.is_empty() && !line.starts_with('#') {
-                        if path_str.contains(line) || path_str.starts_with(line) {
-                            return Ok(true);
-                        }
-                    }
-                }
-            }
-        }
+fn print_totals(staged: &[Staged], ignored_count: usize) {
+    let file_count = staged.iter().filter(|staged| matches!(staged, Staged::Added { .. })).count();
+    if file_count == 0 && ignored_count == 0 {
+        println!("{}", "No files to add.".yellow());
+        return;
     }
-    
-    Ok(false)
-} 
\ No newline at end of file
+
+    println!();
+    println!("{} {} {}",
+        "Total:".bright_blue(),
+        file_count.to_string().bright_yellow(),
+        "files added".bright_blue()
+    );
+
+    if ignored_count > 0 {
+        println!("{} {} {}",
+            "Ignored:".bright_black(),
+            ignored_count.to_string().bright_black(),
+            "paths (matches ignore patterns)".bright_black()
+        );
+    }
+}
@@ -0,0 +1,200 @@
+use anyhow::{Result, bail};
+use colored::*;
+use serde::Serialize;
+use rit::branch::{self, BranchInfo};
+use rit::refs::Head;
+use rit::repository::{self, Repository};
+use rit::tree::glyph;
+use super::output::{self, Format};
+
+/// What `rit branch` was asked to do.
+pub enum BranchAction {
+    List,
+    Create { name: String, start_point: Option<String> },
+    Delete { name: String, force: bool },
+    Rename { old: Option<String>, new: String },
+    SetUpstream { branch: Option<String>, upstream: Option<String> },
+}
+
+pub fn run(action: BranchAction, format: Format) -> Result<()> {
+    // Check if we're in a repository
+    let Some(repo) = repository::discover()? else {
+        if format.is_json() {
+            bail!("not a rit repository");
+        }
+        println!("{}", "fatal: not a rit repository".red());
+        return Ok(());
+    };
+
+    let repo = repo.as_ref();
+    let done = match action {
+        BranchAction::List => {
+            let list = branch::list_branches(repo)?;
+            let head = repo.head()?;
+            return if format.is_json() {
+                print_list_json(&list, &head)
+            } else {
+                display_branches(repo, &list, &head)
+            };
+        }
+        BranchAction::Create { name, start_point } => {
+            let id = branch::create(repo, &name, start_point.as_deref())?;
+            Done::Created { name, id }
+        }
+        BranchAction::Delete { name, force } => {
+            let id = branch::delete(repo, &name, force)?;
+            Done::Deleted { name, id }
+        }
+        BranchAction::Rename { old, new } => {
+            let old = branch::rename(repo, old.as_deref(), &new)?;
+            Done::Renamed { old, new }
+        }
+        BranchAction::SetUpstream { branch, upstream } => {
+            let name = branch::set_upstream(repo, branch.as_deref(), upstream.as_deref())?;
+            Done::UpstreamSet { name, upstream }
+        }
+    };
+
+    if format.is_json() {
+        output::print_json(&done)
+    } else {
+        print_done(&done);
+        Ok(())
+    }
+}
+
+/// A change made by `rit branch`. With `--format json` it is printed as
+/// an object tagged by `"action"`.
+#[derive(Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum Done {
+    Created { name: String, id: String },
+    /// `id` is the commit the branch pointed at.
+    Deleted { name: String, id: String },
+    Renamed { old: String, new: String },
+    UpstreamSet { name: String, upstream: Option<String> },
+}
+
+fn print_done(done: &Done) {
+    match done {
+        Done::Created { name, id } => println!("{} {} {} {}",
+            "Created branch".bright_green(),
+            name.bright_green().bold(),
+            "at".bright_green(),
+            id[..7].bright_yellow()
+        ),
+        Done::Deleted { name, id } => println!("{} {} {}",
+            "Deleted branch".bright_red(),
+            name.bright_red().bold(),
+            format!("(was {})", &id[..7]).bright_black()
+        ),
+        Done::Renamed { old, new } => println!("{} {} {} {}",
+            "Renamed branch".bright_green(),
+            old.bright_yellow(),
+            glyph("→").bright_blue(),
+            new.bright_green().bold()
+        ),
+        Done::UpstreamSet { name, upstream: Some(upstream) } => println!("{} {} {} {}",
+            "Branch".bright_green(),
+            name.bright_green().bold(),
+            "now tracks".bright_green(),
+            upstream.bright_cyan()
+        ),
+        Done::UpstreamSet { name, upstream: None } => println!("{} {} {}",
+            "Branch".bright_green(),
+            name.bright_green().bold(),
+            "no longer tracks an upstream".bright_green()
+        ),
+    }
+}
+
+/// `--format json` for the branch list: where HEAD is and every local
+/// branch.
+fn print_list_json(list: &[BranchInfo], head: &Head) -> Result<()> {
+    #[derive(Serialize)]
+    struct Json<'a> {
+        head: HeadJson<'a>,
+        branches: Vec<Branch<'a>>,
+    }
+    #[derive(Serialize)]
+    struct HeadJson<'a> {
+        branch: Option<&'a str>,
+        /// Only set when HEAD is detached
+        detached: Option<&'a str>,
+    }
+    #[derive(Serialize)]
+    struct Branch<'a> {
+        name: &'a str,
+        id: &'a str,
+        summary: &'a str,
+        upstream: Option<&'a str>,
+        current: bool,
+    }
+
+    output::print_json(&Json {
+        head: HeadJson {
+            branch: head.branch_name(),
+            detached: match head {
+                Head::Detached(id) => Some(id),
+                Head::Branch(_) => None,
+            },
+        },
+        branches: list.iter().map(|branch| Branch {
+            name: &branch.name,
+            id: &branch.tip,
+            summary: &branch.summary,
+            upstream: branch.upstream.as_deref(),
+            current: head.branch_name() == Some(branch.name.as_str()),
+        }).collect(),
+    })
+}
+
+/// Draw every branch as a tree, marking the checked-out one.
+fn display_branches(repo: &dyn Repository, list: &[BranchInfo], head: &Head) -> Result<()> {
+    println!("{}", "Branches".bright_green().bold());
+    println!();
+
+    let mut rows = Vec::new();
+    if let Head::Detached(id) = head {
+        rows.push((true, format!("(HEAD detached at {})", &id[..7]), id.clone(), branch::summary(repo, id)?, None));
+    }
+    for branch in list {
+        let current = head.branch_name() == Some(branch.name.as_str());
+        rows.push((current, branch.name.clone(), branch.tip.clone(), branch.summary.clone(), branch.upstream.clone()));
+    }
+
+    if rows.is_empty() {
+        let name = head.branch_name().unwrap_or("HEAD");
+        println!("{} {} {}",
+            glyph("└──").cyan(),
+            glyph("●").bright_yellow(),
+            format!("{} (no commits yet)", name).bright_black()
+        );
+        return Ok(());
+    }
+
+    let width = rows.iter().map(|row| row.1.chars().count()).max().unwrap_or(0);
+    for (i, (current, name, tip, summary, upstream)) in rows.iter().enumerate() {
+        let connector = glyph(if i == rows.len() - 1 { "└──" } else { "├──" });
+        let padded = format!("{:<width$}", name, width = width);
+        let (marker, name) = if *current {
+            (glyph("●").bright_yellow(), padded.bright_green().bold())
+        } else {
+            (glyph("○").bright_black(), padded.normal())
+        };
+        let upstream = upstream
+            .as_ref()
+            .map(|upstream| format!(" [{} {}]", glyph("→"), upstream).bright_cyan().to_string())
+            .unwrap_or_default();
+        println!("{} {} {} {} {}{}",
+            connector.cyan(),
+            marker,
+            name,
+            tip[..7].bright_yellow(),
+            summary,
+            upstream
+        );
+    }
+
+    Ok(())
+}
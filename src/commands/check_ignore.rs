@@ -0,0 +1,106 @@
+use anyhow::Result;
+use colored::*;
+use std::collections::HashSet;
+use rit::gitignore::{BUILT_IN_SOURCE, Decision, IgnoreRules};
+use rit::repository;
+use rit::tree::glyph;
+
+/// Explain which ignore rule, if any, decides each path.
+pub fn run(paths: Vec<String>, verbose: bool, non_matching: bool) -> Result<()> {
+    // Check if we're in a repository
+    let Some(repo) = repository::discover()? else {
+        println!("{}", "fatal: not a rit repository".red());
+        return Ok(());
+    };
+
+    println!("{}", "Ignore check".bright_green().bold());
+    println!();
+
+    let location = repo.location();
+    let rules = IgnoreRules::load(location);
+    let tracked: HashSet<String> = repo.load_index()?.entries().map(|entry| entry.path.clone()).collect();
+    let mut results = Vec::new();
+    for path in &paths {
+        let normalized = location.repo_path(path)?;
+        // Ignore rules only apply to untracked files
+        if tracked.contains(&normalized) {
+            if non_matching {
+                results.push((path, Verdict::Tracked));
+            }
+            continue;
+        }
+        let is_dir = path.ends_with('/') || location.work_tree.join(&normalized).is_dir();
+        let decision = rules.decide(&normalized, is_dir);
+        let ignored = decision.as_ref().is_some_and(Decision::is_ignored);
+        if ignored || non_matching {
+            results.push((path, Verdict::Checked { path: normalized, decision }));
+        }
+    }
+
+    if results.is_empty() {
+        println!("{}", "No paths are ignored.".green());
+        return Ok(());
+    }
+
+    // Paths are echoed as typed; rule files and matched directories are
+    // shown relative to the caller's directory too
+    for (i, (path, verdict)) in results.iter().enumerate() {
+        let is_last = i == results.len() - 1;
+        let connector = glyph(if is_last { "└──" } else { "├──" });
+        let (normalized, decision) = match verdict {
+            Verdict::Tracked => {
+                println!("{} {} {}  {}", connector.cyan(), glyph("✓").bright_green(), path.bright_green(), "tracked".bright_black());
+                if verbose {
+                    let child_prefix = if is_last { "    " } else { glyph("│   ") };
+                    println!("{}{} {}", child_prefix, glyph("└──").cyan(), "in the index, so ignore rules don't apply".bright_black());
+                }
+                continue;
+            }
+            Verdict::Checked { path, decision } => (path, decision),
+        };
+        let ignored = decision.as_ref().is_some_and(Decision::is_ignored);
+        let (marker, name, verdict) = if ignored {
+            (glyph("✗").bright_red(), path.bright_red(), "ignored".bright_black())
+        } else {
+            (glyph("✓").bright_green(), path.bright_green(), "not ignored".bright_black())
+        };
+        println!("{} {} {}  {}", connector.cyan(), marker, name, verdict);
+
+        if !verbose {
+            continue;
+        }
+        let child_prefix = if is_last { "    " } else { glyph("│   ") };
+        let detail = match decision {
+            None => "no rule matches".bright_black().to_string(),
+            Some(Decision::Metadata) => format!("{}  {}",
+                BUILT_IN_SOURCE.bright_blue(),
+                "repository metadata (.git/, .rit/) is never walked".bright_black()
+            ),
+            Some(Decision::Rule { rule, path: matched }) => {
+                let source = if rule.source == BUILT_IN_SOURCE {
+                    rule.source.clone()
+                } else {
+                    format!("{}:{}", location.display_path(&rule.source), rule.line)
+                };
+                let pattern = if rule.negated {
+                    rule.pattern.bright_green()
+                } else {
+                    rule.pattern.bright_yellow()
+                };
+                let mut detail = format!("{}  {}", source.bright_blue(), pattern);
+                if matched.trim_end_matches('/') != normalized {
+                    detail.push_str(&format!("  {}", format!("(matched {}/)", location.display_path(matched)).bright_black()));
+                }
+                detail
+            }
+        };
+        println!("{}{} {}", child_prefix, glyph("└──").cyan(), detail);
+    }
+
+    Ok(())
+}
+
+enum Verdict {
+    Tracked,
+    Checked { path: String, decision: Option<Decision> },
+}
@@ -0,0 +1,127 @@
+use anyhow::Result;
+use colored::*;
+use rit::checkout::{self, Blocked, Change};
+use rit::location::Location;
+use rit::refs::Head;
+use rit::repository::{self, Repository};
+use rit::tree::{self, TreeLimits, glyph};
+
+/// `rit switch <branch>`: move HEAD to a branch, or with `--detach` to any
+/// commit, rewriting the working tree and index to match.
+pub fn switch(branch: String, detach: bool) -> Result<()> {
+    // Check if we're in a repository
+    let Some(repo) = repository::discover()? else {
+        println!("{}", "fatal: not a rit repository".red());
+        return Ok(());
+    };
+
+    switch_to(repo.as_ref(), &branch, detach)
+}
+
+/// `rit checkout <commit>` switches like `rit switch` (detaching HEAD when
+/// the target isn't a branch). With paths, it only restores those files
+/// from the commit and leaves HEAD alone.
+pub fn checkout(target: String, paths: Vec<String>, force: bool) -> Result<()> {
+    // Check if we're in a repository
+    let Some(repo) = repository::discover()? else {
+        println!("{}", "fatal: not a rit repository".red());
+        return Ok(());
+    };
+
+    if paths.is_empty() {
+        switch_to(repo.as_ref(), &target, true)
+    } else {
+        restore(repo.as_ref(), &target, &paths, force)
+    }
+}
+
+fn switch_to(repo: &dyn Repository, revision: &str, allow_detach: bool) -> Result<()> {
+    let switched = checkout::switch(repo, revision, allow_detach)
+        .inspect_err(|err| report_blocked(repo.location(), err))?;
+    print_switch_summary(repo.location(), &switched.head, &switched.id, &switched.summary, &switched.changes);
+    Ok(())
+}
+
+fn restore(repo: &dyn Repository, revision: &str, paths: &[String], force: bool) -> Result<()> {
+    let location = repo.location();
+    // Paths are relative to the caller's directory; the top of the working
+    // tree becomes the empty spec, which matches everything
+    let specs = paths.iter()
+        .map(|path| location.repo_path(path))
+        .collect::<Result<Vec<_>>>()?;
+    let restored = checkout::restore(repo, revision, &specs, force)
+        .inspect_err(|err| report_blocked(location, err))?;
+    print_restore_summary(location, &restored.id, &restored.changes);
+    Ok(())
+}
+
+fn print_switch_summary(location: &Location, head: &Head, id: &str, summary: &str, changes: &[(String, Change)]) {
+    match head.branch_name() {
+        Some(name) => println!("{} {}",
+            "Switched to branch".bright_green(),
+            name.bright_yellow().bold()
+        ),
+        None => println!("{} {} {}",
+            "HEAD is now detached at".bright_yellow(),
+            id[..7].bright_yellow().bold(),
+            summary
+        ),
+    }
+    println!();
+    display_changes(location, changes);
+}
+
+fn print_restore_summary(location: &Location, id: &str, changes: &[(String, Change)]) {
+    println!("{} {}",
+        format!("Restored {} file{} from", changes.len(), if changes.len() == 1 { "" } else { "s" }).bright_green(),
+        id[..7].bright_yellow().bold()
+    );
+    println!();
+    display_changes(location, changes);
+}
+
+/// Draw the files a checkout touched, grouped by directory, with paths
+/// relative to the caller's directory.
+fn display_changes(location: &Location, changes: &[(String, Change)]) {
+    if changes.is_empty() {
+        println!("{}", "Working tree already up to date".green());
+        return;
+    }
+
+    println!("{}", "Working tree changes:".bright_blue().bold());
+    let items: Vec<(String, Change)> = changes.iter()
+        .map(|(path, change)| (location.display_path(path), *change))
+        .collect();
+    let lines = tree::render(&items, TreeLimits::default(), |change, name| {
+        let (symbol, name) = match change {
+            Change::Created => ("+".bright_green(), name.bright_green()),
+            Change::Changed => ("~".bright_yellow(), name.bright_yellow()),
+            Change::Removed => ("-".bright_red(), name.bright_red()),
+        };
+        format!("{} {}", symbol, name)
+    });
+    for line in lines {
+        println!("{}", line);
+    }
+
+    let count = |kind: Change| changes.iter().filter(|(_, change)| *change == kind).count();
+    println!();
+    println!("{}, {}, {}",
+        format!("{} created", count(Change::Created)).bright_green(),
+        format!("{} changed", count(Change::Changed)).bright_yellow(),
+        format!("{} removed", count(Change::Removed)).bright_red()
+    );
+}
+
+/// List the files that block a checkout.
+fn report_blocked(location: &Location, err: &anyhow::Error) {
+    let Some(Blocked { paths, .. }) = err.downcast_ref::<Blocked>() else {
+        return;
+    };
+    println!("{}", "Your local changes to these files would be overwritten:".red().bold());
+    for (i, path) in paths.iter().enumerate() {
+        let connector = glyph(if i == paths.len() - 1 { "└──" } else { "├──" });
+        println!("{} {} {}", connector.cyan(), "!".bright_red(), location.display_path(path).bright_red());
+    }
+    println!();
+}
@@ -1,143 +1,168 @@
-use anyhow::Result;
+use anyhow::{Result, bail};
 use colored::*;
-use chrono::{DateTime, Local};
-use std::process::Command;
+use serde::Serialize;
+use rit::commit::{self, CommitOutcome, Created, FileChange};
+use rit::identity::{self, Role};
+use rit::objects::Signature;
+use rit::repository;
+use rit::tree::{self, TreeLimits, glyph};
+use super::output::{self, Format, Person};
 
-pub fn run(message: String) -> Result<()> {
-    let current_dir = std::env::current_dir()?;
-    
-    println!("{}", "Creating commit...".bright_green().bold());
-    println!();
+/// `author` (`Name <email>`) and `date` override the configured author.
+pub fn run(message: String, author: Option<String>, date: Option<String>, format: Format) -> Result<()> {
+    if !format.is_json() {
+        println!("{}", "Creating commit...".bright_green().bold());
+        println!();
+    }
     
     // Check if we're in a repository
-    if !current_dir.join(".git").exists() && !current_dir.join(".rit").exists() {
+    let Some(repo) = repository::discover()? else {
+        if format.is_json() {
+            bail!("not a rit repository");
+        }
         println!("{}", "fatal: not a rit repository".red());
         return Ok(());
+    };
+    
+    let config = repo.config()?;
+    let mut author = match author {
+        Some(author) => identity::parse_ident(&author, identity::date(Role::Author)?)?,
+        None => identity::signature(&config, Role::Author)?,
+    };
+    if let Some(date) = date {
+        author.time = identity::parse_date(&date)?;
     }
+    let committer = identity::signature(&config, Role::Committer)?;
     
-    // Get commit info
-    let timestamp = Local::now();
-    let author = get_author_info()?;
-    
-    // Display commit information in a graphical way
-    println!("{}", "┌─ Commit Information".bright_blue().bold());
-    println!("{} {}", "│ Message:".bright_blue(), message.bright_white().bold());
-    println!("{} {}", "│ Author: ".bright_blue(), author.bright_yellow());
-    println!("{} {}", "│ Date:   ".bright_blue(), timestamp.format("%Y-%m-%d %H:%M:%S").to_string().bright_cyan());
-    println!("{}", "│".bright_blue());
-    
-    // Show files being committed (mock implementation)
-    println!("{}", "│ Files in this commit:".bright_blue());
-    show_commit_tree(&current_dir)?;
-    
-    println!("{}", "└─ Commit created successfully!".bright_green().bold());
-    println!();
-    
-    // Create a simple commit hash (mock)
-    let commit_hash = generate_commit_hash(&message, &timestamp.to_rfc3339())?;
-    println!("{} {}", 
-        "Commit hash:".bright_blue(), 
-        commit_hash.bright_yellow().bold()
-    );
+    let outcome = commit::commit(repo.as_ref(), message, author, committer)?;
+    if format.is_json() {
+        return print_json(&outcome);
+    }
+    let created = match outcome {
+        CommitOutcome::EmptyIndex => {
+            println!("{}", "nothing to commit (use \"rit add\" to track files)".yellow());
+            return Ok(());
+        }
+        CommitOutcome::NothingToCommit => {
+            println!("{}", "nothing to commit, working tree clean".green());
+            return Ok(());
+        }
+        CommitOutcome::Created(created) => created,
+    };
     
-    println!();
-    println!("{}", "Run 'rit log' to see the commit history.".bright_blue());
+    // Paths are shown relative to where rit was run from
+    let changes: Vec<(String, FileChange)> = created.changes
+        .iter()
+        .map(|(path, change)| (repo.location().display_path(path), *change))
+        .collect();
+    let commit = &created.commit;
+    print_commit_summary(commit.message.trim_end(), &commit.author, &commit.committer, &changes, &created.id, created.branch.as_deref());
     
     Ok(())
 }
 
-fn get_author_info() -> Result<String> {
-    // Try to get git config first
-    if let Ok(output) = Command::new("git")
-        .args(&["config", "user.name"])
-        .output() 
-    {
-        if output.status.success() {
-            if let Ok(name) = String::from_utf8(output.stdout) {
-                let name = name.trim();
-                if !name.is_empty() {
-                    if let Ok(output) = Command::new("git")
-                        .args(&["config", "user.email"])
-                        .output() 
-                    {
-                        if output.status.success() {
-                            if let Ok(email) = String::from_utf8(output.stdout) {
-                                let email = email.trim();
-                                if !email.is_empty() {
-                                    return Ok(format!("{} <{}>", name, email));
-                                }
-                            }
-                        }
-                    }
-                    return Ok(name.to_string());
-                }
-            }
-        }
+/// `--format json`: the new commit and the files it changed, or why
+/// nothing was committed.
+fn print_json(outcome: &CommitOutcome) -> Result<()> {
+    #[derive(Serialize)]
+    #[serde(tag = "result", rename_all = "snake_case")]
+    enum Json<'a> {
+        Created {
+            id: &'a str,
+            branch: Option<&'a str>,
+            tree: &'a str,
+            parents: &'a [String],
+            author: Person<'a>,
+            committer: Person<'a>,
+            message: &'a str,
+            changes: Vec<Change<'a>>,
+        },
+        NothingToCommit,
+        EmptyIndex,
     }
-    
-    // Fallback to environment variables
-    if let Ok(user) = std::env::var("USER") {
-        Ok(format!("{} <{}@localhost>", user, user))
-    } else {
-        Ok("Unknown User <unknown@localhost>".to_string())
+    #[derive(Serialize)]
+    struct Change<'a> {
+        path: &'a str,
+        change: FileChange,
     }
+    
+    let json = match outcome {
+        CommitOutcome::EmptyIndex => Json::EmptyIndex,
+        CommitOutcome::NothingToCommit => Json::NothingToCommit,
+        CommitOutcome::Created(created) => {
+            let Created { id, commit, changes, branch } = created.as_ref();
+            Json::Created {
+                id,
+                branch: branch.as_deref(),
+                tree: &commit.tree,
+                parents: &commit.parents,
+                author: Person { name: &commit.author.name, email: &commit.author.email, date: commit.author.time },
+                committer: Person { name: &commit.committer.name, email: &commit.committer.email, date: commit.committer.time },
+                message: &commit.message,
+                changes: changes.iter().map(|(path, change)| Change { path, change: *change }).collect(),
+            }
+        }
+    };
+    output::print_json(&json)
 }
 
-fn show_commit_tree(base_path: &std::path::Path) -> Result<()> {
-    use walkdir::WalkDir;
-    
-    let mut file_count = 0;
-    let files: Vec<_> = WalkDir::new(base_path)
-        .max_depth(3)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_file())
-        .filter(|e| !e.path().to_string_lossy().contains(".git"))
-        .filter(|e| !e.path().to_string_lossy().contains(".rit"))
-        .filter(|e| !e.path().to_string_lossy().contains("target"))
-        .take(5)
-        .collect();
-    
-    for (i, entry) in files.iter().enumerate() {
-        if let Ok(relative_path) = entry.path().strip_prefix(base_path) {
-            let is_last = i == files.len() - 1;
-            let connector = if is_last { "└──" } else { "├──" };
-            
-            println!("{} {} {}", 
-                "│".bright_blue(),
-                connector.cyan(), 
-                relative_path.to_string_lossy().bright_green()
-            );
-            file_count += 1;
+fn show_staged_changes(changes: &[(String, FileChange)]) {
+    let lines = tree::render(changes, TreeLimits::default(), |change, file_name| {
+        match change {
+            FileChange::Added => format!("{} {}", "A".bright_green(), file_name.bright_green()),
+            FileChange::Modified => format!("{} {}", "M".bright_yellow(), file_name.bright_yellow()),
+            FileChange::Deleted => format!("{} {}", "D".bright_red(), file_name.bright_red()),
         }
+    });
+    for line in lines {
+        println!("{} {}", glyph("│").bright_blue(), line);
     }
     
-    if files.len() == 5 {
-        println!("{} {} {}", 
-            "│".bright_blue(),
-            "└── ...".cyan(), 
-            "(and more files)".bright_black()
-        );
+    let count = |kind: fn(&FileChange) -> bool| changes.iter().filter(|(_, change)| kind(change)).count();
+    println!("{} {} {}, {}, {}",
+        glyph("│").bright_blue(),
+        format!("{} {} changed:", changes.len(), if changes.len() == 1 { "file" } else { "files" }).bright_blue(),
+        format!("{} added", count(|change| matches!(change, FileChange::Added))).bright_green(),
+        format!("{} modified", count(|change| matches!(change, FileChange::Modified))).bright_yellow(),
+        format!("{} deleted", count(|change| matches!(change, FileChange::Deleted))).bright_red()
+    );
+}
+
+/// Display commit information in a graphical way
+fn print_commit_summary(
+    message: &str,
+    author: &Signature,
+    committer: &Signature,
+    changes: &[(String, FileChange)],
+    commit_id: &str,
+    branch: Option<&str>,
+) {
+    println!("{}", format!("{} Commit Information", glyph("┌─")).bright_blue().bold());
+    println!("{} {}", format!("{} Message:", glyph("│")).bright_blue(), message.bright_white().bold());
+    println!("{} {}", format!("{} Author: ", glyph("│")).bright_blue(), identity(author).bright_yellow());
+    // Only worth a line when someone else recorded the change
+    if (&committer.name, &committer.email) != (&author.name, &author.email) {
+        println!("{} {}", format!("{} Commit: ", glyph("│")).bright_blue(), identity(committer).bright_yellow());
     }
+    println!("{} {}", format!("{} Date:   ", glyph("│")).bright_blue(), author.time.format("%Y-%m-%d %H:%M:%S").to_string().bright_cyan());
+    println!("{}", glyph("│").bright_blue());
     
-    if file_count == 0 {
-        println!("{} {}", 
-            "│".bright_blue(),
-            "└── No files to commit".yellow()
-        );
-    }
+    println!("{}", format!("{} Files in this commit:", glyph("│")).bright_blue());
+    show_staged_changes(changes);
     
-    Ok(())
-}
-
-fn generate_commit_hash(message: &str, timestamp: &str) -> Result<String> {
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hash, Hasher};
+    println!("{}", format!("{} Commit created successfully!", glyph("└─")).bright_green().bold());
+    println!();
     
-    let mut hasher = DefaultHasher::new();
-    message.hash(&mut hasher);
-    timestamp.hash(&mut hasher);
+    println!("{} {} {}", 
+        "Commit hash:".bright_blue(), 
+        commit_id[..7].bright_yellow().bold(),
+        format!("({})", branch.unwrap_or("detached HEAD")).bright_green()
+    );
     
-    let hash = hasher.finish();
-    Ok(format!("{:x}", hash)[..8].to_string())
-} 
\ No newline at end of file
+    println!();
+    println!("{}", "Run 'rit log' to see the commit history.".bright_blue());
+}
+
+fn identity(signature: &Signature) -> String {
+    format!("{} <{}>", signature.name, signature.email)
+}
@@ -0,0 +1,91 @@
+use anyhow::{Result, anyhow, bail};
+use colored::*;
+use std::path::PathBuf;
+use rit::config::{self, Config, Level, ValueType};
+use rit::repository;
+use rit::tree::glyph;
+
+/// What `rit config` was asked to do.
+pub enum ConfigAction {
+    Get { key: String, all: bool },
+    Set { key: String, value: String },
+    Unset { key: String },
+    List,
+}
+
+/// Read or change settings. Reads see every level merged unless one is
+/// picked; writes go to the repository's config unless told otherwise.
+pub fn run(action: ConfigAction, level: Option<Level>, value_type: Option<ValueType>) -> Result<()> {
+    match action {
+        ConfigAction::Get { key, all } => {
+            let config = load(level)?;
+            let values = if all { config.get_all(&key) } else { config.get(&key).into_iter().collect() };
+            if values.is_empty() {
+                bail!("config key '{}' is not set", key);
+            }
+            for value in values {
+                match value_type {
+                    Some(value_type) => println!("{}", value_type.normalize(&key, value)?),
+                    None => println!("{}", value),
+                }
+            }
+        }
+        ConfigAction::List => {
+            let config = load(level)?;
+            for (key, value) in config.entries() {
+                println!("{}={}", key.cyan(), value);
+            }
+        }
+        ConfigAction::Set { key, value } => {
+            let level = level.unwrap_or(Level::Local);
+            let value = match value_type {
+                Some(value_type) => value_type.normalize(&key, &value)?,
+                None => value,
+            };
+            config::set_value(&path(level)?, &key, &value)?;
+            println!("{} {} {} {} {}",
+                glyph("✓").bright_green(),
+                "Set".green(),
+                key.bright_green(),
+                format!("= {}", value).bright_white(),
+                format!("({})", level.name()).bright_black()
+            );
+        }
+        ConfigAction::Unset { key } => {
+            let level = level.unwrap_or(Level::Local);
+            if !config::unset_value(&path(level)?, &key)? {
+                bail!("config key '{}' is not set in the {} config", key, level.name());
+            }
+            println!("{} {} {} {}",
+                glyph("✓").bright_green(),
+                "Unset".green(),
+                key.bright_green(),
+                format!("({})", level.name()).bright_black()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// One level's file, or all of them merged.
+fn load(level: Option<Level>) -> Result<Config> {
+    match level {
+        Some(level) => Config::load(&path(level)?),
+        None => match repository::discover()? {
+            Some(repo) => repo.config(),
+            None => Config::load_all(None),
+        },
+    }
+}
+
+fn path(level: Level) -> Result<PathBuf> {
+    match level {
+        Level::System => Ok(config::system_path()),
+        Level::Global => config::global_path().ok_or_else(|| anyhow!("cannot find the global config: $HOME is not set")),
+        Level::Local => match repository::discover()? {
+            Some(repo) => Ok(repo.config_path()),
+            None => bail!("not a rit repository (use --global or --system outside one)"),
+        },
+    }
+}
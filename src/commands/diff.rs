@@ -0,0 +1,702 @@
+use anyhow::{Result, bail};
+use colored::*;
+use std::io::IsTerminal;
+use terminal_size::{Width, terminal_size};
+use unicode_width::UnicodeWidthChar;
+use rit::binary;
+use rit::diff::{self, Algorithm, Blob, DiffStatus, Edit, FileDiff, Hunk, RenameOptions, Span};
+use rit::repository;
+use regex::Regex;
+use rit::tree::{self, TreeLimits, glyph};
+
+/// How `rit diff` shows the changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum View {
+    /// A unified diff in git's format.
+    Unified,
+    /// Old and new side by side in two columns.
+    SideBySide,
+    /// Changed lines per file, as bars in a file tree.
+    Stat,
+    /// A unified diff that marks changed words instead of whole lines.
+    Words(WordStyle),
+}
+
+/// How `--word-diff` marks the changed words.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordStyle {
+    /// Colors only.
+    Color,
+    /// `[-removed-]{+added+}`.
+    Plain,
+    /// One piece per line behind ` `, `-` or `+`, with `~` for line ends,
+    /// for scripts to parse.
+    Porcelain,
+}
+
+/// What `rit diff` was asked to compare and how to show it.
+pub struct DiffArgs {
+    /// Compare against the index instead of the working tree.
+    pub staged: bool,
+    /// Revisions, `a..b` ranges and paths, told apart the way git does.
+    pub args: Vec<String>,
+    /// Paths given after `--`.
+    pub paths: Vec<String>,
+    /// Lines of context around each change (`-U`).
+    pub context: Option<usize>,
+    pub algorithm: Option<Algorithm>,
+    pub view: View,
+    /// What counts as a word for `--word-diff` (`--word-diff-regex`).
+    pub word_regex: Option<String>,
+    /// Rename detection from `-M`, `-C` or `--no-renames` (`Some(None)`);
+    /// `None` leaves it to `diff.renames`.
+    pub renames: Option<Option<RenameOptions>>,
+}
+
+/// Lines of context git shows by default.
+const DEFAULT_CONTEXT: usize = 3;
+
+pub fn run(args: DiffArgs) -> Result<()> {
+    // Check if we're in a repository
+    let Some(repo) = repository::discover()? else {
+        println!("{}", "fatal: not a rit repository".red());
+        return Ok(());
+    };
+    let repo = repo.as_ref();
+
+    let config = repo.config()?;
+    let algorithm = match args.algorithm {
+        Some(algorithm) => algorithm,
+        None => config.get("diff.algorithm").map(Algorithm::parse).transpose()?.unwrap_or_default(),
+    };
+    let context = match args.context {
+        Some(context) => context,
+        None => config.get_int("diff.context")?.map_or(DEFAULT_CONTEXT, |context| context.max(0) as usize),
+    };
+
+    let (old, new, paths) = diff::sources(repo, args.staged, &args.args, &args.paths)?;
+    let mut files = diff::diff_files(repo, &old, &new, &paths)?;
+    let renames = match args.renames {
+        Some(renames) => renames,
+        None => RenameOptions::from_config("diff.renames", config.get("diff.renames"))?,
+    };
+    if let Some(renames) = renames {
+        files = diff::detect_renames(files, renames);
+    }
+    match args.view {
+        View::Unified => {
+            for file in &files {
+                print_file(file, algorithm, context, None);
+            }
+        }
+        View::Words(style) => {
+            let pattern = args.word_regex.as_deref().or(config.get("diff.wordregex"));
+            let regex = match pattern {
+                Some(pattern) => match Regex::new(pattern) {
+                    Ok(regex) => Some(regex),
+                    Err(err) => bail!("invalid word regex '{}': {}", pattern, err),
+                },
+                None => None,
+            };
+            for file in &files {
+                print_file(file, algorithm, context, Some((style, regex.as_ref())));
+            }
+        }
+        View::SideBySide => {
+            let width = terminal_width();
+            for file in &files {
+                print_side_by_side(file, algorithm, context, width);
+            }
+        }
+        View::Stat => print_stat(&files, algorithm, terminal_width()),
+    }
+
+    Ok(())
+}
+
+/// Print one file as a git-style unified diff, with changed lines
+/// compared word by word when a word style is given.
+fn print_file(file: &FileDiff, algorithm: Algorithm, context: usize, words: Option<(WordStyle, Option<&Regex>)>) {
+    let path = &file.path;
+    let old_path = file.origin.as_ref().map_or(path, |origin| &origin.path);
+    println!("{}", format!("diff --git a/{} b/{}", old_path, path).bright_white().bold());
+    let short = |blob: Option<&Blob>| blob.map_or_else(|| "0".repeat(7), |blob| blob.id[..7].to_string());
+    match (&file.old, &file.new) {
+        (None, Some(new)) => println!("{}", format!("new file mode {:o}", new.mode).bright_white().bold()),
+        (Some(old), None) => println!("{}", format!("deleted file mode {:o}", old.mode).bright_white().bold()),
+        (Some(old), Some(new)) if old.mode != new.mode => {
+            println!("{}", format!("old mode {:o}", old.mode).bright_white().bold());
+            println!("{}", format!("new mode {:o}", new.mode).bright_white().bold());
+        }
+        _ => {}
+    }
+    if let Some(origin) = &file.origin {
+        let verb = if file.status == DiffStatus::Copied { "copy" } else { "rename" };
+        println!("{}", format!("similarity index {}%", origin.similarity).bright_white().bold());
+        println!("{}", format!("{} from {}", verb, origin.path).bright_white().bold());
+        println!("{}", format!("{} to {}", verb, path).bright_white().bold());
+    }
+    let same_contents = matches!((&file.old, &file.new), (Some(old), Some(new)) if old.id == new.id);
+    if same_contents {
+        return;
+    }
+    let mode_suffix = match (&file.old, &file.new) {
+        (Some(old), Some(new)) if old.mode == new.mode => format!(" {:o}", old.mode),
+        _ => String::new(),
+    };
+    println!("{}", format!("index {}..{}{}", short(file.old.as_ref()), short(file.new.as_ref()), mode_suffix).bright_white().bold());
+
+    let header_path = |side: &str, path: &str, present: bool| if present { format!("{}/{}", side, path) } else { "/dev/null".to_string() };
+    let old_header = header_path("a", old_path, file.status != DiffStatus::Added);
+    let new_header = header_path("b", path, file.status != DiffStatus::Deleted);
+    if file.is_binary() {
+        println!("Binary files {} and {} differ", old_header, new_header);
+        println!("{}", binary_summary(file).bright_black());
+        return;
+    }
+
+    let old_lines = diff::lines(file.old_data());
+    let new_lines = diff::lines(file.new_data());
+    let edits = diff::diff(&old_lines, &new_lines, algorithm);
+    let hunks = diff::hunks(&edits, context);
+    if hunks.is_empty() {
+        return;
+    }
+    println!("{}", format!("--- {}", old_header).bright_white().bold());
+    println!("{}", format!("+++ {}", new_header).bright_white().bold());
+
+    for hunk in hunks {
+        println!("{}", hunk.header().bright_cyan());
+        if let Some((style, regex)) = words {
+            print_word_hunk(&hunk, &old_lines, &new_lines, style, regex, algorithm);
+            continue;
+        }
+        for edit in &hunk.edits {
+            match *edit {
+                Edit::Equal { old, .. } => print_line(' ', old_lines[old], |text| text.normal()),
+                Edit::Delete { old } => print_line('-', old_lines[old], |text| text.bright_red()),
+                Edit::Insert { new } => print_line('+', new_lines[new], |text| text.bright_green()),
+            }
+        }
+    }
+}
+
+/// The size of each side of a binary file, with the dimensions of PNG and
+/// JPEG images: `12.0 KiB, 640x480 → 13.4 KiB, 800x600`.
+fn binary_summary(file: &FileDiff) -> String {
+    let describe = |blob: &Blob| match binary::image_size(&blob.data) {
+        Some((width, height)) => format!("{}, {}x{}", human_size(blob.data.len()), width, height),
+        None => human_size(blob.data.len()),
+    };
+    [&file.old, &file.new]
+        .into_iter()
+        .flatten()
+        .map(describe)
+        .collect::<Vec<_>>()
+        .join(&format!(" {} ", glyph("→")))
+}
+
+/// `812 bytes`, `3.4 KiB`, `1.2 MiB`.
+fn human_size(bytes: usize) -> String {
+    const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+    if bytes < 1024 {
+        return format!("{} byte{}", bytes, if bytes == 1 { "" } else { "s" });
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Print a diff line, noting when it is the last line of a file that
+/// doesn't end in a newline.
+fn print_line(marker: char, line: &[u8], paint: impl Fn(&str) -> ColoredString) {
+    let text = String::from_utf8_lossy(line);
+    let (text, newline) = match text.strip_suffix('\n') {
+        Some(text) => (text, true),
+        None => (text.as_ref(), false),
+    };
+    println!("{}", paint(&format!("{}{}", marker, text)));
+    if !newline {
+        println!("{}", "\\ No newline at end of file".bright_black());
+    }
+}
+
+/// Print a hunk with its changed lines compared word by word. Context
+/// lines are printed as they are; each run of removed and added lines
+/// between them is word-diffed as one block.
+fn print_word_hunk(hunk: &Hunk, old_lines: &[&[u8]], new_lines: &[&[u8]], style: WordStyle, regex: Option<&Regex>, algorithm: Algorithm) {
+    let mut old = String::new();
+    let mut new = String::new();
+    let mut out = String::new();
+    for edit in &hunk.edits {
+        match *edit {
+            Edit::Equal { old: line, .. } => {
+                flush_words(&mut old, &mut new, style, regex, algorithm, &mut out);
+                let text = line_text(old_lines[line]);
+                match style {
+                    WordStyle::Porcelain => out.push_str(&format!(" {}\n~\n", text)),
+                    _ => out.push_str(&format!("{}\n", text)),
+                }
+            }
+            Edit::Delete { old: line } => push_line(&mut old, old_lines[line]),
+            Edit::Insert { new: line } => push_line(&mut new, new_lines[line]),
+        }
+    }
+    flush_words(&mut old, &mut new, style, regex, algorithm, &mut out);
+    print!("{}", out);
+}
+
+/// Add a line to a block being collected, ending it with a newline even
+/// when the file doesn't.
+fn push_line(block: &mut String, line: &[u8]) {
+    block.push_str(&line_text(line));
+    block.push('\n');
+}
+
+fn flush_words(old: &mut String, new: &mut String, style: WordStyle, regex: Option<&Regex>, algorithm: Algorithm, out: &mut String) {
+    for change in diff::word_diff(old, new, regex, algorithm) {
+        match change {
+            diff::WordChange::Same(text) => write_words(out, text, style, " ", None, |text| text.normal()),
+            diff::WordChange::Deleted(text) => write_words(out, text, style, "-", Some(("[-", "-]")), |text| text.red()),
+            diff::WordChange::Inserted(text) => write_words(out, text, style, "+", Some(("{+", "+}")), |text| text.green()),
+        }
+    }
+    old.clear();
+    new.clear();
+}
+
+/// Write one piece of a word diff line by line, marking each line's part
+/// of it in the given style.
+fn write_words(
+    out: &mut String,
+    text: &str,
+    style: WordStyle,
+    porcelain: &str,
+    brackets: Option<(&str, &str)>,
+    paint: fn(&str) -> ColoredString,
+) {
+    let mut lines = text.split('\n').peekable();
+    while let Some(line) = lines.next() {
+        if !line.is_empty() {
+            let marked = match style {
+                WordStyle::Color => paint(line).to_string(),
+                WordStyle::Plain => match brackets {
+                    Some((open, close)) => paint(&format!("{}{}{}", open, line, close)).to_string(),
+                    None => line.to_string(),
+                },
+                WordStyle::Porcelain => format!("{}{}\n", porcelain, paint(line)),
+            };
+            out.push_str(&marked);
+        }
+        if lines.peek().is_some() {
+            out.push_str(if style == WordStyle::Porcelain { "~\n" } else { "\n" });
+        }
+    }
+}
+
+/// Columns to lay output out in: the terminal's width, `$COLUMNS` when
+/// output isn't a terminal, or 80.
+fn terminal_width() -> usize {
+    let width = match terminal_size() {
+        Some((Width(width), _)) if std::io::stdout().is_terminal() => width as usize,
+        _ => std::env::var("COLUMNS").ok().and_then(|columns| columns.parse().ok()).unwrap_or(80),
+    };
+    width.max(40)
+}
+
+/// The lines of a file and the edits between them.
+fn line_diff(file: &FileDiff, algorithm: Algorithm) -> (Vec<&[u8]>, Vec<&[u8]>, Vec<Edit>) {
+    let old_lines = diff::lines(file.old_data());
+    let new_lines = diff::lines(file.new_data());
+    let edits = diff::diff(&old_lines, &new_lines, algorithm);
+    (old_lines, new_lines, edits)
+}
+
+/// Print one file as two columns, old on the left and new on the right,
+/// with each hunk under a box-drawn `@@` separator and the changed words
+/// of paired lines highlighted.
+fn print_side_by_side(file: &FileDiff, algorithm: Algorithm, context: usize, width: usize) {
+    let ids = match (&file.old, &file.new) {
+        (Some(old), Some(new)) if old.id != new.id => format!("{} {} {}", &old.id[..7], glyph("→"), &new.id[..7]),
+        (Some(blob), _) | (_, Some(blob)) => blob.id[..7].to_string(),
+        (None, None) => String::new(),
+    };
+    let modes = match (&file.old, &file.new) {
+        (Some(old), Some(new)) if old.mode != new.mode => format!("  mode {:o} {} {:o}", old.mode, glyph("→"), new.mode),
+        _ => String::new(),
+    };
+    println!("{} {} {}  {}{}",
+        glyph("┌─").bright_blue(),
+        status_letter(file.status),
+        shown_path(file, &file.path),
+        ids.bright_black(),
+        modes.bright_black()
+    );
+
+    if file.is_binary() {
+        println!("{}", format!("{} binary files differ: {}", glyph("└─"), binary_summary(file)).bright_black());
+        println!();
+        return;
+    }
+
+    let (old_lines, new_lines, edits) = line_diff(file, algorithm);
+    let hunks = diff::hunks(&edits, context);
+    // Each row is `│` + left cell + `│` + right cell
+    let column = (width - 2) / 2;
+    let number_width = old_lines.len().max(new_lines.len()).max(1).to_string().len();
+    let layout = Layout { column, number_width };
+
+    if hunks.is_empty() {
+        println!("{}", format!("{} no changes to the contents", glyph("└─")).bright_black());
+        println!();
+        return;
+    }
+    for (i, hunk) in hunks.iter().enumerate() {
+        let title = format!("{} {} ", glyph("├─"), hunk.header());
+        let fill = (column + 1).saturating_sub(title.chars().count());
+        println!("{}{}{}{}",
+            glyph("├─").bright_blue(),
+            format!(" {} ", hunk.header()).bright_cyan(),
+            glyph("─").repeat(fill).bright_blue(),
+            format!("{}{}", glyph(if i == 0 { "┬" } else { "┼" }), glyph("─").repeat(column)).bright_blue()
+        );
+        print_hunk_rows(hunk, &old_lines, &new_lines, layout);
+    }
+    println!("{}", format!("{}{}{}{}", glyph("└"), glyph("─").repeat(column), glyph("┴"), glyph("─").repeat(column)).bright_blue());
+    println!();
+}
+
+#[derive(Clone, Copy)]
+struct Layout {
+    /// Width of each cell, line number included.
+    column: usize,
+    number_width: usize,
+}
+
+/// One side of a side-by-side row.
+enum Cell<'a> {
+    Empty,
+    Context(usize, &'a [u8]),
+    Deleted(usize, &'a [u8], Option<Vec<Span>>),
+    Inserted(usize, &'a [u8], Option<Vec<Span>>),
+}
+
+fn print_hunk_rows(hunk: &Hunk, old_lines: &[&[u8]], new_lines: &[&[u8]], layout: Layout) {
+    let edits = &hunk.edits;
+    let mut i = 0;
+    while i < edits.len() {
+        if let Edit::Equal { old, new } = edits[i] {
+            print_row(Cell::Context(old, old_lines[old]), Cell::Context(new, new_lines[new]), layout);
+            i += 1;
+            continue;
+        }
+
+        // Deletions come before insertions in each changed stretch; pair
+        // them up row by row
+        let mut deleted = Vec::new();
+        while let Some(&Edit::Delete { old }) = edits.get(i) {
+            deleted.push(old);
+            i += 1;
+        }
+        let mut inserted = Vec::new();
+        while let Some(&Edit::Insert { new }) = edits.get(i) {
+            inserted.push(new);
+            i += 1;
+        }
+        for row in 0..deleted.len().max(inserted.len()) {
+            let old = deleted.get(row).map(|&old| (old, old_lines[old]));
+            let new = inserted.get(row).map(|&new| (new, new_lines[new]));
+            let (old_spans, new_spans) = match (old, new) {
+                (Some((_, old_text)), Some((_, new_text))) => {
+                    match diff::inline_diff(&line_text(old_text), &line_text(new_text)) {
+                        Some((old_spans, new_spans)) => (Some(old_spans), Some(new_spans)),
+                        None => (None, None),
+                    }
+                }
+                _ => (None, None),
+            };
+            print_row(
+                old.map_or(Cell::Empty, |(number, text)| Cell::Deleted(number, text, old_spans)),
+                new.map_or(Cell::Empty, |(number, text)| Cell::Inserted(number, text, new_spans)),
+                layout,
+            );
+        }
+    }
+}
+
+fn print_row(left: Cell, right: Cell, layout: Layout) {
+    println!("{}{}{}{}",
+        glyph("│").bright_blue(),
+        render_cell(&left, layout),
+        glyph("│").bright_blue(),
+        render_cell(&right, layout)
+    );
+}
+
+/// `  12 - text`, padded or cut to the cell width.
+fn render_cell(cell: &Cell, layout: Layout) -> String {
+    let text_width = layout.column.saturating_sub(layout.number_width + 3);
+    let (number, marker, line, spans, paint): (usize, ColoredString, &[u8], Option<&[Span]>, Paint) = match cell {
+        Cell::Empty => return " ".repeat(layout.column),
+        Cell::Context(number, line) => (*number, " ".normal(), line, None, Paint::Context),
+        Cell::Deleted(number, line, spans) => (*number, "-".bright_red(), line, spans.as_deref(), Paint::Deleted),
+        Cell::Inserted(number, line, spans) => (*number, "+".bright_green(), line, spans.as_deref(), Paint::Inserted),
+    };
+
+    let text = line_text(line);
+    let text = text.as_str();
+    let whole = [Span { range: 0..text.len(), changed: false }];
+    let spans = spans.unwrap_or(&whole);
+    let body = match fit(text, spans, text_width, paint) {
+        Some(body) => body,
+        // Lines that don't fit end in an ellipsis
+        None => {
+            let ellipsis = glyph("…");
+            let room = text_width.saturating_sub(ellipsis.chars().count());
+            let body = fit(text, spans, room, paint).unwrap_or_else(|| truncated(text, spans, room, paint));
+            format!("{}{}", body, ellipsis.bright_black())
+        }
+    };
+
+    format!("{:>width$} {} {}",
+        (number + 1).to_string().bright_black(),
+        marker,
+        body,
+        width = layout.number_width
+    )
+}
+
+/// A line as shown in a cell, without its line ending.
+fn line_text(line: &[u8]) -> String {
+    String::from_utf8_lossy(line).trim_end_matches(['\n', '\r']).to_string()
+}
+
+#[derive(Clone, Copy)]
+enum Paint {
+    Context,
+    Deleted,
+    Inserted,
+}
+
+impl Paint {
+    fn apply(self, text: &str, changed: bool) -> ColoredString {
+        match (self, changed) {
+            (Paint::Context, _) => text.normal(),
+            (Paint::Deleted, false) => text.bright_red(),
+            (Paint::Deleted, true) => text.bright_white().on_red(),
+            (Paint::Inserted, false) => text.bright_green(),
+            (Paint::Inserted, true) => text.bright_white().on_green(),
+        }
+    }
+}
+
+/// `text` painted span by span and padded to `width` columns, or `None`
+/// when it is wider.
+fn fit(text: &str, spans: &[Span], width: usize, paint: Paint) -> Option<String> {
+    let expanded: usize = text.chars().map(column_width).sum();
+    if expanded > width {
+        return None;
+    }
+    Some(truncated(text, spans, width, paint))
+}
+
+/// As much of `text` as fits in `width` columns, painted span by span, with
+/// tabs expanded and padded to the full width.
+fn truncated(text: &str, spans: &[Span], width: usize, paint: Paint) -> String {
+    let mut out = String::new();
+    let mut used = 0;
+    for span in spans {
+        let mut piece = String::new();
+        let mut full = false;
+        for c in text[span.range.clone()].chars() {
+            let columns = column_width(c);
+            if used + columns > width {
+                full = true;
+                break;
+            }
+            if c == '\t' {
+                piece.push_str(&" ".repeat(TAB_WIDTH));
+            } else {
+                piece.push(c);
+            }
+            used += columns;
+        }
+        out.push_str(&paint.apply(&piece, span.changed).to_string());
+        if full {
+            break;
+        }
+    }
+    out.push_str(&" ".repeat(width - used));
+    out
+}
+
+const TAB_WIDTH: usize = 4;
+
+fn column_width(c: char) -> usize {
+    if c == '\t' { TAB_WIDTH } else { c.width().unwrap_or(0) }
+}
+
+fn status_letter(status: DiffStatus) -> ColoredString {
+    match status {
+        DiffStatus::Added => "A".green().bold(),
+        DiffStatus::Deleted => "D".red().bold(),
+        DiffStatus::Modified => "M".yellow().bold(),
+        DiffStatus::Renamed => "R".cyan().bold(),
+        DiffStatus::Copied => "C".cyan().bold(),
+    }
+}
+
+fn path_colored(path: &str, status: DiffStatus) -> ColoredString {
+    match status {
+        DiffStatus::Added => path.green(),
+        DiffStatus::Deleted => path.red(),
+        DiffStatus::Modified => path.yellow(),
+        DiffStatus::Renamed | DiffStatus::Copied => path.cyan(),
+    }
+}
+
+/// A file's path (or `name` in a tree) colored by its status, with where a
+/// renamed or copied file came from: `old → new (87%)`.
+fn shown_path(file: &FileDiff, name: &str) -> String {
+    match &file.origin {
+        Some(origin) => {
+            let similarity = if origin.similarity < 100 { format!(" ({}%)", origin.similarity) } else { String::new() };
+            format!("{} {} {}{}",
+                origin.path.cyan(),
+                glyph("→"),
+                path_colored(name, file.status).bold(),
+                similarity.bright_black()
+            )
+        }
+        None => path_colored(name, file.status).bold().to_string(),
+    }
+}
+
+/// Lines added and removed in one file, for `--stat`.
+struct FileStat {
+    added: usize,
+    deleted: usize,
+    status: DiffStatus,
+    /// Sizes of a binary file's two sides, shown instead of a bar.
+    binary: Option<(usize, usize)>,
+    /// Where a renamed or copied file came from.
+    from: Option<String>,
+    /// Directory levels above the file, which push its name right in the
+    /// tree.
+    depth: usize,
+}
+
+impl FileStat {
+    /// What comes before the file name: `old/path → ` for a rename.
+    fn prefix(&self) -> String {
+        self.from.as_ref().map_or_else(String::new, |from| format!("{} {} ", from, glyph("→")))
+    }
+}
+
+/// Print the changed files as a tree, each with its count of changed lines
+/// and a bar of `+` and `-` scaled to fit the terminal, then git's summary
+/// line.
+fn print_stat(files: &[FileDiff], algorithm: Algorithm, width: usize) {
+    if files.is_empty() {
+        return;
+    }
+
+    let mut items: Vec<(String, FileStat)> = Vec::new();
+    for file in files {
+        let (added, deleted) = if file.is_binary() {
+            (0, 0)
+        } else {
+            let (_, _, edits) = line_diff(file, algorithm);
+            let added = edits.iter().filter(|edit| matches!(edit, Edit::Insert { .. })).count();
+            let deleted = edits.iter().filter(|edit| matches!(edit, Edit::Delete { .. })).count();
+            (added, deleted)
+        };
+        let binary = file.is_binary().then(|| (file.old_data().len(), file.new_data().len()));
+        // A typechange comes as a deletion and an addition of one path
+        if let Some((_, stat)) = items.iter_mut().find(|(path, _)| *path == file.path) {
+            stat.added += added;
+            stat.deleted += deleted;
+            stat.status = DiffStatus::Modified;
+            if let Some((_, new_size)) = binary {
+                let old_size = stat.binary.map_or(0, |(old_size, _)| old_size);
+                stat.binary = Some((old_size, new_size));
+            }
+            continue;
+        }
+        let depth = file.path.matches('/').count();
+        let from = file.origin.as_ref().map(|origin| origin.path.clone());
+        items.push((file.path.clone(), FileStat { added, deleted, status: file.status, binary, from, depth }));
+    }
+
+    // Names are padded so the bars line up whatever the depth
+    let name_width = |path: &str| path.rsplit('/').next().unwrap_or(path).chars().count();
+    let name_column = items.iter()
+        .map(|(path, stat)| 4 * stat.depth + stat.prefix().chars().count() + name_width(path))
+        .max()
+        .unwrap_or(0);
+    let largest = items.iter().map(|(_, stat)| stat.added + stat.deleted).max().unwrap_or(0);
+    let count_width = largest.max(1).to_string().len();
+    // Tree prefix, name, ` | `, count and a space come before the bar
+    let room = width.saturating_sub(4 + name_column + 3 + count_width + 1).max(10);
+    let scale = |count: usize| {
+        if largest <= room {
+            count
+        } else {
+            // Never round a change away entirely
+            (count * room).div_ceil(largest)
+        }
+    };
+
+    let lines = tree::render(&items, TreeLimits::unlimited(), |stat, file_name| {
+        let prefix = stat.prefix();
+        let padding = name_column - 4 * stat.depth - prefix.chars().count() - file_name.chars().count();
+        if let Some((old_size, new_size)) = stat.binary {
+            return format!("{}{}{} {} {} {} {} {} bytes",
+                prefix.cyan(),
+                path_colored(file_name, stat.status),
+                " ".repeat(padding),
+                glyph("│").bright_black(),
+                "Bin".bright_black(),
+                old_size,
+                glyph("→"),
+                new_size
+            );
+        }
+        let changed = stat.added + stat.deleted;
+        let (plus, minus) = if changed == 0 {
+            (0, 0)
+        } else {
+            let bar = scale(changed);
+            let plus = if stat.added == 0 { 0 } else { (bar * stat.added / changed).max(1) };
+            (plus, bar - plus)
+        };
+        format!("{}{}{} {} {:>width$} {}{}",
+            prefix.cyan(),
+            path_colored(file_name, stat.status),
+            " ".repeat(padding),
+            glyph("│").bright_black(),
+            changed,
+            "+".repeat(plus).green(),
+            "-".repeat(minus).red(),
+            width = count_width
+        )
+    });
+    for line in lines {
+        println!("{}", line);
+    }
+
+    let added: usize = items.iter().map(|(_, stat)| stat.added).sum();
+    let deleted: usize = items.iter().map(|(_, stat)| stat.deleted).sum();
+    let mut summary = format!(" {} file{} changed", items.len(), if items.len() == 1 { "" } else { "s" });
+    if added > 0 || deleted == 0 {
+        summary.push_str(&format!(", {} insertion{}(+)", added, if added == 1 { "" } else { "s" }));
+    }
+    if deleted > 0 || added == 0 {
+        summary.push_str(&format!(", {} deletion{}(-)", deleted, if deleted == 1 { "" } else { "s" }));
+    }
+    println!("{}", summary);
+}
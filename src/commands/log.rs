@@ -1,114 +1,406 @@
-use anyhow::Result;
+use anyhow::{Result, bail};
 use colored::*;
-use chrono::{DateTime, Local};
+use chrono::{DateTime, FixedOffset, Local};
+use serde::Serialize;
+use rit::log::{self, LogOptions};
+use rit::repository::{self, Repository};
+use rit::tree::glyph;
+use super::output::{self, Format, Person};
+
+pub fn run(mut options: LogOptions, format: Format) -> Result<()> {
+    if !format.is_json() {
+        println!("{}", "Commit History".bright_green().bold());
+        println!();
+    }
 
-pub fn run() -> Result<()> {
-    let current_dir = std::env::current_dir()?;
-    
-    println!("{}", "Commit History".bright_green().bold());
-    println!();
-    
     // Check if we're in a repository
-    if !current_dir.join(".git").exists() && !current_dir.join(".rit").exists() {
+    let Some(repo) = repository::discover()? else {
+        if format.is_json() {
+            bail!("not a rit repository");
+        }
         println!("{}", "fatal: not a rit repository".red());
         return Ok(());
+    };
+
+    // Path filters are typed relative to the caller's directory
+    options.paths = options.paths.iter()
+        .map(|path| repo.location().repo_path(path))
+        .collect::<Result<_>>()?;
+
+    if format.is_json() {
+        return print_json(repo.as_ref(), &options);
     }
-    
-    // Try to get git log first, if available
-    if current_dir.join(".git").exists() {
-        display_git_log()?;
-    } else {
-        display_mock_log()?;
+    display_log(repo.as_ref(), &options)
+}
+
+/// `--format json`: one commit per line, printed as the history is walked.
+/// Parents are the commit's real parents, even when filters hide them.
+fn print_json(repo: &dyn Repository, options: &LogOptions) -> Result<()> {
+    #[derive(Serialize)]
+    struct Json<'a> {
+        id: &'a str,
+        parents: &'a [String],
+        tree: &'a str,
+        author: Person<'a>,
+        committer: Person<'a>,
+        message: &'a str,
+        refs: &'a [String],
+    }
+
+    let Some(history) = log::log(repo, options)? else {
+        return Ok(());
+    };
+    let labels = log::labels(repo)?;
+    for commit in history {
+        let commit = commit?;
+        output::print_json(&Json {
+            id: &commit.id,
+            parents: &commit.parents,
+            tree: &commit.tree,
+            author: Person { name: &commit.author_name, email: &commit.author_email, date: commit.author_time },
+            committer: Person { name: &commit.committer_name, email: &commit.committer_email, date: commit.commit_time },
+            message: &commit.message,
+            refs: labels.get(&commit.id).map_or(&[], Vec::as_slice),
+        })?;
     }
-    
     Ok(())
 }
 
-fn display_git_log() -> Result<()> {
-    use std::process::Command;
-    
-    if let Ok(output) = Command::new("git")
-        .args(&["log", "--oneline", "--graph", "--decorate", "--color=always", "-10"])
-        .output() 
-    {
-        if output.status.success() {
-            if let Ok(log_output) = String::from_utf8(output.stdout) {
-                if !log_output.trim().is_empty() {
-                    println!("{}", "Git commit history:".bright_blue().bold());
-                    println!();
-                    println!("{}", log_output);
-                    return Ok(());
-                }
-            }
-        }
+fn display_log(repo: &dyn Repository, options: &LogOptions) -> Result<()> {
+    let Some(history) = log::log(repo, options)? else {
+        let branch = repo.head()?.branch_name().unwrap_or("HEAD").to_string();
+        println!("{}", format!("Your current branch '{}' does not have any commits yet.", branch).yellow());
+        return Ok(());
+    };
+
+    let head_id = repo.head_commit()?.unwrap_or_default();
+    let commits = history.graph()?;
+    if commits.is_empty() {
+        println!("{}", "No commits match the given filters.".yellow());
+        return Ok(());
     }
-    
-    // Fallback to mock log
-    display_mock_log()
-}
 
-fn display_mock_log() -> Result<()> {
-    println!("{}", "Rit commit history (example):".bright_blue().bold());
-    println!();
-    
-    // Mock commit data
-    let commits = vec![
-        ("a1b2c3d4", "Initial commit with rit structure", "2 hours ago", "main"),
-        ("e5f6g7h8", "Add graphical status display", "1 hour ago", ""),
-        ("i9j0k1l2", "Implement tree visualization", "30 minutes ago", ""),
-        ("m3n4o5p6", "Add colorful output support", "15 minutes ago", "HEAD"),
-    ];
-    
-    for (i, (hash, message, time, branch)) in commits.iter().enumerate() {
-        let is_head = branch.contains("HEAD");
-        let is_main = branch.contains("main");
-        
-        // Draw commit graph
-        let connector = match i {
-            0 => "│",
-            _ => "│",
-        };
-        
-        let commit_symbol = if is_head { "●" } else { "○" };
-        let commit_color = if is_head { 
-            commit_symbol.bright_yellow() 
-        } else { 
-            commit_symbol.bright_blue() 
-        };
-        
+    let labels = log::labels(repo)?;
+    let mut graph = Graph::default();
+
+    for (i, commit) in commits.iter().enumerate() {
+        let is_head = commit.id == head_id;
+        let commit_symbol = if is_head { glyph("●").bright_yellow() } else { glyph("○").bright_blue() };
+        let row = graph.place(&commit.id, commit_symbol, &commit.parents);
+
         // Commit line
-        print!("{} {} ", connector.bright_blue(), commit_color);
-        print!("{} ", hash.bright_yellow());
-        print!("{}", message.bright_white());
-        
-        if !branch.is_empty() {
-            if is_head {
-                print!(" {}", format!("({})", branch).bright_red().bold());
-            } else if is_main {
-                print!(" {}", format!("({})", branch).bright_green().bold());
-            }
+        print!("{} ", row.commit_line);
+        print!("{} ", commit.id[..7].bright_yellow());
+        print!("{}", commit.summary().bright_white());
+        if let Some(names) = labels.get(&commit.id) {
+            print!(" {}", format!("({})", names.join(", ")).bright_green().bold());
         }
-        
         println!();
-        
-        // Additional info line
-        println!("{} {} {}", 
-            "│".bright_blue(),
-            "└─".cyan(),
-            time.bright_black()
+
+        // Additional info lines
+        println!("{} {} {}",
+            row.info_prefix,
+            glyph("├─").cyan(),
+            format!("{} <{}>", commit.author_name, commit.author_email).bright_black()
+        );
+        println!("{} {} {} {}",
+            row.info_prefix,
+            glyph("└─").cyan(),
+            commit.author_time.format("%Y-%m-%d %H:%M:%S %z").to_string().bright_black(),
+            format!("({})", format_relative(commit.author_time)).bright_black()
         );
-        
+
+        if let Some(connector) = row.connector {
+            println!("{}", connector);
+        }
         if i < commits.len() - 1 {
-            println!("{}", "│".bright_blue());
+            println!("{}", graph.spacer());
         }
     }
-    
+
     println!();
     println!("{}", "Legend:".bright_blue().bold());
-    println!("{} Current HEAD", "●".bright_yellow());
-    println!("{} Previous commits", "○".bright_blue());
+    println!("{} Current HEAD", glyph("●").bright_yellow());
+    println!("{} Previous commits", glyph("○").bright_blue());
     println!();
     println!("{}", "Use 'rit status' to see current working directory state.".bright_blue());
-    
+
     Ok(())
-} 
\ No newline at end of file
+}
+
+/// The lines drawn for one commit by [`Graph::place`].
+struct GraphRow {
+    /// Lanes with the commit's node, printed before the commit summary.
+    commit_line: String,
+    /// Lanes printed before the author and date lines.
+    info_prefix: String,
+    /// Forks and merges leading to the commit's parents, if any.
+    connector: Option<String>,
+}
+
+/// Assigns each commit a column ("lane") and draws the edges to its
+/// parents with box-drawing characters. Every lane holds the id of the
+/// commit it is waiting to reach, and a commit never occupies two lanes:
+/// when two children share a parent, their lanes are joined so the parent
+/// continues in the leftmost one.
+#[derive(Default)]
+struct Graph {
+    lanes: Vec<Option<String>>,
+}
+
+/// How an edge from a commit reaches the lane of one of its parents.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Edge {
+    /// The parent gets a lane of its own.
+    New,
+    /// The parent is already waiting in this lane, which carries on.
+    Join,
+    /// The parent was waiting in this lane, which ends here and continues
+    /// in the commit's lane instead.
+    Absorb,
+}
+
+const LANE_COLORS: [Color; 6] = [
+    Color::BrightBlue,
+    Color::BrightMagenta,
+    Color::BrightCyan,
+    Color::BrightGreen,
+    Color::BrightYellow,
+    Color::BrightRed,
+];
+
+fn lane(symbol: &'static str, column: usize) -> ColoredString {
+    glyph(symbol).color(LANE_COLORS[column % LANE_COLORS.len()])
+}
+
+impl Graph {
+    fn place(&mut self, id: &str, symbol: ColoredString, parents: &[String]) -> GraphRow {
+        let col = match self.find(id) {
+            Some(col) => col,
+            None => self.allocate(id),
+        };
+
+        let commit_line = self.render(|x, active| {
+            if x == col {
+                symbol.clone()
+            } else if active {
+                lane("│", x)
+            } else {
+                " ".normal()
+            }
+        });
+        let info_prefix = self.render(|x, active| {
+            if (x == col && !parents.is_empty()) || (x != col && active) {
+                lane("│", x)
+            } else {
+                " ".normal()
+            }
+        });
+
+        // Route every parent to a lane: one already waiting for it, the
+        // commit's own lane for the first parent, or a free lane.
+        let before = self.lanes.clone();
+        self.lanes[col] = None;
+        let mut targets: Vec<(usize, Edge)> = Vec::new();
+        for (i, parent) in parents.iter().enumerate() {
+            match self.find(parent) {
+                Some(existing) if i == 0 && existing > col => {
+                    self.lanes[existing] = None;
+                    self.lanes[col] = Some(parent.clone());
+                    targets.push((existing, Edge::Absorb));
+                }
+                Some(existing) => targets.push((existing, Edge::Join)),
+                None if i == 0 => {
+                    self.lanes[col] = Some(parent.clone());
+                    targets.push((col, Edge::New));
+                }
+                None => targets.push((self.allocate(parent), Edge::New)),
+            }
+        }
+
+        let connector = if targets.iter().any(|&(x, _)| x != col) {
+            Some(self.render_connector(&before, col, &targets))
+        } else {
+            None
+        };
+
+        while self.lanes.last().is_some_and(|lane| lane.is_none()) {
+            self.lanes.pop();
+        }
+
+        GraphRow {
+            commit_line,
+            info_prefix,
+            connector,
+        }
+    }
+
+    /// Vertical lines for every active lane, drawn between commits.
+    fn spacer(&self) -> String {
+        self.render(|x, active| if active { lane("│", x) } else { " ".normal() })
+    }
+
+    fn find(&self, id: &str) -> Option<usize> {
+        self.lanes.iter().position(|lane| lane.as_deref() == Some(id))
+    }
+
+    fn allocate(&mut self, id: &str) -> usize {
+        match self.lanes.iter().position(|lane| lane.is_none()) {
+            Some(free) => {
+                self.lanes[free] = Some(id.to_string());
+                free
+            }
+            None => {
+                self.lanes.push(Some(id.to_string()));
+                self.lanes.len() - 1
+            }
+        }
+    }
+
+    fn render(&self, cell: impl Fn(usize, bool) -> ColoredString) -> String {
+        let cells: Vec<String> = (0..self.lanes.len())
+            .map(|x| cell(x, self.lanes[x].is_some()).to_string())
+            .collect();
+        cells.join(" ")
+    }
+
+    /// Draw the edges from the commit in lane `col` to the lanes its
+    /// parents continue in, e.g. `├─╮` for a merge whose second parent
+    /// starts a new lane, or `├─╯` for a branch ending where it forked.
+    fn render_connector(&self, before: &[Option<String>], col: usize, targets: &[(usize, Edge)]) -> String {
+        let span_min = targets.iter().map(|&(x, _)| x).min().unwrap_or(col).min(col);
+        let span_max = targets.iter().map(|&(x, _)| x).max().unwrap_or(col).max(col);
+        let width = before.len().max(self.lanes.len());
+        let edge_color = |x: usize| if x < col { span_min } else { span_max };
+
+        let mut line = String::new();
+        for x in 0..width {
+            let passing = x != col
+                && before.get(x).is_some_and(|lane| lane.is_some())
+                && before.get(x) == self.lanes.get(x);
+
+            let symbol = if x == col {
+                let continues = targets.iter().any(|&(target, edge)| target == col || edge == Edge::Absorb);
+                let symbol = match (continues, span_min < col, span_max > col) {
+                    (true, false, true) => "├",
+                    (true, true, false) => "┤",
+                    (true, true, true) => "┼",
+                    (false, false, true) => "╰",
+                    (false, true, false) => "╯",
+                    (false, true, true) => "┴",
+                    (true, false, false) => "│",
+                    (false, false, false) => " ",
+                };
+                lane(symbol, col)
+            } else if let Some(&(_, edge)) = targets.iter().find(|&&(target, _)| target == x) {
+                let symbol = match (x > col, edge) {
+                    (true, Edge::New) => "╮",
+                    (true, Edge::Join) => "┤",
+                    (true, Edge::Absorb) => "╯",
+                    (false, Edge::New) => "╭",
+                    (false, Edge::Join) => "├",
+                    (false, Edge::Absorb) => "╰",
+                };
+                lane(symbol, x)
+            } else if span_min < x && x < span_max {
+                lane(if passing { "┼" } else { "─" }, edge_color(x))
+            } else if passing {
+                lane("│", x)
+            } else {
+                " ".normal()
+            };
+            line.push_str(&symbol.to_string());
+
+            if x + 1 < width {
+                if span_min <= x && x < span_max {
+                    line.push_str(&lane("─", edge_color(x)).to_string());
+                } else {
+                    line.push(' ');
+                }
+            }
+        }
+        line
+    }
+}
+
+/// Describe how long ago `time` was, e.g. `3 hours ago`.
+fn format_relative(time: DateTime<FixedOffset>) -> String {
+    let seconds = (Local::now().fixed_offset() - time).num_seconds().max(0);
+    let (count, unit) = match seconds {
+        0..60 => (seconds, "second"),
+        60..3600 => (seconds / 60, "minute"),
+        3600..86400 => (seconds / 3600, "hour"),
+        86400..2_592_000 => (seconds / 86400, "day"),
+        2_592_000..31_536_000 => (seconds / 2_592_000, "month"),
+        _ => (seconds / 31_536_000, "year"),
+    };
+    if count == 1 {
+        format!("1 {} ago", unit)
+    } else {
+        format!("{} {}s ago", count, unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The graph drawn for `commits`, given children first as `(id,
+    /// parents)`: each commit's line, then its connector if it has one.
+    fn draw(commits: &[(&str, &[&str])]) -> Vec<String> {
+        colored::control::set_override(false);
+        rit::tree::use_ascii(false);
+        let mut graph = Graph::default();
+        let mut lines = Vec::new();
+        for &(id, parents) in commits {
+            let parents: Vec<String> = parents.iter().map(|parent| parent.to_string()).collect();
+            let row = graph.place(id, "○".normal(), &parents);
+            lines.push(row.commit_line.trim_end().to_string());
+            lines.extend(row.connector.map(|line| line.trim_end().to_string()));
+        }
+        lines
+    }
+
+    #[test]
+    fn a_linear_history_stays_in_one_lane() {
+        assert_eq!(draw(&[("c", &["b"]), ("b", &["a"]), ("a", &[])]), ["○", "○", "○"]);
+    }
+
+    #[test]
+    fn a_merge_opens_a_lane_that_closes_at_the_fork() {
+        assert_eq!(draw(&[
+            ("merge", &["main", "topic"]),
+            ("topic", &["base"]),
+            ("main", &["base"]),
+            ("base", &[]),
+        ]), ["○", "├─╮", "│ ○", "○ │", "├─╯", "○"]);
+    }
+
+    #[test]
+    fn branches_forking_from_one_commit_join_its_lane() {
+        assert_eq!(draw(&[
+            ("one", &["base"]),
+            ("two", &["base"]),
+            ("base", &[]),
+        ]), ["○", "│ ○", "├─╯", "○"]);
+    }
+
+    #[test]
+    fn a_lane_waiting_for_a_first_parent_is_absorbed() {
+        assert_eq!(draw(&[
+            ("one", &["middle"]),
+            ("two", &["base"]),
+            ("middle", &["base"]),
+            ("base", &[]),
+        ]), ["○", "│ ○", "○ │", "├─╯", "○"]);
+    }
+
+    #[test]
+    fn edges_cross_lanes_that_pass_by() {
+        assert_eq!(draw(&[
+            ("one", &["merge"]),
+            ("two", &["base"]),
+            ("merge", &["main", "topic"]),
+        ]), ["○", "│ ○", "○ │", "├─┼─╮"]);
+    }
+}
@@ -2,4 +2,10 @@ pub mod init;
 pub mod status;
 pub mod add;
 pub mod commit;
-pub mod log; 
\ No newline at end of file
+pub mod diff;
+pub mod log; 
+pub mod branch;
+pub mod checkout;
+pub mod check_ignore;
+pub mod config;
+pub mod output;
@@ -0,0 +1,96 @@
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
+use std::io::IsTerminal;
+use rit::config::{self, Config};
+use rit::repository;
+use rit::tree;
+
+/// How a command prints its results, chosen with the global `--format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// Colored trees for people
+    Text,
+    /// One JSON document per command (one per line for `rit log`)
+    Json,
+}
+
+impl Format {
+    pub fn is_json(self) -> bool {
+        self == Format::Json
+    }
+}
+
+/// When to color output, chosen with the global `--color`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Only when writing to a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+/// Decide how output looks before a command runs. Colors follow `--color`,
+/// then `NO_COLOR`, then `rit.coloroutput` (`true`/`auto`, `always` or
+/// `false`/`never`), and in auto mode are only used on a terminal.
+/// `rit.graphical = false` swaps box-drawing characters for ASCII. A bad
+/// value for either is warned about and ignored, so it can't stop the
+/// `rit config` that would fix it.
+pub fn configure(color: Option<ColorChoice>) -> Result<()> {
+    let config = match repository::discover()? {
+        Some(repo) => repo.config()?,
+        None => Config::default(),
+    };
+
+    let choice = match color {
+        Some(choice) => choice,
+        None if std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) => ColorChoice::Never,
+        None => match config.get("rit.coloroutput") {
+            None => ColorChoice::Auto,
+            Some(value) => match value.to_lowercase().as_str() {
+                "auto" => ColorChoice::Auto,
+                "always" => ColorChoice::Always,
+                "never" => ColorChoice::Never,
+                _ => match config::parse_bool("rit.coloroutput", value) {
+                    Ok(true) => ColorChoice::Auto,
+                    Ok(false) => ColorChoice::Never,
+                    Err(err) => {
+                        warn(&err);
+                        ColorChoice::Auto
+                    }
+                },
+            },
+        },
+    };
+    colored::control::set_override(match choice {
+        ColorChoice::Auto => std::io::stdout().is_terminal(),
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    });
+
+    let graphical = config.get_bool("rit.graphical").unwrap_or_else(|err| {
+        warn(&err);
+        None
+    });
+    tree::use_ascii(!graphical.unwrap_or(true));
+    Ok(())
+}
+
+fn warn(err: &anyhow::Error) {
+    eprintln!("warning: {} (ignored)", err);
+}
+
+/// A person and time attached to a commit, as it appears in JSON output.
+#[derive(Serialize)]
+pub struct Person<'a> {
+    pub name: &'a str,
+    pub email: &'a str,
+    /// RFC 3339, in the offset the commit was recorded with
+    pub date: DateTime<FixedOffset>,
+}
+
+/// Print a value as a single line of JSON.
+pub fn print_json(value: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string(value)?);
+    Ok(())
+}
@@ -1,225 +1,242 @@
-use anyhow::Result;
+use anyhow::{Result, bail};
 use colored::*;
-use walkdir::WalkDir;
+use serde::Serialize;
 use std::collections::HashSet;
-use std::path::{Path, PathBuf};
-use std::fs;
-use ignore::WalkBuilder;
+use rit::binary::BinaryFiles;
+use rit::diff::RenameOptions;
+use rit::porcelain;
+use rit::repository::{self, Repository};
+use rit::status::{self, Change, Status, StatusEntry};
+use rit::tree::{self, TreeLimits, glyph};
+use super::output::{self, Format};
 
-pub fn run() -> Result<()> {
-    println!("{}", "rit status".bright_green().bold());
-    println!();
-    
-    let current_dir = std::env::current_dir()?;
+pub fn run(limits: TreeLimits, renames: Option<Option<RenameOptions>>, format: Format) -> Result<()> {
+    if !format.is_json() {
+        println!("{}", "rit status".bright_green().bold());
+        println!();
+    }
     
-    // Check if we're in a git/rit repository
-    if !is_repo(&current_dir)? {
+    // Find the repository root, however deep inside it we are
+    let Some(repo) = repository::discover()? else {
+        if format.is_json() {
+            bail!("not a rit repository (or any of the parent directories)");
+        }
         println!("{}", "fatal: not a rit repository (or any of the parent directories)".red());
         return Ok(());
+    };
+    
+    let mut status = load_status(repo.as_ref(), renames)?;
+    if format.is_json() {
+        return print_json(&status, repo.head_commit()?);
     }
     
     // Get the current branch
-    let branch = get_current_branch(&current_dir)?;
+    let branch = status.head.branch_name().unwrap_or("HEAD (detached)");
     println!("{} {}", "On branch".blue(), branch.bright_yellow().bold());
     println!();
     
-    // Get file status
-    let file_status = get_file_status(&current_dir)?;
-    
-    if file_status.is_empty() {
-        println!("{}", "nothing to commit, working tree clean".green());
-        return Ok(());
+    // Paths are shown relative to where rit was run from, binary files
+    // with a marker of their own
+    let detector = BinaryFiles::new(repo.as_ref());
+    let mut binary = HashSet::new();
+    for entry in &mut status.entries {
+        let is_binary = detector.is_binary(&entry.path)?;
+        entry.path = repo.location().display_path(&entry.path);
+        if let Some(origin) = &mut entry.origin {
+            origin.path = repo.location().display_path(&origin.path);
+        }
+        if is_binary {
+            binary.insert(entry.path.clone());
+        }
     }
     
-    println!("{}", "Changes in working directory:".bright_blue().bold());
-    display_tree_structure(&current_dir, &file_status)?;
-    
-    Ok(())
+    display_sections(&status.entries, &binary, limits)
 }
 
-fn is_repo(path: &Path) -> Result<bool> {
-    let git_dir = path.join(".git");
-    let rit_dir = path.join(".rit");
-    Ok(git_dir.exists() || rit_dir.exists())
+/// `--porcelain`: git's line-based format, for tools that parse
+/// `git status`.
+pub fn porcelain(options: porcelain::Options, renames: Option<Option<RenameOptions>>) -> Result<()> {
+    let Some(repo) = repository::discover()? else {
+        bail!("not a rit repository (or any of the parent directories)");
+    };
+    let status = load_status(repo.as_ref(), renames)?;
+    print!("{}", porcelain::render(repo.as_ref(), &status, options)?);
+    Ok(())
 }
 
-fn get_current_branch(path: &Path) -> Result<String> {
-    // First try git
-    if let Ok(repo) = git2::Repository::open(path) {
-        if let Ok(head) = repo.head() {
-            if let Some(name) = head.shorthand() {
-                return Ok(name.to_string());
-            }
-        }
+/// The status, with renames found as the command line says or else as
+/// configured.
+fn load_status(repo: &dyn Repository, renames: Option<Option<RenameOptions>>) -> Result<Status> {
+    match renames {
+        Some(renames) => status::status_with_renames(repo, renames),
+        None => status::status(repo),
     }
-    
-    // Fallback to default
-    Ok("main".to_string())
-}
-
-#[derive(Debug, Clone)]
-enum FileStatus {
-    New,
-    Modified,
-    Deleted,
-    Renamed,
 }
 
-fn get_file_status(path: &Path) -> Result<Vec<(PathBuf, FileStatus)>> {
-    let mut status_list = Vec::new();
+/// `--format json`: the branch, HEAD commit and every changed path.
+fn print_json(status: &Status, head_id: Option<String>) -> Result<()> {
+    #[derive(Serialize)]
+    struct Json<'a> {
+        branch: Option<&'a str>,
+        head: Option<String>,
+        entries: Vec<Entry<'a>>,
+    }
+    #[derive(Serialize)]
+    struct Entry<'a> {
+        path: &'a str,
+        x: String,
+        y: String,
+        staged: Option<Change>,
+        unstaged: Option<Change>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        from: Option<&'a str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        similarity: Option<u8>,
+    }
     
-    // Try to get status from git first
-    if let Ok(repo) = git2::Repository::open(path) {
-        let mut status_opts = git2::StatusOptions::new();
-        status_opts.include_untracked(true);
-        
-        if let Ok(statuses) = repo.statuses(Some(&mut status_opts)) {
-            for entry in statuses.iter() {
-                if let Some(file_path) = entry.path() {
-                    let path_buf = PathBuf::from(file_path);
-                    
-                    // Check if this file should be ignored by .ritignore
-                    if !is_ignored_by_rit(path, &path_buf)? {
-                        let status = match entry.status() {
-                            s if s.contains(git2::Status::WT_NEW) => FileStatus::New,
-                            s if s.contains(git2::Status::WT_MODIFIED) => FileStatus::Modified,
-                            s if s.contains(git2::Status::WT_DELETED) => FileStatus::Deleted,
-                            s if s.contains(git2::Status::WT_RENAMED) => FileStatus::Renamed,
-                            _ => FileStatus::Modified,
-                        };
-                        status_list.push((path_buf, status));
-                    }
-                }
+    output::print_json(&Json {
+        branch: status.head.branch_name(),
+        head: head_id,
+        entries: status.entries.iter().map(|entry| {
+            let (x, y) = entry.xy();
+            Entry {
+                path: &entry.path,
+                x: x.to_string(),
+                y: y.to_string(),
+                staged: entry.staged,
+                unstaged: entry.unstaged,
+                from: entry.origin.as_ref().map(|origin| origin.path.as_str()),
+                similarity: entry.origin.as_ref().map(|origin| origin.similarity),
             }
-        }
+        }).collect(),
+    })
+}
+
+/// Print the conflicted, staged, unstaged and untracked files as separate
+/// trees, each in its own color. Paths in `binary` are marked as binary.
+fn display_sections(files: &[StatusEntry], binary: &HashSet<String>, limits: TreeLimits) -> Result<()> {
+    if files.is_empty() {
+        println!("{}", "nothing to commit, working tree clean".green());
+        return Ok(());
     }
     
-    // If no git status or empty, show files respecting .gitignore and .ritignore
-    if status_list.is_empty() {
-        let mut walker = WalkBuilder::new(path);
-        walker.max_depth(Some(3));
-        walker.hidden(false); // Show hidden files but respect ignore files
-        
-        // Add custom ignore file for .ritignore
-        let ritignore_path = path.join(".ritignore");
-        if ritignore_path.exists() {
-            walker.add_ignore(ritignore_path);
-        }
-        
-        for result in walker.build() {
-            if let Ok(entry) = result {
-                if entry.file_type().map_or(false, |ft| ft.is_file()) {
-                    if let Ok(relative_path) = entry.path().strip_prefix(path) {
-                        // Skip target directory for Rust projects
-                        if !relative_path.to_string_lossy().starts_with("target/") {
-                            status_list.push((relative_path.to_path_buf(), FileStatus::New));
-                        }
-                    }
-                }
-            }
+    let conflicted: Vec<&StatusEntry> = files.iter().filter(|file| file.is_conflicted()).collect();
+    let tracked = || files.iter().filter(|file| !file.is_conflicted() && !file.is_untracked());
+    let staged: Vec<&StatusEntry> = tracked().filter(|file| file.staged.is_some()).collect();
+    let unstaged: Vec<&StatusEntry> = tracked().filter(|file| file.unstaged.is_some()).collect();
+    let untracked: Vec<&StatusEntry> = files.iter().filter(|file| file.is_untracked()).collect();
+    
+    let counts = [
+        (conflicted.len(), "conflicted".bright_red()),
+        (staged.len(), "staged".bright_green()),
+        (unstaged.len(), "not staged".bright_yellow()),
+        (untracked.len(), "untracked".bright_magenta()),
+    ];
+    let sections = [
+        (Section::Conflicted, conflicted),
+        (Section::Staged, staged),
+        (Section::Unstaged, unstaged),
+        (Section::Untracked, untracked),
+    ];
+    let mut collapsed = false;
+    for (section, entries) in sections {
+        if entries.is_empty() {
+            continue;
         }
         
-        // Limit to 15 files for display
-        status_list.truncate(15);
+        let title = match section {
+            Section::Conflicted => "Unmerged paths:".bright_red().bold(),
+            Section::Staged => "Changes to be committed:".bright_green().bold(),
+            Section::Unstaged => "Changes not staged for commit:".bright_yellow().bold(),
+            Section::Untracked => "Untracked files:".bright_magenta().bold(),
+        };
+        println!("{}", title);
+        collapsed |= display_tree_structure(&entries, section, binary, limits)?;
+        println!();
     }
     
-    Ok(status_list)
-}
-
-fn is_ignored_by_rit(base_path: &Path, file_path: &PathBuf) -> Result<bool> {
-    let ritignore_path = base_path.join(".ritignore");
-    
-    if ritignore_path.exists() {
-        if let Ok(ritignore_content) = std::fs::read_to_string(&ritignore_path) {
-            let path_str = file_path.to_string_lossy();
-            
-            for line in ritignore_content.lines() {
-                let line = line.trim();
-                if !line.is_empty() && !line.starts_with('#') {
-                    // Simple pattern matching
-                    if line.starts_with('*') && line.len() > 1 {
-                        let pattern = &line[1..]; // Remove the *
-                        if path_str.ends_with(pattern) {
-                            return Ok(true);
-                        }
-                    } else if line.ends_with('/') {
-                        let dir_pattern = &line[..line.len()-1];
-                        if path_str.starts_with(dir_pattern) {
-                            return Ok(true);
-                        }
-                    } else {
-                        if path_str.contains(line) || path_str.starts_with(line) {
-                            return Ok(true);
-                        }
-                    }
-                }
-            }
-        }
+    let summary: Vec<String> = counts
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, label)| format!("{} {}", count.to_string().bold(), label))
+        .collect();
+    let mut summary_line = format!("{} {}",
+        format!("{} {}:", files.len(), if files.len() == 1 { "path" } else { "paths" }).bright_blue().bold(),
+        summary.join(", ")
+    );
+    if collapsed && limits != TreeLimits::unlimited() {
+        summary_line.push_str(&format!(" {}", "(use --all to show every file)".bright_black()));
     }
+    println!("{}", summary_line);
     
-    Ok(false)
+    Ok(())
 }
 
-fn display_tree_structure(base_path: &Path, files: &[(PathBuf, FileStatus)]) -> Result<()> {
-    let mut tree_map: std::collections::BTreeMap<String, Vec<(String, FileStatus)>> = std::collections::BTreeMap::new();
-    
-    // Group files by directory
-    for (file_path, status) in files {
-        if let Some(parent) = file_path.parent() {
-            let dir_key = if parent == Path::new("") {
-                ".".to_string()
-            } else {
-                parent.to_string_lossy().to_string()
-            };
-            
-            let file_name = file_path.file_name()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_string();
-                
-            tree_map.entry(dir_key)
-                .or_insert_with(Vec::new)
-                .push((file_name, status.clone()));
-        }
-    }
-    
-    // Display the tree structure
-    for (dir, files_in_dir) in tree_map {
-        if dir != "." {
-            println!("{}", format!("{}/", dir).bright_blue().bold());
-        }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Conflicted,
+    Staged,
+    Unstaged,
+    Untracked,
+}
+
+/// Draw one section's files, returning whether any of them were folded
+/// into a summary line.
+fn display_tree_structure(files: &[&StatusEntry], section: Section, binary: &HashSet<String>, limits: TreeLimits) -> Result<bool> {
+    let items: Vec<(String, &StatusEntry)> = files
+        .iter()
+        .map(|file| (file.path.clone(), *file))
+        .collect();
+    
+    let drawn = std::cell::Cell::new(0);
+    let lines = tree::render(&items, limits, |file, file_name| {
+        drawn.set(drawn.get() + 1);
+        // The staged column is always green and the unstaged one red,
+        // as in `git status --short`
+        let (x, y) = file.xy();
+        let status_symbol = match section {
+            Section::Conflicted => format!("{}{}", x, y).bright_red().bold().to_string(),
+            Section::Untracked => "??".bright_magenta().to_string(),
+            _ => format!("{}{}", x.to_string().bright_green(), y.to_string().bright_red()),
+        };
+        let status_symbol = if binary.contains(&file.path) {
+            format!("{} {}", status_symbol, glyph("◆").bright_blue())
+        } else {
+            status_symbol
+        };
         
-        for (i, (file_name, status)) in files_in_dir.iter().enumerate() {
-            let is_last = i == files_in_dir.len() - 1;
-            let connector = if is_last { "└──" } else { "├──" };
-            let prefix = if dir != "." { "│   " } else { "" };
-            
-            let status_symbol = match status {
-                FileStatus::New => "??".bright_green(),
-                FileStatus::Modified => " M".bright_yellow(),
-                FileStatus::Deleted => " D".bright_red(),
-                FileStatus::Renamed => " R".bright_cyan(),
-            };
-            
-            let file_color = match status {
-                FileStatus::New => file_name.bright_green(),
-                FileStatus::Modified => file_name.bright_yellow(),
-                FileStatus::Deleted => file_name.bright_red(),
-                FileStatus::Renamed => file_name.bright_cyan(),
-            };
-            
-            println!("{}{} {} {}", 
-                prefix,
-                connector.cyan(),
-                status_symbol,
-                file_color
-            );
-        }
+        let change = match section {
+            Section::Staged => file.staged,
+            _ => file.unstaged,
+        };
+        let file_color = match (section, change) {
+            (Section::Conflicted, _) => file_name.bright_red().bold(),
+            (Section::Untracked, _) => file_name.bright_magenta(),
+            (Section::Staged, _) => file_name.bright_green(),
+            (_, Some(Change::Deleted)) => file_name.bright_red(),
+            (_, Some(Change::Typechange)) => file_name.bright_cyan(),
+            _ => file_name.bright_yellow(),
+        };
         
-        if dir != "." {
-            println!();
+        // Staged renames show where they came from
+        match (section, &file.origin) {
+            (Section::Staged, Some(origin)) => {
+                let similarity = if origin.similarity < 100 { format!(" ({}%)", origin.similarity) } else { String::new() };
+                format!("{} {} {} {}{}",
+                    status_symbol,
+                    origin.path.bright_cyan(),
+                    glyph("→").bright_black(),
+                    file_color,
+                    similarity.bright_black()
+                )
+            }
+            _ => format!("{} {}", status_symbol, file_color),
         }
+    });
+    
+    for line in lines {
+        println!("{}", line);
     }
     
-    Ok(())
-} 
\ No newline at end of file
+    Ok(drawn.get() < items.len())
+}
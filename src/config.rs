@@ -0,0 +1,697 @@
+use anyhow::{Context, Result, anyhow, bail};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where a config file lives, from the least to the most specific. Later
+/// levels override earlier ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// `/etc/ritconfig`, or `$RIT_CONFIG_SYSTEM`.
+    System,
+    /// `~/.ritconfig`, or `$RIT_CONFIG_GLOBAL`.
+    Global,
+    /// The repository's own `.rit/config` (or `.git/config`).
+    Local,
+}
+
+impl Level {
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::System => "system",
+            Level::Global => "global",
+            Level::Local => "local",
+        }
+    }
+}
+
+/// The system-wide config file.
+pub fn system_path() -> PathBuf {
+    env::var_os("RIT_CONFIG_SYSTEM")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/etc/ritconfig"))
+}
+
+/// The current user's config file, if their home directory is known.
+pub fn global_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("RIT_CONFIG_GLOBAL") {
+        return Some(PathBuf::from(path));
+    }
+    home_dir().map(|home| home.join(".ritconfig"))
+}
+
+/// How a value should be read: `rit config --type` checks and normalizes
+/// values with these, as the typed getters do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    /// `true`/`yes`/`on`/`1` or `false`/`no`/`off`/`0`, shown as `true`/`false`.
+    Bool,
+    /// A whole number with an optional `k`, `m` or `g` suffix.
+    Int,
+    /// A path, where a leading `~/` means the home directory.
+    Path,
+}
+
+impl ValueType {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "bool" => Ok(ValueType::Bool),
+            "int" => Ok(ValueType::Int),
+            "path" => Ok(ValueType::Path),
+            other => bail!("unknown config type '{}'", other),
+        }
+    }
+
+    /// The type rit reads `key` as, for the settings that have one.
+    pub fn of_key(key: &str) -> Option<Self> {
+        match normalize_key(key).as_str() {
+            "rit.graphical" => Some(ValueType::Bool),
+            "diff.context" => Some(ValueType::Int),
+            _ => None,
+        }
+    }
+
+    /// The canonical spelling of `value`, or an error if it isn't valid.
+    pub fn normalize(self, key: &str, value: &str) -> Result<String> {
+        Ok(match self {
+            ValueType::Bool => parse_bool(key, value)?.to_string(),
+            ValueType::Int => parse_int(key, value)?.to_string(),
+            ValueType::Path => expand_path(value).to_string_lossy().to_string(),
+        })
+    }
+}
+
+/// Values read from git-style INI config files such as `.rit/config`.
+/// Keys are stored as `section.key` or `section.subsection.key`, with the
+/// section and key lowercased; subsections keep their case.
+#[derive(Debug, Default)]
+pub struct Config {
+    entries: Vec<(String, String)>,
+}
+
+/// How deeply `include.path` may nest before it is treated as a loop.
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+impl Config {
+    /// Read a config file and the files it includes, returning an empty
+    /// config if it doesn't exist.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut config = Config::default();
+        config.read_file(path, 0)?;
+        Ok(config)
+    }
+
+    /// Read the system, global and local config files in that order, so
+    /// the most specific value wins. Missing files are skipped.
+    pub fn load_all(local: Option<&Path>) -> Result<Self> {
+        let mut config = Config::default();
+        config.read_file(&system_path(), 0)?;
+        if let Some(global) = global_path() {
+            config.read_file(&global, 0)?;
+        }
+        if let Some(local) = local {
+            config.read_file(local, 0)?;
+        }
+        Ok(config)
+    }
+
+    /// Parse config text. `include.path` is kept as a plain value, since
+    /// there is no file to resolve it against.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut config = Config::default();
+        config.parse_text(text, None, 0)?;
+        Ok(config)
+    }
+
+    fn read_file(&mut self, path: &Path, depth: usize) -> Result<()> {
+        if depth > MAX_INCLUDE_DEPTH {
+            bail!("exceeded maximum include depth ({}) while including {}", MAX_INCLUDE_DEPTH, path.display());
+        }
+        if !path.is_file() {
+            return Ok(());
+        }
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("cannot read config file {}", path.display()))?;
+        self.parse_text(&text, Some(path), depth)
+    }
+
+    fn parse_text(&mut self, text: &str, path: Option<&Path>, depth: usize) -> Result<()> {
+        let source = path.map_or_else(|| "config".to_string(), |path| path.display().to_string());
+        let mut section = String::new();
+        let mut lines = text.lines().enumerate();
+
+        while let Some((number, line)) = lines.next() {
+            let line = line.trim_start();
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+
+            if line.starts_with('[') {
+                let Some(name) = parse_section_header(line) else {
+                    bail!("bad config line {} in {}: '{}'", number + 1, source, line);
+                };
+                section = name;
+                continue;
+            }
+
+            if section.is_empty() {
+                bail!("bad config line {} in {}: key outside of a section", number + 1, source);
+            }
+            let (key, rest) = match line.find(|c: char| c == '=' || c.is_whitespace()) {
+                Some(end) => (&line[..end], line[end..].trim_start()),
+                None => (line, ""),
+            };
+            if !is_valid_key(key) {
+                bail!("bad config line {} in {}: invalid key '{}'", number + 1, source, key);
+            }
+            let value = match rest.strip_prefix('=') {
+                Some(raw) => {
+                    // A trailing backslash continues the value on the next line
+                    let mut raw = raw.to_string();
+                    while ends_with_continuation(&raw) {
+                        raw.pop();
+                        let Some((_, next)) = lines.next() else {
+                            break;
+                        };
+                        raw.push_str(next);
+                    }
+                    parse_value(&raw).with_context(|| format!("bad config line {} in {}", number + 1, source))?
+                }
+                // A bare key is shorthand for `key = true`
+                None if rest.is_empty() || rest.starts_with(['#', ';']) => "true".to_string(),
+                None => bail!("bad config line {} in {}: '{}'", number + 1, source, line),
+            };
+
+            let name = format!("{}.{}", section, key.to_lowercase());
+            if name == "include.path"
+                && let Some(path) = path
+            {
+                let included = expand_path(&value);
+                let included = match path.parent() {
+                    Some(dir) if included.is_relative() => dir.join(included),
+                    _ => included,
+                };
+                self.read_file(&included, depth + 1)?;
+            }
+            self.entries.push((name, value));
+        }
+
+        Ok(())
+    }
+
+    /// Look up `section.key`. When a key is set more than once the last
+    /// value wins.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.get_all(key).pop()
+    }
+
+    /// Every value of `section.key`, in the order they were read.
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        let key = normalize_key(key);
+        self.entries
+            .iter()
+            .filter(|(name, _)| *name == key)
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    /// Look up `section.key` as a boolean, spelled like git does:
+    /// `true`/`yes`/`on`/`1` or `false`/`no`/`off`/`0`.
+    pub fn get_bool(&self, key: &str) -> Result<Option<bool>> {
+        self.get(key).map(|value| parse_bool(key, value)).transpose()
+    }
+
+    /// Look up `section.key` as a number, allowing `k`, `m` and `g`
+    /// suffixes.
+    pub fn get_int(&self, key: &str) -> Result<Option<i64>> {
+        self.get(key).map(|value| parse_int(key, value)).transpose()
+    }
+
+    /// Look up `section.key` as a path, expanding a leading `~/`.
+    pub fn get_path(&self, key: &str) -> Option<PathBuf> {
+        self.get(key).map(expand_path)
+    }
+
+    /// Every `(key, value)` pair, in the order they were read.
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+}
+
+/// Parse a boolean config value. `key` is only used in the error message.
+pub fn parse_bool(key: &str, value: &str) -> Result<bool> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" | "" => Ok(false),
+        _ => bail!("bad boolean config value '{}' for '{}'", value, key),
+    }
+}
+
+/// Parse a numeric config value such as `42`, `-3` or `8k`.
+pub fn parse_int(key: &str, value: &str) -> Result<i64> {
+    let trimmed = value.trim();
+    let (digits, factor) = match trimmed.chars().last().map(|c| c.to_ascii_lowercase()) {
+        Some('k') => (&trimmed[..trimmed.len() - 1], 1024),
+        Some('m') => (&trimmed[..trimmed.len() - 1], 1024 * 1024),
+        Some('g') => (&trimmed[..trimmed.len() - 1], 1024 * 1024 * 1024),
+        _ => (trimmed, 1),
+    };
+    digits.parse::<i64>()
+        .ok()
+        .and_then(|number| number.checked_mul(factor))
+        .ok_or_else(|| anyhow!("bad numeric config value '{}' for '{}'", value, key))
+}
+
+/// Expand a leading `~/` to the home directory.
+pub fn expand_path(value: &str) -> PathBuf {
+    match (value.strip_prefix("~/"), home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ if value == "~" => home_dir().unwrap_or_else(|| PathBuf::from(value)),
+        _ => PathBuf::from(value),
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Key names start with a letter and hold only letters, digits and `-`.
+fn is_valid_key(key: &str) -> bool {
+    key.starts_with(|c: char| c.is_ascii_alphabetic())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// Whether a raw value ends in an unescaped backslash.
+fn ends_with_continuation(raw: &str) -> bool {
+    let trailing = raw.chars().rev().take_while(|&c| c == '\\').count();
+    trailing % 2 == 1
+}
+
+/// Unquote a raw value: double quotes keep spaces and comment characters,
+/// backslash escapes `\"`, `\\`, `\n`, `\t` and `\b`, an unquoted `#` or `;`
+/// starts a comment, and whitespace around the value is dropped.
+fn parse_value(raw: &str) -> Result<String> {
+    let mut value = String::new();
+    let mut in_quotes = false;
+    // Length of `value` up to its last quoted or non-space character, so
+    // trailing spaces outside quotes can be trimmed
+    let mut keep = 0;
+    let mut chars = raw.trim_start().chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '\\' => {
+                let escaped = match chars.next() {
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('b') => '\u{8}',
+                    Some(other) => bail!("invalid escape '\\{}' in value", other),
+                    None => bail!("value ends with a backslash"),
+                };
+                value.push(escaped);
+            }
+            '#' | ';' if !in_quotes => break,
+            c => value.push(c),
+        }
+        if in_quotes || !c.is_whitespace() {
+            keep = value.len();
+        }
+    }
+
+    if in_quotes {
+        bail!("unterminated quote in value");
+    }
+    value.truncate(keep);
+    Ok(value)
+}
+
+/// Write a value so that [`parse_value`] reads it back unchanged, quoting
+/// it when it has surrounding spaces or comment characters.
+fn format_value(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+    if value.starts_with(char::is_whitespace) || value.ends_with(char::is_whitespace) || value.contains(['#', ';']) {
+        format!("\"{}\"", escaped)
+    } else {
+        escaped
+    }
+}
+
+/// Set `key` to `value` in the config file at `path`, replacing an existing
+/// value or adding it to (or after) its section. The rest of the file,
+/// including comments, is left untouched. As in git, a key with several
+/// values is refused rather than having one of them picked to replace.
+/// Settings rit reads as a type must hold a valid value, so a typo can't
+/// break the commands that read them.
+pub fn set_value(path: &Path, key: &str, value: &str) -> Result<()> {
+    check_value(key, value)?;
+    let key = normalize_key(key);
+    let Some((section, name)) = key.rsplit_once('.') else {
+        bail!("key does not contain a section: {}", key);
+    };
+    let text = if path.exists() { fs::read_to_string(path)? } else { String::new() };
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+    let new_line = format!("    {} = {}", name, format_value(value));
+
+    let mut current = String::new();
+    let mut section_end = None;
+    let mut existing = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            current = parse_section_header(trimmed).unwrap_or_default();
+            continue;
+        }
+        if current != section || trimmed.is_empty() || trimmed.starts_with(['#', ';']) {
+            continue;
+        }
+        section_end = Some(i);
+        if line_key(trimmed) == name {
+            existing.push(i);
+        }
+    }
+
+    match existing[..] {
+        [] => {}
+        [i] => {
+            lines[i] = new_line;
+            return write_lines(path, &lines);
+        }
+        _ => bail!("{} has {} values in {}; unset it first to replace them", key, existing.len(), path.display()),
+    }
+    match section_end.or_else(|| section_header_line(&lines, section)) {
+        Some(i) => lines.insert(i + 1, new_line),
+        None => {
+            lines.push(format_section_header(section));
+            lines.push(new_line);
+        }
+    }
+    write_lines(path, &lines)
+}
+
+/// Fail unless `value` is one rit can read for `key`. Keys rit doesn't
+/// read, or reads as plain text, take anything.
+pub fn check_value(key: &str, value: &str) -> Result<()> {
+    let key = normalize_key(key);
+    match key.as_str() {
+        "rit.coloroutput" if matches!(value.to_lowercase().as_str(), "auto" | "always" | "never") => Ok(()),
+        "rit.coloroutput" => parse_bool(&key, value).map(drop),
+        "diff.renames" | "status.renames" => crate::diff::RenameOptions::from_config(&key, Some(value)).map(drop),
+        "diff.algorithm" => crate::diff::Algorithm::parse(value).map(drop),
+        _ => match ValueType::of_key(&key) {
+            Some(value_type) => value_type.normalize(&key, value).map(drop),
+            None => Ok(()),
+        },
+    }
+}
+
+/// Remove every value of `key` from the config file at `path`, dropping
+/// its section header if nothing else is left in it. Returns whether
+/// anything was removed.
+pub fn unset_value(path: &Path, key: &str) -> Result<bool> {
+    if !path.exists() {
+        return Ok(false);
+    }
+    let key = normalize_key(key);
+    let Some((section, name)) = key.rsplit_once('.') else {
+        bail!("key does not contain a section: {}", key);
+    };
+    let text = fs::read_to_string(path)?;
+
+    let mut kept: Vec<String> = Vec::new();
+    let mut removed = false;
+    let mut current = String::new();
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            current = parse_section_header(trimmed).unwrap_or_default();
+        } else if current == section && !trimmed.starts_with(['#', ';']) && line_key(trimmed) == name {
+            removed = true;
+            continue;
+        }
+        kept.push(line.to_string());
+    }
+
+    if removed {
+        if let Some(i) = section_header_line(&kept, section)
+            && kept[i + 1..].iter().take_while(|line| !line.trim().starts_with('[')).all(|line| line.trim().is_empty())
+        {
+            kept.remove(i);
+        }
+        write_lines(path, &kept)?;
+    }
+    Ok(removed)
+}
+
+/// Turn `[core]`, `[branch "main"]` or the older `[branch.main]` into
+/// `core` or `branch.main`. Anything after the closing bracket must be a
+/// comment.
+fn parse_section_header(line: &str) -> Option<String> {
+    let header = line.strip_prefix('[')?;
+    let end = header.find(']')?;
+    let rest = header[end + 1..].trim_start();
+    if !(rest.is_empty() || rest.starts_with(['#', ';'])) {
+        return None;
+    }
+    // Subsection names may contain `]` inside their quotes
+    let (name, sub) = match header.split_once(|c: char| c.is_whitespace()) {
+        Some((name, sub)) if sub.trim_start().starts_with('"') => {
+            let sub = sub.trim_start().strip_prefix('"')?;
+            let mut parsed = String::new();
+            let mut chars = sub.char_indices();
+            let mut closed = None;
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\\' => parsed.push(chars.next()?.1),
+                    '"' => {
+                        closed = Some(i);
+                        break;
+                    }
+                    c => parsed.push(c),
+                }
+            }
+            let after = sub[closed? + 1..].trim_start().strip_prefix(']')?.trim_start();
+            if !(after.is_empty() || after.starts_with(['#', ';'])) {
+                return None;
+            }
+            (name, Some(parsed))
+        }
+        _ => (&header[..end], None),
+    };
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.') {
+        return None;
+    }
+    Some(match sub {
+        Some(sub) => format!("{}.{}", name.to_lowercase(), sub),
+        None => name.to_lowercase(),
+    })
+}
+
+fn format_section_header(section: &str) -> String {
+    match section.split_once('.') {
+        Some((name, sub)) => format!("[{} \"{}\"]", name, sub.replace('\\', "\\\\").replace('"', "\\\"")),
+        None => format!("[{}]", section),
+    }
+}
+
+fn section_header_line(lines: &[String], section: &str) -> Option<usize> {
+    lines.iter().rposition(|line| parse_section_header(line.trim()).as_deref() == Some(section))
+}
+
+fn line_key(line: &str) -> String {
+    line.split(|c: char| c == '=' || c.is_whitespace()).next().unwrap_or("").to_lowercase()
+}
+
+fn write_lines(path: &Path, lines: &[String]) -> Result<()> {
+    let mut text = lines.join("\n");
+    text.push('\n');
+    fs::write(path, text)?;
+    Ok(())
+}
+
+/// Section and key names are case-insensitive, subsection names are not.
+fn normalize_key(key: &str) -> String {
+    match (key.split_once('.'), key.rsplit_once('.')) {
+        (Some((section, _)), Some((middle, name))) if middle.len() > section.len() => {
+            format!(
+                "{}{}.{}",
+                section.to_lowercase(),
+                &middle[section.len()..],
+                name.to_lowercase()
+            )
+        }
+        _ => key.to_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+    /// A config file holding `text`, removed again when dropped.
+    struct TempConfig(PathBuf);
+
+    impl TempConfig {
+        fn new(text: &str) -> Self {
+            let name = format!("rit-config-test-{}-{}", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed));
+            let path = std::env::temp_dir().join(name);
+            fs::write(&path, text).unwrap();
+            TempConfig(path)
+        }
+
+        fn text(&self) -> String {
+            fs::read_to_string(&self.0).unwrap()
+        }
+    }
+
+    impl Drop for TempConfig {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn set_replaces_a_single_value_in_place() {
+        let config = TempConfig::new("[user]\n    # who\n    name = Old\n    email = a@b\n");
+        set_value(&config.0, "user.name", "New Name").unwrap();
+        assert_eq!(config.text(), "[user]\n    # who\n    name = New Name\n    email = a@b\n");
+        assert_eq!(Config::load(&config.0).unwrap().get("user.name"), Some("New Name"));
+    }
+
+    #[test]
+    fn set_adds_missing_keys_and_sections() {
+        let config = TempConfig::new("[user]\n    name = Me\n");
+        set_value(&config.0, "user.email", "me@example.com").unwrap();
+        set_value(&config.0, "core.editor", "vi").unwrap();
+        assert_eq!(config.text(), "[user]\n    name = Me\n    email = me@example.com\n[core]\n    editor = vi\n");
+    }
+
+    #[test]
+    fn set_refuses_values_rit_cannot_read() {
+        let config = TempConfig::new("[rit]\n    graphical = true\n");
+        let bad = [("rit.graphical", "maybe"), ("rit.colorOutput", "sometimes"), ("diff.context", "lots"), ("diff.algorithm", "fast")];
+        for (key, value) in bad {
+            assert!(set_value(&config.0, key, value).is_err(), "{} = {}", key, value);
+        }
+        assert_eq!(config.text(), "[rit]\n    graphical = true\n");
+
+        let good = [
+            ("rit.graphical", "no"),
+            ("rit.coloroutput", "always"),
+            ("diff.context", "5"),
+            ("diff.renames", "copies"),
+            ("core.editor", "anything"),
+        ];
+        for (key, value) in good {
+            set_value(&config.0, key, value).unwrap();
+        }
+        assert_eq!(Config::load(&config.0).unwrap().get("rit.graphical"), Some("no"));
+    }
+
+    #[test]
+    fn set_refuses_keys_with_several_values() {
+        let text = "[remote \"origin\"]\n    fetch = one\n[remote \"origin\"]\n    fetch = two\n";
+        let config = TempConfig::new(text);
+        let error = set_value(&config.0, "remote.origin.fetch", "three").unwrap_err();
+        assert!(error.to_string().contains("has 2 values"));
+        assert_eq!(config.text(), text);
+
+        assert!(unset_value(&config.0, "remote.origin.fetch").unwrap());
+        set_value(&config.0, "remote.origin.fetch", "three").unwrap();
+        assert_eq!(Config::load(&config.0).unwrap().get_all("remote.origin.fetch"), ["three"]);
+    }
+
+    #[test]
+    fn sections_keys_and_subsections() {
+        let config = Config::parse("[Core]\n\tEditor = vim\n[branch \"Feature/X\"]\n  remote = origin\n[a.b]\nc = d\n").unwrap();
+        assert_eq!(config.get("core.editor"), Some("vim"));
+        assert_eq!(config.get("CORE.EDITOR"), Some("vim"));
+        assert_eq!(config.get("branch.Feature/X.remote"), Some("origin"));
+        assert_eq!(config.get("branch.feature/x.remote"), None);
+        assert_eq!(config.get("a.b.c"), Some("d"));
+    }
+
+    #[test]
+    fn values_are_unquoted_and_unescaped() {
+        let text = concat!(
+            "[x]\n",
+            "plain = some words   # a comment\n",
+            "quoted = \"  kept # and ; kept  \"\n",
+            "escaped = a\\tb\\\\c\\\"d\\ne\n",
+            "empty =\n",
+            "bare\n",
+            "continued = one \\\n two\n",
+        );
+        let config = Config::parse(text).unwrap();
+        assert_eq!(config.get("x.plain"), Some("some words"));
+        assert_eq!(config.get("x.quoted"), Some("  kept # and ; kept  "));
+        assert_eq!(config.get("x.escaped"), Some("a\tb\\c\"d\ne"));
+        assert_eq!(config.get("x.empty"), Some(""));
+        assert_eq!(config.get("x.bare"), Some("true"));
+        assert_eq!(config.get("x.continued"), Some("one  two"));
+    }
+
+    #[test]
+    fn the_last_value_wins() {
+        let config = Config::parse("[x]\nk = 1\n[y]\nk = 2\n[x]\nk = 3\n").unwrap();
+        assert_eq!(config.get("x.k"), Some("3"));
+        assert_eq!(config.get_all("x.k"), ["1", "3"]);
+    }
+
+    #[test]
+    fn malformed_lines_are_errors() {
+        for text in [
+            "key = outside\n",
+            "[x\nk = v\n",
+            "[x]\n1key = v\n",
+            "[x]\nk = \"open\n",
+            "[x]\nk = bad\\q\n",
+            "[x]\nk v\n",
+        ] {
+            assert!(Config::parse(text).is_err(), "{:?} should not parse", text);
+        }
+    }
+
+    #[test]
+    fn formatted_values_read_back_unchanged() {
+        for value in ["plain", " padded ", "a # b", "semi;colon", "quote\"back\\slash", "tab\tnew\nline", ""] {
+            let config = Config::parse(&format!("[x]\nk = {}\n", format_value(value))).unwrap();
+            assert_eq!(config.get("x.k"), Some(value));
+        }
+    }
+
+    #[test]
+    fn includes_are_read_relative_to_the_including_file() {
+        let included = TempConfig::new("[user]\n    name = Included\n");
+        let name = included.0.file_name().unwrap().to_str().unwrap();
+        let config = TempConfig::new(&format!("[user]\n    name = Before\n[include]\n    path = {}\n", name));
+        assert_eq!(Config::load(&config.0).unwrap().get("user.name"), Some("Included"));
+    }
+
+    #[test]
+    fn typed_values() {
+        assert!(parse_bool("k", "Yes").unwrap());
+        assert!(!parse_bool("k", "").unwrap());
+        assert!(parse_bool("k", "maybe").is_err());
+        assert_eq!(parse_int("k", "8k").unwrap(), 8192);
+        assert_eq!(parse_int("k", "-3").unwrap(), -3);
+        assert!(parse_int("k", "9999999999g").is_err());
+    }
+}
@@ -0,0 +1,338 @@
+use anyhow::{Result, bail};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::path::Path;
+use crate::attributes::Attributes;
+use crate::binary;
+use crate::index::{self, MODE_SYMLINK};
+use crate::objects::{ObjectKind, hash_object};
+use crate::repository::Repository;
+use super::renames::{Candidate, Origin, RenameOptions, find_renames};
+
+/// One side of a comparison.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// No files at all, such as HEAD before the first commit.
+    Empty,
+    /// The tree of a commit, by full id.
+    Commit(String),
+    /// The staged files.
+    Index,
+    /// The tracked files as they are on disk. Untracked files are left
+    /// out, as in `git diff`.
+    Worktree,
+}
+
+/// How a path differs between the two sides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffStatus {
+    Added,
+    Deleted,
+    /// The contents, the mode or both changed.
+    Modified,
+    /// Moved from another path, maybe with changes.
+    Renamed,
+    /// Added as a copy of another file, maybe with changes.
+    Copied,
+}
+
+impl DiffStatus {
+    pub fn code(self) -> char {
+        match self {
+            DiffStatus::Added => 'A',
+            DiffStatus::Deleted => 'D',
+            DiffStatus::Modified => 'M',
+            DiffStatus::Renamed => 'R',
+            DiffStatus::Copied => 'C',
+        }
+    }
+}
+
+/// A file's contents on one side of a comparison.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blob {
+    pub mode: u32,
+    pub id: String,
+    /// The file's bytes, or the target path for a symlink.
+    pub data: Vec<u8>,
+    /// Not to be compared line by line, going by its contents and
+    /// attributes. Symlinks never are.
+    pub binary: bool,
+}
+
+/// One changed path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDiff {
+    /// Path relative to the top of the working tree.
+    pub path: String,
+    pub status: DiffStatus,
+    /// Where a renamed or copied file came from; `old` is that file.
+    pub origin: Option<Origin>,
+    /// `None` when the file was added.
+    pub old: Option<Blob>,
+    /// `None` when the file was deleted.
+    pub new: Option<Blob>,
+}
+
+impl FileDiff {
+    pub fn old_data(&self) -> &[u8] {
+        self.old.as_ref().map_or(&[], |blob| &blob.data)
+    }
+
+    pub fn new_data(&self) -> &[u8] {
+        self.new.as_ref().map_or(&[], |blob| &blob.data)
+    }
+
+    /// Either side is binary, so the file is summarized instead of
+    /// compared line by line, as in git.
+    pub fn is_binary(&self) -> bool {
+        [&self.old, &self.new].into_iter().flatten().any(|blob| blob.binary)
+    }
+}
+
+/// Compare two snapshots of the repository, limited to `paths` (relative
+/// to the top of the working tree, `""` for everything) when any are
+/// given. A file that turned into a symlink or back is reported as a
+/// deletion and an addition, as git does. Sorted by path.
+pub fn diff_files(repo: &dyn Repository, old: &Source, new: &Source, paths: &[String]) -> Result<Vec<FileDiff>> {
+    let old_files = snapshot(repo, old)?;
+    let new_files = snapshot(repo, new)?;
+    let attributes = Attributes::load(repo.location());
+    let wanted = |path: &str| {
+        paths.is_empty() || paths.iter().any(|spec| {
+            spec.is_empty() || path == spec || path.strip_prefix(spec.as_str()).is_some_and(|rest| rest.starts_with('/'))
+        })
+    };
+
+    let all: BTreeSet<&String> = old_files.keys().chain(new_files.keys()).collect();
+    let mut diffs = Vec::new();
+    for path in all.into_iter().filter(|path| wanted(path)) {
+        let before = old_files.get(path);
+        let after = new_files.get(path);
+        if before == after {
+            continue;
+        }
+        let old_blob = before.map(|entry| load(repo, &attributes, path, entry, old)).transpose()?;
+        let new_blob = after.map(|entry| load(repo, &attributes, path, entry, new)).transpose()?;
+
+        match (old_blob, new_blob) {
+            (Some(old_blob), Some(new_blob)) if is_symlink(old_blob.mode) != is_symlink(new_blob.mode) => {
+                diffs.push(FileDiff { path: path.clone(), status: DiffStatus::Deleted, origin: None, old: Some(old_blob), new: None });
+                diffs.push(FileDiff { path: path.clone(), status: DiffStatus::Added, origin: None, old: None, new: Some(new_blob) });
+            }
+            (old_blob, new_blob) => {
+                let status = match (&old_blob, &new_blob) {
+                    (None, _) => DiffStatus::Added,
+                    (_, None) => DiffStatus::Deleted,
+                    _ => DiffStatus::Modified,
+                };
+                diffs.push(FileDiff { path: path.clone(), status, origin: None, old: old_blob, new: new_blob });
+            }
+        }
+    }
+    Ok(diffs)
+}
+
+/// Turn deleted and added files that look alike into renames, and with
+/// copies on, added files that look like a modified or deleted file into
+/// copies of it. Sorted by path, with renames and copies under their new
+/// path.
+pub fn detect_renames(files: Vec<FileDiff>, options: RenameOptions) -> Vec<FileDiff> {
+    let positions = |status: DiffStatus| -> Vec<usize> {
+        files.iter().enumerate().filter(|(_, file)| file.status == status).map(|(i, _)| i).collect()
+    };
+    let (removed, kept, added) = (positions(DiffStatus::Deleted), positions(DiffStatus::Modified), positions(DiffStatus::Added));
+    let candidates = |positions: &[usize], new_side: bool| -> Vec<Candidate> {
+        positions.iter()
+            .map(|&i| {
+                let file = &files[i];
+                let blob = if new_side { file.new.as_ref() } else { file.old.as_ref() }.expect("side is present");
+                Candidate { path: &file.path, id: &blob.id, data: &blob.data, is_symlink: is_symlink(blob.mode) }
+            })
+            .collect()
+    };
+    let pairings = find_renames(&candidates(&removed, false), &candidates(&kept, false), &candidates(&added, true), options);
+
+    let mut files: Vec<Option<FileDiff>> = files.into_iter().map(Some).collect();
+    let mut renamed_away = HashSet::new();
+    for pairing in pairings {
+        let source = if pairing.source < removed.len() {
+            removed[pairing.source]
+        } else {
+            kept[pairing.source - removed.len()]
+        };
+        let (path, old) = {
+            let source = files[source].as_ref().expect("sources stay until the end");
+            (source.path.clone(), source.old.clone())
+        };
+        let file = files[added[pairing.destination]].as_mut().expect("each file is paired once");
+        file.status = if pairing.copy { DiffStatus::Copied } else { DiffStatus::Renamed };
+        file.origin = Some(Origin { path, similarity: pairing.similarity });
+        file.old = old;
+        if !pairing.copy {
+            renamed_away.insert(source);
+        }
+    }
+
+    let mut files: Vec<FileDiff> = files.into_iter()
+        .enumerate()
+        .filter(|(i, _)| !renamed_away.contains(i))
+        .filter_map(|(_, file)| file)
+        .collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    files
+}
+
+/// Mode and blob id of every file on one side.
+fn snapshot(repo: &dyn Repository, source: &Source) -> Result<BTreeMap<String, Entry>> {
+    Ok(match source {
+        Source::Empty => BTreeMap::new(),
+        Source::Commit(id) => repo.commit_files(id)?
+            .into_iter()
+            .map(|(path, (mode, id))| (path, Entry { mode, id }))
+            .collect(),
+        Source::Index => repo.load_index()?
+            .entries()
+            .map(|entry| (entry.path.clone(), Entry { mode: entry.mode, id: entry.id.clone() }))
+            .collect(),
+        Source::Worktree => {
+            let root = &repo.location().work_tree;
+            let mut files = BTreeMap::new();
+            for entry in repo.load_index()?.entries() {
+                let full_path = root.join(&entry.path);
+                let Some(mode) = index::worktree_mode(&full_path) else {
+                    continue;
+                };
+                // Unchanged files keep their staged id without re-hashing
+                let id = if entry.matches_file(&full_path, repo.format())? {
+                    entry.id.clone()
+                } else {
+                    hash_object(repo.format(), ObjectKind::Blob, &read_worktree(&full_path, mode)?)
+                };
+                files.insert(entry.path.clone(), Entry { mode, id });
+            }
+            files
+        }
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    mode: u32,
+    id: String,
+}
+
+/// Work out the two sides from the arguments: nothing compares the index
+/// with the working tree, `--staged` HEAD with the index, one commit that
+/// commit with the working tree (or the index with `--staged`), and two
+/// commits or `a..b` one commit with another. Arguments that don't name a
+/// commit are paths, as are all arguments after a path or `--`, so
+/// `../README.md` is a path unless both its sides name commits.
+pub fn sources(repo: &dyn Repository, staged: bool, args: &[String], after_dashes: &[String]) -> Result<(Source, Source, Vec<String>)> {
+    let location = repo.location();
+    let mut revisions = Vec::new();
+    let mut paths = Vec::new();
+    for arg in args {
+        if paths.is_empty()
+            && let Some(range) = revision_range(repo, arg)
+        {
+            revisions.extend(range);
+        } else if paths.is_empty() && let Ok(id) = repo.resolve(arg) {
+            revisions.push(id);
+        } else if location.work_tree.join(location.repo_path(arg)?).exists() || !after_dashes.is_empty() {
+            paths.push(location.repo_path(arg)?);
+        } else {
+            bail!("'{}' is neither a revision nor a path in the working tree (use '--' to separate paths from revisions)", arg);
+        }
+    }
+    for path in after_dashes {
+        paths.push(location.repo_path(path)?);
+    }
+
+    let head = || -> Result<Source> {
+        Ok(match repo.head_commit()? {
+            Some(id) => Source::Commit(id),
+            None => Source::Empty,
+        })
+    };
+    let (old, new) = match (revisions.as_slice(), staged) {
+        ([], false) => (Source::Index, Source::Worktree),
+        ([], true) => (head()?, Source::Index),
+        ([commit], false) => (Source::Commit(commit.clone()), Source::Worktree),
+        ([commit], true) => (Source::Commit(commit.clone()), Source::Index),
+        ([from, to], false) => (Source::Commit(from.clone()), Source::Commit(to.clone())),
+        ([_, _], true) => bail!("--staged compares with the index, so it takes at most one commit"),
+        _ => bail!("too many revisions: diff compares at most two commits"),
+    };
+    Ok((old, new, paths))
+}
+
+/// `a..b` as the two commits it names, an empty side meaning HEAD, or
+/// `None` unless both sides resolve.
+fn revision_range(repo: &dyn Repository, arg: &str) -> Option<[String; 2]> {
+    let (from, to) = arg.split_once("..")?;
+    let resolve = |side: &str| repo.resolve(if side.is_empty() { "HEAD" } else { side }).ok();
+    Some([resolve(from)?, resolve(to)?])
+}
+
+/// Read a file's contents from wherever `source` keeps them.
+fn load(repo: &dyn Repository, attributes: &Attributes, path: &str, entry: &Entry, source: &Source) -> Result<Blob> {
+    let data = match source {
+        Source::Worktree => read_worktree(&repo.location().work_tree.join(path), entry.mode)?,
+        Source::Empty | Source::Commit(_) | Source::Index => repo.read_blob(&entry.id)?,
+    };
+    let binary = !is_symlink(entry.mode) && binary::is_binary(attributes, path, &data);
+    Ok(Blob { mode: entry.mode, id: entry.id.clone(), data, binary })
+}
+
+fn read_worktree(full_path: &Path, mode: u32) -> Result<Vec<u8>> {
+    // A symlink's contents are the path it points to
+    if mode == MODE_SYMLINK {
+        return Ok(fs::read_link(full_path)?.to_string_lossy().as_bytes().to_vec());
+    }
+    Ok(fs::read(full_path)?)
+}
+
+fn is_symlink(mode: u32) -> bool {
+    mode == MODE_SYMLINK
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::location::Location;
+    use crate::repository;
+    use crate::test_repo::TestRepo;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn arguments_are_revisions_ranges_or_paths() {
+        let repo = TestRepo::new();
+        repo.write("README.md", "one\n");
+        repo.write("src/main.rs", "fn main() {}\n");
+        repo.add(&[""]);
+        let first = repo.commit("first", 1000);
+        repo.write("README.md", "two\n");
+        repo.add(&["README.md"]);
+        let second = repo.commit("second", 1001);
+
+        // Run from `src/`, where `..` also starts paths
+        let location = Location { cwd: repo.path("src"), ..repo.repo.location().clone() };
+        let repo = repository::open(location).unwrap();
+        let sources = |list: &[&str]| sources(repo.as_ref(), false, &args(list), &[]).unwrap();
+
+        assert_eq!(sources(&["../README.md"]), (Source::Index, Source::Worktree, args(&["README.md"])));
+        assert_eq!(sources(&["main.rs"]), (Source::Index, Source::Worktree, args(&["src/main.rs"])));
+        assert_eq!(
+            sources(&[&format!("{}..{}", first, second), "../README.md"]),
+            (Source::Commit(first.clone()), Source::Commit(second.clone()), args(&["README.md"]))
+        );
+        assert_eq!(sources(&[&format!("{}..", first)]), (Source::Commit(first), Source::Commit(second), args(&[])));
+        assert!(super::sources(repo.as_ref(), false, &args(&["../missing"]), &[]).is_err());
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use super::{Marks, Range, myers};
+
+/// Lines occurring more often than this on the old side are never used as
+/// anchors; if nothing else matches the range falls back to Myers.
+const MAX_CHAIN: usize = 64;
+
+/// Histogram diff, as in jgit and git: find the longest common run whose
+/// rarest line is as rare as possible, match it, and repeat on both sides
+/// of it.
+pub(super) fn diff(a: &[usize], b: &[usize], range: Range, marks: &mut Marks) {
+    let mut pending = vec![range];
+    while let Some(range) = pending.pop() {
+        let range = range.trim(a, b);
+        if range.is_one_sided() {
+            marks.mark_all(range);
+            continue;
+        }
+        match best_run(a, b, range) {
+            Some(run) => {
+                pending.push(Range { a_lo: run.a_end, b_lo: run.b_end, ..range });
+                pending.push(Range { a_hi: run.a_start, b_hi: run.b_start, ..range });
+            }
+            None => myers::diff(a, b, range, marks),
+        }
+    }
+}
+
+/// A stretch of lines common to both sides.
+#[derive(Debug, Clone, Copy)]
+struct Run {
+    a_start: usize,
+    a_end: usize,
+    b_start: usize,
+    b_end: usize,
+    /// How often the run's rarest line occurs on the old side.
+    rarity: usize,
+}
+
+fn best_run(a: &[usize], b: &[usize], range: Range) -> Option<Run> {
+    // Where each line occurs on the old side
+    let mut occurrences: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, line) in a.iter().enumerate().take(range.a_hi).skip(range.a_lo) {
+        occurrences.entry(*line).or_default().push(i);
+    }
+    let count = |line: &usize| occurrences.get(line).map_or(0, Vec::len);
+
+    let mut best: Option<Run> = None;
+    let mut j = range.b_lo;
+    while j < range.b_hi {
+        let Some(positions) = occurrences.get(&b[j]).filter(|positions| positions.len() <= MAX_CHAIN) else {
+            j += 1;
+            continue;
+        };
+        // Candidates rarer than the best so far, or as rare and longer
+        if best.is_some_and(|best| positions.len() > best.rarity) {
+            j += 1;
+            continue;
+        }
+
+        let mut next_j = j + 1;
+        for &i in positions {
+            let (mut a_start, mut b_start) = (i, j);
+            while a_start > range.a_lo && b_start > range.b_lo && a[a_start - 1] == b[b_start - 1] {
+                a_start -= 1;
+                b_start -= 1;
+            }
+            let (mut a_end, mut b_end) = (i + 1, j + 1);
+            while a_end < range.a_hi && b_end < range.b_hi && a[a_end] == b[b_end] {
+                a_end += 1;
+                b_end += 1;
+            }
+            let rarity = a[a_start..a_end].iter().map(count).min().unwrap_or(usize::MAX);
+            let candidate = Run { a_start, a_end, b_start, b_end, rarity };
+            let better = match best {
+                None => true,
+                Some(best) => rarity < best.rarity
+                    || (rarity == best.rarity && a_end - a_start > best.a_end - best.a_start),
+            };
+            if better {
+                best = Some(candidate);
+                // Lines inside this run can't start a longer one
+                next_j = next_j.max(b_end);
+            }
+        }
+        j = next_j;
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::check::{common, lcs, random_pairs};
+
+    #[test]
+    fn keeps_a_common_subsequence() {
+        for (a, b) in random_pairs(2000) {
+            assert!(common(diff, &a, &b) <= lcs(&a, &b));
+        }
+    }
+
+    #[test]
+    fn anchors_on_the_rarest_line() {
+        // Matching `1 2 1 2` would keep more, but `3` is rarer, as in git
+        assert_eq!(common(diff, &[1, 2, 1, 2, 3], &[3, 1, 2, 1, 2]), 1);
+    }
+
+    #[test]
+    fn extends_the_anchor_into_a_run() {
+        assert_eq!(common(diff, &[5, 1, 2, 3, 6], &[7, 1, 2, 3, 8, 1]), 3);
+    }
+
+    #[test]
+    fn lines_repeated_too_often_fall_back_to_myers() {
+        let run = MAX_CHAIN + 10;
+        let a = [vec![0; run], vec![1; run]].concat();
+        let b = [vec![1; run], vec![0; run]].concat();
+        assert_eq!(common(diff, &a, &b), run);
+    }
+}
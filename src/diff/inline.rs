@@ -0,0 +1,91 @@
+use std::ops::Range;
+use super::{Algorithm, Edit, diff};
+
+/// Part of a line, marked as changed when it has no counterpart on the
+/// other side. Ranges are byte offsets into the line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub range: Range<usize>,
+    pub changed: bool,
+}
+
+/// Split text into words (runs of letters, digits and `_`), runs of
+/// whitespace, and single characters for everything else, so a changed
+/// bracket or operator is shown on its own.
+pub fn tokenize(text: &str) -> Vec<Range<usize>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let same_kind: fn(char) -> bool = if is_word(c) {
+            is_word
+        } else if c.is_whitespace() {
+            char::is_whitespace
+        } else {
+            |_| false
+        };
+        let mut end = start + c.len_utf8();
+        while let Some(&(i, next)) = chars.peek() {
+            if !same_kind(next) {
+                break;
+            }
+            end = i + next.len_utf8();
+            chars.next();
+        }
+        tokens.push(start..end);
+    }
+    tokens
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Compare two versions of a line token by token, returning the spans of
+/// each side. When the lines have too little in common for the result to
+/// help, `None` is returned and the whole lines count as changed.
+pub fn inline_diff(old: &str, new: &str) -> Option<(Vec<Span>, Vec<Span>)> {
+    let old_tokens = tokenize(old);
+    let new_tokens = tokenize(new);
+    let old_words: Vec<&str> = old_tokens.iter().map(|range| &old[range.clone()]).collect();
+    let new_words: Vec<&str> = new_tokens.iter().map(|range| &new[range.clone()]).collect();
+
+    let edits = diff(&old_words, &new_words, Algorithm::Myers);
+    let common: usize = edits.iter()
+        .filter_map(|edit| match edit {
+            Edit::Equal { old, .. } if !old_words[*old].trim().is_empty() => Some(old_words[*old].chars().count()),
+            _ => None,
+        })
+        .sum();
+    // Less than a third in common reads better as two unrelated lines
+    let visible = |text: &str| text.chars().filter(|c| !c.is_whitespace()).count();
+    if common * 3 < visible(old).max(visible(new)) {
+        return None;
+    }
+
+    let mut old_spans = Vec::new();
+    let mut new_spans = Vec::new();
+    for edit in edits {
+        match edit {
+            Edit::Equal { old, new } => {
+                push(&mut old_spans, old_tokens[old].clone(), false);
+                push(&mut new_spans, new_tokens[new].clone(), false);
+            }
+            Edit::Delete { old } => push(&mut old_spans, old_tokens[old].clone(), true),
+            Edit::Insert { new } => push(&mut new_spans, new_tokens[new].clone(), true),
+        }
+    }
+    Some((old_spans, new_spans))
+}
+
+/// Add a token, merging it into the previous span when both are changed
+/// or both unchanged.
+fn push(spans: &mut Vec<Span>, range: Range<usize>, changed: bool) {
+    if let Some(last) = spans.last_mut()
+        && last.changed == changed
+        && last.range.end == range.start
+    {
+        last.range.end = range.end;
+        return;
+    }
+    spans.push(Span { range, changed });
+}
@@ -0,0 +1,562 @@
+//! Line diffs: the algorithms that find what changed between two
+//! sequences, the hunks that group those changes for display, and the
+//! comparison of whole snapshots (commits, the index, the working tree)
+//! that `rit diff` is built on.
+
+use anyhow::{Result, bail};
+use std::collections::HashMap;
+use std::hash::Hash;
+
+mod files;
+mod histogram;
+mod inline;
+mod myers;
+mod patience;
+mod renames;
+mod words;
+
+pub use files::{Blob, DiffStatus, FileDiff, Source, detect_renames, diff_files, sources};
+pub use inline::{Span, inline_diff, tokenize};
+pub use renames::{
+    Candidate, DEFAULT_THRESHOLD, Origin, Pairing, RenameOptions, find_renames, parse_threshold, similarity,
+};
+pub use words::{WordChange, word_diff, words};
+
+/// How the longest common subsequence is searched for. All three find a
+/// minimal or near-minimal diff; they differ in which of several equally
+/// short diffs they pick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Algorithm {
+    /// Eugene Myers' O(ND) algorithm, git's default.
+    #[default]
+    Myers,
+    /// Anchors on lines that occur rarely, which keeps blocks of code
+    /// together where Myers would match up blank lines and braces.
+    Histogram,
+    /// Anchors on lines that occur exactly once on each side.
+    Patience,
+}
+
+impl Algorithm {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "myers" | "default" => Ok(Algorithm::Myers),
+            "histogram" => Ok(Algorithm::Histogram),
+            "patience" => Ok(Algorithm::Patience),
+            other => bail!("unknown diff algorithm '{}' (expected myers, histogram or patience)", other),
+        }
+    }
+}
+
+/// One step of an edit script turning the old sequence into the new one.
+/// Positions are 0-based indexes into the two sequences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    /// `old[old]` and `new[new]` are the same.
+    Equal { old: usize, new: usize },
+    /// `old[old]` was removed.
+    Delete { old: usize },
+    /// `new[new]` was added.
+    Insert { new: usize },
+}
+
+/// Compare two sequences, returning every element of both in order:
+/// common elements once as [`Edit::Equal`], and within each changed
+/// stretch the deletions before the insertions.
+pub fn diff<T: Eq + Hash>(old: &[T], new: &[T], algorithm: Algorithm) -> Vec<Edit> {
+    // Elements are compared many times over, so give equal ones the same
+    // small number first
+    let mut ids = HashMap::new();
+    let mut intern = |item| {
+        let next = ids.len();
+        *ids.entry(item).or_insert(next)
+    };
+    let a: Vec<usize> = old.iter().map(&mut intern).collect();
+    let b: Vec<usize> = new.iter().map(&mut intern).collect();
+
+    let mut marks = Marks {
+        deleted: vec![false; a.len()],
+        inserted: vec![false; b.len()],
+    };
+    let whole = Range { a_lo: 0, a_hi: a.len(), b_lo: 0, b_hi: b.len() };
+    match algorithm {
+        Algorithm::Myers => myers::diff(&a, &b, whole, &mut marks),
+        Algorithm::Histogram => histogram::diff(&a, &b, whole, &mut marks),
+        Algorithm::Patience => patience::diff(&a, &b, whole, &mut marks),
+    }
+    compact(&mut marks.deleted, &a, &marks.inserted);
+    compact(&mut marks.inserted, &b, &marks.deleted);
+
+    let mut edits = Vec::with_capacity(a.len().max(b.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && marks.deleted[i] {
+            edits.push(Edit::Delete { old: i });
+            i += 1;
+        } else if j < b.len() && marks.inserted[j] {
+            edits.push(Edit::Insert { new: j });
+            j += 1;
+        } else {
+            edits.push(Edit::Equal { old: i, new: j });
+            i += 1;
+            j += 1;
+        }
+    }
+    edits
+}
+
+/// A run of changes with the unchanged lines around them, as shown
+/// after an `@@ -old_start,old_len +new_start,new_len @@` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    /// 1-based first line on each side, or the line before the hunk when
+    /// that side is empty, as in unified diffs.
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    pub edits: Vec<Edit>,
+}
+
+impl Hunk {
+    /// The `@@ -1,4 +1,5 @@` line, without a trailing section heading.
+    pub fn header(&self) -> String {
+        format!("@@ -{} +{} @@", range(self.old_start, self.old_len), range(self.new_start, self.new_len))
+    }
+}
+
+fn range(start: usize, len: usize) -> String {
+    if len == 1 {
+        start.to_string()
+    } else {
+        format!("{},{}", start, len)
+    }
+}
+
+/// Group an edit script into hunks with `context` unchanged lines around
+/// each change. Changes closer together than twice the context share a
+/// hunk.
+pub fn hunks(edits: &[Edit], context: usize) -> Vec<Hunk> {
+    let changes: Vec<usize> = edits.iter()
+        .enumerate()
+        .filter(|(_, edit)| !matches!(edit, Edit::Equal { .. }))
+        .map(|(i, _)| i)
+        .collect();
+    let Some(&first) = changes.first() else {
+        return Vec::new();
+    };
+
+    // Spans of `edits` to show, widened by the context and merged when
+    // they touch
+    let mut spans = Vec::new();
+    let (mut start, mut end) = (first, first);
+    for &i in &changes[1..] {
+        if i - end > 2 * context + 1 {
+            spans.push((start, end));
+            start = i;
+        }
+        end = i;
+    }
+    spans.push((start, end));
+
+    spans.into_iter()
+        .map(|(start, end)| {
+            let start = start.saturating_sub(context);
+            let end = (end + context + 1).min(edits.len());
+            let slice = &edits[start..end];
+
+            // Where each side is just before the hunk
+            let (old_before, new_before) = edits[..start].iter().fold((0, 0), |(old, new), edit| match edit {
+                Edit::Equal { .. } => (old + 1, new + 1),
+                Edit::Delete { .. } => (old + 1, new),
+                Edit::Insert { .. } => (old, new + 1),
+            });
+            let old_len = slice.iter().filter(|edit| !matches!(edit, Edit::Insert { .. })).count();
+            let new_len = slice.iter().filter(|edit| !matches!(edit, Edit::Delete { .. })).count();
+            Hunk {
+                old_start: if old_len == 0 { old_before } else { old_before + 1 },
+                old_len,
+                new_start: if new_len == 0 { new_before } else { new_before + 1 },
+                new_len,
+                edits: slice.to_vec(),
+            }
+        })
+        .collect()
+}
+
+/// Split text into lines, each keeping its `\n`. A missing newline at the
+/// end is part of the last line's identity, so `a` and `a\n` differ.
+pub fn lines(text: &[u8]) -> Vec<&[u8]> {
+    text.split_inclusive(|&byte| byte == b'\n').collect()
+}
+
+/// Which elements of each side are not part of the common subsequence.
+/// The algorithms only ever set marks; [`diff`] turns them into edits.
+struct Marks {
+    deleted: Vec<bool>,
+    inserted: Vec<bool>,
+}
+
+impl Marks {
+    /// Everything left in `range` differs.
+    fn mark_all(&mut self, range: Range) {
+        self.deleted[range.a_lo..range.a_hi].fill(true);
+        self.inserted[range.b_lo..range.b_hi].fill(true);
+    }
+}
+
+/// Slide each run of changes on one side as far down as it goes, as git
+/// does, so that of several equally short diffs the same one is shown:
+/// going from `a b` to `a b a b` inserts the last `a b`, not the first.
+/// A run that can line up with a change on the other side is slid back up
+/// to it, so a replaced line stays next to its replacement.
+fn compact(changed: &mut [bool], items: &[usize], other: &[bool]) {
+    let mut group = Group::first(changed);
+    let mut other_group = Group::first(other);
+    loop {
+        if group.end > group.start {
+            let mut earliest_end;
+            let mut end_matching_other;
+            loop {
+                let size = group.end - group.start;
+                // Only a position found on this pass can be slid back to
+                end_matching_other = None;
+                // The runs on both sides move in step, so the other side
+                // always has a run before this one to step back to
+                while group.slide_up(changed, items) && other_group.previous(other) {}
+                earliest_end = group.end;
+                if other_group.end > other_group.start {
+                    end_matching_other = Some(group.end);
+                }
+                while group.slide_down(changed, items) {
+                    other_group.next(other);
+                    if other_group.end > other_group.start {
+                        end_matching_other = Some(group.end);
+                    }
+                }
+                // Sliding merged in a neighbouring run; go again
+                if group.end - group.start == size {
+                    break;
+                }
+            }
+            if group.end != earliest_end && end_matching_other.is_some() {
+                while other_group.end == other_group.start
+                    && group.slide_up(changed, items)
+                    && other_group.previous(other)
+                {}
+            }
+        }
+        if group.end >= changed.len() {
+            break;
+        }
+        group.next(changed);
+        other_group.next(other);
+    }
+}
+
+/// A run of changed elements `start..end` on one side. Runs on both sides
+/// correspond one to one, separated by the common elements, and either
+/// may be empty.
+struct Group {
+    start: usize,
+    end: usize,
+}
+
+impl Group {
+    fn first(changed: &[bool]) -> Self {
+        let mut group = Group { start: 0, end: 0 };
+        group.extend(changed);
+        group
+    }
+
+    fn extend(&mut self, changed: &[bool]) {
+        while self.end < changed.len() && changed[self.end] {
+            self.end += 1;
+        }
+    }
+
+    fn next(&mut self, changed: &[bool]) {
+        self.start = self.end + 1;
+        self.end = self.start;
+        self.extend(changed);
+    }
+
+    /// Step back to the run before this one, unless this is the first.
+    fn previous(&mut self, changed: &[bool]) -> bool {
+        if self.start == 0 {
+            return false;
+        }
+        self.end = self.start - 1;
+        self.start = self.end;
+        while self.start > 0 && changed[self.start - 1] {
+            self.start -= 1;
+        }
+        true
+    }
+
+    /// Move the run down one element when the element after it equals its
+    /// first, merging with the next run if they meet.
+    fn slide_down(&mut self, changed: &mut [bool], items: &[usize]) -> bool {
+        if self.end >= items.len() || items[self.start] != items[self.end] {
+            return false;
+        }
+        changed[self.start] = false;
+        changed[self.end] = true;
+        self.start += 1;
+        self.end += 1;
+        self.extend(changed);
+        true
+    }
+
+    fn slide_up(&mut self, changed: &mut [bool], items: &[usize]) -> bool {
+        if self.start == 0 || items[self.start - 1] != items[self.end - 1] {
+            return false;
+        }
+        self.start -= 1;
+        self.end -= 1;
+        changed[self.start] = true;
+        changed[self.end] = false;
+        while self.start > 0 && changed[self.start - 1] {
+            self.start -= 1;
+        }
+        true
+    }
+}
+
+/// Half-open windows `a[a_lo..a_hi]` and `b[b_lo..b_hi]` still to compare.
+#[derive(Debug, Clone, Copy)]
+struct Range {
+    a_lo: usize,
+    a_hi: usize,
+    b_lo: usize,
+    b_hi: usize,
+}
+
+impl Range {
+    /// Drop the common prefix and suffix, which every algorithm matches
+    /// the same way.
+    fn trim(mut self, a: &[usize], b: &[usize]) -> Self {
+        while self.a_lo < self.a_hi && self.b_lo < self.b_hi && a[self.a_lo] == b[self.b_lo] {
+            self.a_lo += 1;
+            self.b_lo += 1;
+        }
+        while self.a_lo < self.a_hi && self.b_lo < self.b_hi && a[self.a_hi - 1] == b[self.b_hi - 1] {
+            self.a_hi -= 1;
+            self.b_hi -= 1;
+        }
+        self
+    }
+
+    fn is_one_sided(&self) -> bool {
+        self.a_lo == self.a_hi || self.b_lo == self.b_hi
+    }
+}
+
+/// Checks shared by the tests of each algorithm.
+#[cfg(test)]
+mod check {
+    use super::{Marks, Range};
+
+    pub(super) type Algorithm = fn(&[usize], &[usize], Range, &mut Marks);
+
+    /// Run `algorithm` alone, without the sliding [`super::diff`] does
+    /// afterwards, and count the elements it kept in common, checking
+    /// that they really are the same on both sides and in the same order.
+    pub(super) fn common(algorithm: Algorithm, a: &[usize], b: &[usize]) -> usize {
+        let mut marks = Marks { deleted: vec![false; a.len()], inserted: vec![false; b.len()] };
+        algorithm(a, b, Range { a_lo: 0, a_hi: a.len(), b_lo: 0, b_hi: b.len() }, &mut marks);
+        let kept_a: Vec<usize> = a.iter().zip(&marks.deleted).filter(|(_, deleted)| !**deleted).map(|(x, _)| *x).collect();
+        let kept_b: Vec<usize> = b.iter().zip(&marks.inserted).filter(|(_, inserted)| !**inserted).map(|(x, _)| *x).collect();
+        assert_eq!(kept_a, kept_b, "{:?} -> {:?}", a, b);
+        kept_a.len()
+    }
+
+    /// Length of the longest common subsequence, the slow way.
+    pub(super) fn lcs(a: &[usize], b: &[usize]) -> usize {
+        let mut lengths = vec![vec![0; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lengths[i][j] = if a[i] == b[j] { lengths[i + 1][j + 1] + 1 } else { lengths[i + 1][j].max(lengths[i][j + 1]) };
+            }
+        }
+        lengths[0][0]
+    }
+
+    /// Pairs of short sequences over small alphabets, so they have a lot
+    /// in common, the same from run to run.
+    pub(super) fn random_pairs(count: usize) -> Vec<(Vec<usize>, Vec<usize>)> {
+        let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut next = |limit: u64| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((state >> 33) % limit) as usize
+        };
+        (0..count)
+            .map(|_| {
+                let alphabet = next(6) as u64 + 1;
+                let a = (0..next(30)).map(|_| next(alphabet)).collect();
+                let b = (0..next(30)).map(|_| next(alphabet)).collect();
+                (a, b)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALGORITHMS: [Algorithm; 3] = [Algorithm::Myers, Algorithm::Histogram, Algorithm::Patience];
+
+    /// Rebuild `new` from `old` with the edit script, checking that it
+    /// walks both sides in order and only pairs up equal elements.
+    fn apply<T: Clone + PartialEq + std::fmt::Debug>(old: &[T], new: &[T], edits: &[Edit]) -> Vec<T> {
+        let (mut i, mut j) = (0, 0);
+        let mut result = Vec::new();
+        for edit in edits {
+            match *edit {
+                Edit::Equal { old: o, new: n } => {
+                    assert_eq!((o, n), (i, j), "edits out of order");
+                    assert_eq!(old[o], new[n], "unequal elements paired up");
+                    result.push(old[o].clone());
+                    i += 1;
+                    j += 1;
+                }
+                Edit::Delete { old: o } => {
+                    assert_eq!(o, i, "edits out of order");
+                    i += 1;
+                }
+                Edit::Insert { new: n } => {
+                    assert_eq!(n, j, "edits out of order");
+                    result.push(new[n].clone());
+                    j += 1;
+                }
+            }
+        }
+        assert_eq!((i, j), (old.len(), new.len()), "elements left out");
+        result
+    }
+
+    fn split(text: &str) -> Vec<&str> {
+        text.split_whitespace().collect()
+    }
+
+    #[test]
+    fn runs_at_the_start_of_a_file_stay_in_bounds() {
+        let old = split("3 1 0 3 0 2 2 1 2 2 1");
+        let new = split("3 1 2 2 3 0 0 1 0");
+        for algorithm in ALGORITHMS {
+            let edits = diff(&old, &new, algorithm);
+            assert_eq!(apply(&old, &new, &edits), new, "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn insertions_slide_down() {
+        let old = split("a b");
+        let new = split("a b a b");
+        assert_eq!(diff(&old, &new, Algorithm::Myers), [
+            Edit::Equal { old: 0, new: 0 },
+            Edit::Equal { old: 1, new: 1 },
+            Edit::Insert { new: 2 },
+            Edit::Insert { new: 3 },
+        ]);
+    }
+
+    #[test]
+    fn deletions_slide_down() {
+        let old = split("a b a b");
+        let new = split("a b");
+        assert_eq!(diff(&old, &new, Algorithm::Myers), [
+            Edit::Equal { old: 0, new: 0 },
+            Edit::Equal { old: 1, new: 1 },
+            Edit::Delete { old: 2 },
+            Edit::Delete { old: 3 },
+        ]);
+    }
+
+    #[test]
+    fn replaced_lines_stay_next_to_their_replacement() {
+        let old = split("b a");
+        let new = split("a a");
+        for algorithm in ALGORITHMS {
+            assert_eq!(diff(&old, &new, algorithm), [
+                Edit::Delete { old: 0 },
+                Edit::Insert { new: 0 },
+                Edit::Equal { old: 1, new: 1 },
+            ], "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn compact_keeps_the_number_of_changes() {
+        let items = [0, 1, 0, 1];
+        let mut changed = [true, true, false, false];
+        compact(&mut changed, &items, &[false, false]);
+        assert_eq!(changed, [false, false, true, true]);
+    }
+
+    #[test]
+    fn random_inputs_round_trip() {
+        // A small linear congruential generator keeps the cases the same
+        // from run to run
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |limit: u64| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 33) % limit
+        };
+        for _ in 0..5000 {
+            let alphabet = next(4) + 1;
+            let old: Vec<u64> = (0..next(20)).map(|_| next(alphabet)).collect();
+            let new: Vec<u64> = (0..next(20)).map(|_| next(alphabet)).collect();
+            for algorithm in ALGORITHMS {
+                let edits = diff(&old, &new, algorithm);
+                assert_eq!(apply(&old, &new, &edits), new, "{:?} {:?} -> {:?}", algorithm, old, new);
+            }
+        }
+    }
+
+    /// The headers of the hunks between two texts of one number per line.
+    fn headers(old: &str, new: &str, context: usize) -> Vec<String> {
+        let edits = diff(&split(old), &split(new), Algorithm::Myers);
+        hunks(&edits, context).iter().map(Hunk::header).collect()
+    }
+
+    fn numbers(replace: &[(usize, &'static str)]) -> String {
+        (1..=20)
+            .map(|n| replace.iter().find(|(at, _)| *at == n).map_or(n.to_string(), |(_, text)| text.to_string()))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn hunk_headers_match_git() {
+        let old = numbers(&[]);
+        assert_eq!(headers(&old, &numbers(&[(5, "five")]), 3), ["@@ -2,7 +2,7 @@"]);
+        // Changes with up to twice the context between them share a hunk
+        assert_eq!(headers(&old, &numbers(&[(5, "five"), (12, "twelve")]), 3), ["@@ -2,14 +2,14 @@"]);
+        assert_eq!(headers(&old, &numbers(&[(5, "five"), (13, "x")]), 3), ["@@ -2,7 +2,7 @@", "@@ -10,7 +10,7 @@"]);
+        // An empty side counts from the line before
+        assert_eq!(headers("", "a b", 3), ["@@ -0,0 +1,2 @@"]);
+        assert_eq!(headers("a b", "", 3), ["@@ -1,2 +0,0 @@"]);
+        assert_eq!(headers("1 2 3", "1 x 2 3", 1), ["@@ -1,2 +1,3 @@"]);
+        assert_eq!(headers("1 2 3", "1 x 2 3", 0), ["@@ -1,0 +2 @@"]);
+        assert_eq!(headers("1 x 2 3", "1 2 3", 0), ["@@ -2 +1,0 @@"]);
+        assert!(headers("1 2", "1 2", 3).is_empty());
+    }
+
+    #[test]
+    fn hunks_cover_the_edits_they_describe() {
+        let old = split("a b c d e f g h i j k l m n o p");
+        let new = split("a B c d e f g h i j k l m N o p q");
+        let edits = diff(&old, &new, Algorithm::Myers);
+        for hunk in hunks(&edits, 2) {
+            let old_len = hunk.edits.iter().filter(|edit| !matches!(edit, Edit::Insert { .. })).count();
+            let new_len = hunk.edits.iter().filter(|edit| !matches!(edit, Edit::Delete { .. })).count();
+            assert_eq!((hunk.old_len, hunk.new_len), (old_len, new_len));
+            match hunk.edits[0] {
+                Edit::Equal { old, new } => assert_eq!((hunk.old_start, hunk.new_start), (old + 1, new + 1)),
+                _ => panic!("hunk doesn't start with context: {:?}", hunk),
+            }
+        }
+    }
+}
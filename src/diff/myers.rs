@@ -0,0 +1,159 @@
+use super::{Marks, Range};
+
+/// Myers' diff in linear space: find the middle snake of the shortest
+/// edit script, split the problem there and solve both halves.
+pub(super) fn diff(a: &[usize], b: &[usize], range: Range, marks: &mut Marks) {
+    // An explicit stack instead of recursion, so long inputs can't
+    // overflow the call stack
+    let mut pending = vec![range];
+    while let Some(range) = pending.pop() {
+        let range = range.trim(a, b);
+        if range.is_one_sided() {
+            marks.mark_all(range);
+            continue;
+        }
+        match middle_snake(a, b, range) {
+            Some((x, y)) => {
+                pending.push(Range { a_lo: x, b_lo: y, ..range });
+                pending.push(Range { a_hi: x, b_hi: y, ..range });
+            }
+            None => marks.mark_all(range),
+        }
+    }
+}
+
+/// Walk the edit graph from both corners at once until the paths meet,
+/// returning the point to split at. `None` means nothing in the range is
+/// common.
+fn middle_snake(a: &[usize], b: &[usize], range: Range) -> Option<(usize, usize)> {
+    let a = &a[range.a_lo..range.a_hi];
+    let b = &b[range.b_lo..range.b_hi];
+    let (n, m) = (a.len() as isize, b.len() as isize);
+
+    let max_d = (n + m + 1) / 2;
+    let offset = max_d;
+    let width = 2 * max_d + 2;
+    // Furthest x reached on each diagonal k = x - y, forwards and backwards
+    let mut forward = vec![-1isize; width as usize];
+    let mut backward = vec![-1isize; width as usize];
+    forward[(offset + 1) as usize] = 0;
+    backward[(offset + 1) as usize] = 0;
+
+    // Past this many edits, settle for the forward path that got furthest
+    // instead of the minimal script, as git does, so large rewrites stay
+    // fast
+    let max_cost = (((n + m) as f64).sqrt() as isize).max(256);
+    let mut furthest: Option<(isize, isize)> = None;
+
+    let delta = n - m;
+    // With an odd delta the paths meet while extending forwards
+    let front = delta % 2 != 0;
+    // Diagonals that ran off the edge of the graph are skipped
+    let (mut k1_start, mut k1_end, mut k2_start, mut k2_end) = (0, 0, 0, 0);
+
+    for d in 0..max_d {
+        let mut k1 = -d + k1_start;
+        while k1 <= d - k1_end {
+            let k1_offset = (offset + k1) as usize;
+            let mut x1 = if k1 == -d || (k1 != d && forward[k1_offset - 1] < forward[k1_offset + 1]) {
+                forward[k1_offset + 1]
+            } else {
+                forward[k1_offset - 1] + 1
+            };
+            let mut y1 = x1 - k1;
+            while x1 < n && y1 < m && a[x1 as usize] == b[y1 as usize] {
+                x1 += 1;
+                y1 += 1;
+            }
+            forward[k1_offset] = x1;
+            if x1 <= n && y1 <= m && (x1, y1) != (n, m) && furthest.is_none_or(|(x, y)| x1 + y1 > x + y) {
+                furthest = Some((x1, y1));
+            }
+            if x1 > n {
+                k1_end += 2;
+            } else if y1 > m {
+                k1_start += 2;
+            } else if front {
+                let k2_offset = offset + delta - k1;
+                if k2_offset >= 0 && k2_offset < width && backward[k2_offset as usize] != -1 {
+                    let x2 = n - backward[k2_offset as usize];
+                    if x1 >= x2 {
+                        return Some((range.a_lo + x1 as usize, range.b_lo + y1 as usize));
+                    }
+                }
+            }
+            k1 += 2;
+        }
+
+        let mut k2 = -d + k2_start;
+        while k2 <= d - k2_end {
+            let k2_offset = (offset + k2) as usize;
+            let mut x2 = if k2 == -d || (k2 != d && backward[k2_offset - 1] < backward[k2_offset + 1]) {
+                backward[k2_offset + 1]
+            } else {
+                backward[k2_offset - 1] + 1
+            };
+            let mut y2 = x2 - k2;
+            while x2 < n && y2 < m && a[(n - x2 - 1) as usize] == b[(m - y2 - 1) as usize] {
+                x2 += 1;
+                y2 += 1;
+            }
+            backward[k2_offset] = x2;
+            if x2 > n {
+                k2_end += 2;
+            } else if y2 > m {
+                k2_start += 2;
+            } else if !front {
+                let k1_offset = offset + delta - k2;
+                if k1_offset >= 0 && k1_offset < width && forward[k1_offset as usize] != -1 {
+                    let x1 = forward[k1_offset as usize];
+                    let y1 = offset + x1 - k1_offset;
+                    if x1 >= n - x2 {
+                        return Some((range.a_lo + x1 as usize, range.b_lo + y1 as usize));
+                    }
+                }
+            }
+            k2 += 2;
+        }
+
+        if d >= max_cost
+            && let Some((x, y)) = furthest
+        {
+            return Some((range.a_lo + x as usize, range.b_lo + y as usize));
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::check::{common, lcs, random_pairs};
+
+    #[test]
+    fn finds_a_longest_common_subsequence() {
+        // The example from Myers' paper
+        assert_eq!(common(diff, &[0, 1, 2, 0, 1, 1, 0], &[2, 1, 0, 1, 0, 2]), 4);
+        for (a, b) in random_pairs(2000) {
+            assert_eq!(common(diff, &a, &b), lcs(&a, &b), "{:?} -> {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn one_sided_and_disjoint_inputs() {
+        assert_eq!(common(diff, &[], &[]), 0);
+        assert_eq!(common(diff, &[1, 2], &[]), 0);
+        assert_eq!(common(diff, &[], &[1, 2]), 0);
+        assert_eq!(common(diff, &[1, 2, 3], &[4, 5]), 0);
+    }
+
+    #[test]
+    fn long_inputs_with_few_changes() {
+        let a: Vec<usize> = (0..200_000).collect();
+        let mut b = a.clone();
+        b[100_000] = usize::MAX;
+        b.insert(150_000, usize::MAX - 1);
+        assert_eq!(common(diff, &a, &b), a.len() - 1);
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use super::{Marks, Range, myers};
+
+/// Patience diff: match up the lines that occur exactly once on each side,
+/// keep the longest run of them that is in the same order on both, and
+/// diff the gaps between those anchors the same way. Gaps without unique
+/// lines fall back to Myers.
+pub(super) fn diff(a: &[usize], b: &[usize], range: Range, marks: &mut Marks) {
+    let mut pending = vec![range];
+    while let Some(range) = pending.pop() {
+        let range = range.trim(a, b);
+        if range.is_one_sided() {
+            marks.mark_all(range);
+            continue;
+        }
+        let anchors = anchors(a, b, range);
+        if anchors.is_empty() {
+            myers::diff(a, b, range, marks);
+            continue;
+        }
+
+        // The anchors match each other; everything between them is
+        // compared again
+        let (mut a_lo, mut b_lo) = (range.a_lo, range.b_lo);
+        for (i, j) in anchors {
+            pending.push(Range { a_lo, a_hi: i, b_lo, b_hi: j });
+            a_lo = i + 1;
+            b_lo = j + 1;
+        }
+        pending.push(Range { a_lo, b_lo, ..range });
+    }
+}
+
+/// Pairs `(i, j)` of lines unique on both sides, increasing in both.
+fn anchors(a: &[usize], b: &[usize], range: Range) -> Vec<(usize, usize)> {
+    // Line -> (count and position on the old side, count and position on
+    // the new side)
+    let mut seen: HashMap<usize, (usize, usize, usize, usize)> = HashMap::new();
+    for (i, line) in a.iter().enumerate().take(range.a_hi).skip(range.a_lo) {
+        let entry = seen.entry(*line).or_insert((0, i, 0, 0));
+        entry.0 += 1;
+    }
+    for (j, line) in b.iter().enumerate().take(range.b_hi).skip(range.b_lo) {
+        if let Some(entry) = seen.get_mut(line) {
+            entry.2 += 1;
+            entry.3 = j;
+        }
+    }
+    let mut unique: Vec<(usize, usize)> = seen.into_values()
+        .filter(|&(a_count, _, b_count, _)| a_count == 1 && b_count == 1)
+        .map(|(_, i, _, j)| (i, j))
+        .collect();
+    unique.sort_by_key(|&(_, j)| j);
+
+    longest_increasing(&unique)
+}
+
+/// Patience sorting: the longest subsequence of `pairs` (already in new
+/// side order) whose old side positions also increase.
+fn longest_increasing(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    // Index of the pair on top of each pile, and each pair's predecessor
+    let mut piles: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = vec![None; pairs.len()];
+    for (index, &(i, _)) in pairs.iter().enumerate() {
+        let pile = piles.partition_point(|&top| pairs[top].0 < i);
+        previous[index] = pile.checked_sub(1).map(|pile| piles[pile]);
+        if pile == piles.len() {
+            piles.push(index);
+        } else {
+            piles[pile] = index;
+        }
+    }
+
+    let mut result = Vec::new();
+    let mut current = piles.last().copied();
+    while let Some(index) = current {
+        result.push(pairs[index]);
+        current = previous[index];
+    }
+    result.reverse();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::check::{common, lcs, random_pairs};
+
+    #[test]
+    fn keeps_a_common_subsequence() {
+        for (a, b) in random_pairs(2000) {
+            assert!(common(diff, &a, &b) <= lcs(&a, &b));
+        }
+    }
+
+    #[test]
+    fn anchors_on_lines_unique_to_each_side() {
+        // `3` is the only line found once on each side, as in git
+        assert_eq!(common(diff, &[1, 2, 1, 2, 3], &[3, 1, 2, 1, 2]), 1);
+    }
+
+    #[test]
+    fn keeps_the_longest_run_of_anchors_in_order() {
+        assert_eq!(common(diff, &[1, 2, 3, 4], &[4, 1, 2, 3]), 3);
+        assert_eq!(common(diff, &[1, 2, 3, 4, 5], &[1, 4, 2, 5, 3]), 3);
+    }
+
+    #[test]
+    fn gaps_without_unique_lines_fall_back_to_myers() {
+        assert_eq!(common(diff, &[1, 1, 2, 2], &[2, 2, 1, 1]), 2);
+        let (a, b) = ([1, 2, 1, 2, 2], [2, 1, 1, 2, 1]);
+        assert_eq!(common(diff, &a, &b), lcs(&a, &b));
+    }
+}
@@ -0,0 +1,325 @@
+use anyhow::{Result, bail};
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Files at least this similar are paired up unless told otherwise, as in
+/// git.
+pub const DEFAULT_THRESHOLD: u8 = 50;
+
+/// Beyond this many sources times destinations only exact renames are
+/// looked for, since every pair would have to be compared.
+const MAX_PAIRS: usize = 1000 * 1000;
+
+/// How to pair removed and added files up as renames and copies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenameOptions {
+    /// Minimum similarity, in percent.
+    pub threshold: u8,
+    /// Also look for new files copied from files that were modified, and
+    /// let one removed file become several new ones.
+    pub copies: bool,
+}
+
+impl Default for RenameOptions {
+    fn default() -> Self {
+        RenameOptions { threshold: DEFAULT_THRESHOLD, copies: false }
+    }
+}
+
+impl RenameOptions {
+    /// Rename detection as configured by `key`, such as `diff.renames`:
+    /// on unless set to false, and finding copies too when set to `copies`.
+    pub fn from_config(key: &str, value: Option<&str>) -> Result<Option<Self>> {
+        let copies = match value.map(str::to_lowercase).as_deref() {
+            None => false,
+            Some("copies" | "copy") => true,
+            Some(value) => {
+                if !crate::config::parse_bool(key, value)? {
+                    return Ok(None);
+                }
+                false
+            }
+        };
+        Ok(Some(RenameOptions { copies, ..Default::default() }))
+    }
+}
+
+/// Parse a similarity threshold the way git reads `-M`: `50%` is a
+/// percentage, while bare digits are the digits after a decimal point, so
+/// `5` and `50` both mean half. An empty value is the default.
+pub fn parse_threshold(text: &str) -> Result<u8> {
+    if text.is_empty() {
+        return Ok(DEFAULT_THRESHOLD);
+    }
+    let (digits, percent) = match text.strip_suffix('%') {
+        Some(digits) => (digits, true),
+        None => (text, false),
+    };
+    if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        bail!("invalid similarity '{}' (expected a percentage such as 50%)", text);
+    }
+    let threshold = if percent {
+        digits.parse::<u32>().unwrap_or(u32::MAX)
+    } else {
+        // The first two digits after the point are the percentage
+        let padded = format!("{:0<2}", digits);
+        padded[..2].parse().expect("two digits")
+    };
+    if threshold > 100 {
+        bail!("invalid similarity '{}' (at most 100%)", text);
+    }
+    Ok(threshold as u8)
+}
+
+/// Where a renamed or copied file came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    pub path: String,
+    /// How much of the contents the two share, in percent.
+    pub similarity: u8,
+}
+
+/// A file that may take part in a rename or copy.
+#[derive(Debug, Clone, Copy)]
+pub struct Candidate<'a> {
+    pub path: &'a str,
+    pub id: &'a str,
+    pub data: &'a [u8],
+    /// Symlinks are only paired with symlinks.
+    pub is_symlink: bool,
+}
+
+/// A destination paired with the source it was renamed or copied from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pairing {
+    /// Index into the removed files, or past them into the kept ones.
+    pub source: usize,
+    /// Index into the destinations.
+    pub destination: usize,
+    pub similarity: u8,
+    /// The source stays where it was, so this is a copy, not a rename.
+    pub copy: bool,
+}
+
+/// Pair new files up with the files they most likely came from. `removed`
+/// are the files gone from the new side and `kept` those still there
+/// (only looked at for copies); `added` are the new files. Identical
+/// contents are paired first, then the most similar pairs down to the
+/// threshold. A removed file paired with several new ones is renamed to
+/// the last of them and copied to the others, as in git.
+pub fn find_renames(removed: &[Candidate], kept: &[Candidate], added: &[Candidate], options: RenameOptions) -> Vec<Pairing> {
+    // Removed files first, so a source's position is its index
+    let sources: Vec<&Candidate> = removed.iter()
+        .chain(kept.iter().filter(|_| options.copies))
+        .collect();
+
+    // Every pair worth considering, best first
+    let mut scored: Vec<(u8, usize, usize)> = Vec::new();
+    let compare_all = sources.len() * added.len() <= MAX_PAIRS;
+    for (destination, new) in added.iter().enumerate() {
+        if new.data.is_empty() {
+            continue;
+        }
+        for (position, old) in sources.iter().enumerate() {
+            // Empty files are all alike, so they say nothing about renames
+            if old.data.is_empty() || old.is_symlink != new.is_symlink {
+                continue;
+            }
+            // Files too different in size can't reach the threshold
+            let (smaller, larger) = (old.data.len().min(new.data.len()), old.data.len().max(new.data.len()));
+            let score = if old.id == new.id {
+                100
+            } else if compare_all && !new.is_symlink && smaller * 100 >= larger * options.threshold as usize {
+                similarity(old.data, new.data)
+            } else {
+                continue;
+            };
+            if score >= options.threshold.max(1) {
+                scored.push((score, position, destination));
+            }
+        }
+    }
+    // Ties go to the source with the same file name, then to path order
+    let file_name = |path: &str| path.rsplit('/').next().unwrap_or(path).to_string();
+    scored.sort_by_key(|&(score, position, destination)| {
+        let same_name = file_name(sources[position].path) == file_name(added[destination].path);
+        (std::cmp::Reverse(score), !same_name, position, destination)
+    });
+
+    let mut taken = vec![false; added.len()];
+    let mut used = vec![0usize; sources.len()];
+    let mut pairings = Vec::new();
+    for (score, position, destination) in scored {
+        let is_removed = position < removed.len();
+        if taken[destination] || (is_removed && used[position] > 0 && !options.copies) {
+            continue;
+        }
+        taken[destination] = true;
+        used[position] += 1;
+        pairings.push(Pairing { source: position, destination, similarity: score, copy: !is_removed });
+    }
+
+    // Of the new files taken from one removed file, only the last (in path
+    // order) is the rename
+    pairings.sort_by_key(|pairing| (pairing.source, std::cmp::Reverse(added[pairing.destination].path)));
+    let mut previous = None;
+    for pairing in &mut pairings {
+        if pairing.source < removed.len() {
+            pairing.copy = previous == Some(pairing.source);
+        }
+        previous = Some(pairing.source);
+    }
+    pairings.sort_by_key(|pairing| pairing.destination);
+    pairings
+}
+
+/// How much of `old` is still in `new`, in percent of the larger of the
+/// two. Files are cut into lines (and long lines into 64-byte pieces),
+/// and the bytes of the pieces they have in common are counted.
+pub fn similarity(old: &[u8], new: &[u8]) -> u8 {
+    let larger = old.len().max(new.len());
+    if larger == 0 {
+        return 100;
+    }
+
+    let old_chunks = chunks(old);
+    let new_chunks = chunks(new);
+    let common: usize = old_chunks.iter()
+        .filter_map(|(hash, old_bytes)| new_chunks.get(hash).map(|new_bytes| (*old_bytes).min(*new_bytes)))
+        .sum();
+    (common * 100 / larger) as u8
+}
+
+/// Bytes per distinct piece of `data`.
+fn chunks(data: &[u8]) -> HashMap<u64, usize> {
+    let mut chunks = HashMap::new();
+    let mut start = 0;
+    while start < data.len() {
+        let limit = (start + 64).min(data.len());
+        let end = data[start..limit].iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(limit, |newline| start + newline + 1);
+        let mut hasher = DefaultHasher::new();
+        data[start..end].hash(&mut hasher);
+        *chunks.entry(hasher.finish()).or_insert(0) += end - start;
+        start = end;
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ten numbered lines, with the ones in `changed` rewritten.
+    fn text(changed: &[usize]) -> Vec<u8> {
+        (0..10)
+            .map(|n| if changed.contains(&n) { format!("changed line {}\n", n) } else { format!("original line {}\n", n) })
+            .collect::<String>()
+            .into_bytes()
+    }
+
+    fn candidate<'a>(path: &'a str, data: &'a [u8]) -> Candidate<'a> {
+        // Only equal ids matter here, so the contents stand in for them
+        Candidate { path, id: std::str::from_utf8(data).unwrap(), data, is_symlink: false }
+    }
+
+    #[test]
+    fn similarity_counts_the_bytes_in_common() {
+        assert_eq!(similarity(b"", b""), 100);
+        assert_eq!(similarity(&text(&[]), &text(&[])), 100);
+        assert_eq!(similarity(b"a\nb\n", b"c\nd\n"), 0);
+        // Two short lines out of ten
+        assert_eq!(similarity(&text(&[]), &text(&[3, 7])), 80);
+        // Appending half again as much leaves two thirds in common
+        assert_eq!(similarity(b"aaaa\nbbbb\n", b"aaaa\nbbbb\ncccc\n"), 66);
+        // Long lines are compared in 64-byte pieces
+        let old = "x".repeat(640);
+        let new = format!("{}y", &old[..639]);
+        assert_eq!(similarity(old.as_bytes(), new.as_bytes()), 90);
+    }
+
+    #[test]
+    fn thresholds_are_read_like_git() {
+        assert_eq!(parse_threshold("").unwrap(), DEFAULT_THRESHOLD);
+        assert_eq!(parse_threshold("75%").unwrap(), 75);
+        assert_eq!(parse_threshold("5").unwrap(), 50);
+        assert_eq!(parse_threshold("50").unwrap(), 50);
+        assert_eq!(parse_threshold("05").unwrap(), 5);
+        assert_eq!(parse_threshold("9999").unwrap(), 99);
+        assert_eq!(parse_threshold("100%").unwrap(), 100);
+        assert!(parse_threshold("101%").is_err());
+        assert!(parse_threshold("%").is_err());
+        assert!(parse_threshold("half").is_err());
+    }
+
+    #[test]
+    fn config_values_turn_detection_on_and_off() {
+        assert_eq!(RenameOptions::from_config("diff.renames", None).unwrap(), Some(RenameOptions::default()));
+        assert_eq!(RenameOptions::from_config("diff.renames", Some("false")).unwrap(), None);
+        assert!(RenameOptions::from_config("diff.renames", Some("copies")).unwrap().unwrap().copies);
+        assert!(RenameOptions::from_config("diff.renames", Some("sometimes")).is_err());
+    }
+
+    #[test]
+    fn the_most_similar_files_are_paired() {
+        let (original, close, far) = (text(&[]), text(&[1]), text(&[1, 2, 3, 4, 5, 6]));
+        let removed = [candidate("old", &original)];
+        let added = [candidate("far", &far), candidate("close", &close)];
+        let pairings = find_renames(&removed, &[], &added, RenameOptions::default());
+        assert_eq!(pairings, [Pairing { source: 0, destination: 1, similarity: 90, copy: false }]);
+
+        // Below the threshold nothing is paired
+        let strict = RenameOptions { threshold: 95, ..Default::default() };
+        assert!(find_renames(&removed, &[], &added, strict).is_empty());
+    }
+
+    #[test]
+    fn ties_go_to_the_same_file_name() {
+        let data = text(&[]);
+        let removed = [candidate("a/lib.rs", &data), candidate("b/main.rs", &data)];
+        let added = [candidate("c/main.rs", &data)];
+        let pairings = find_renames(&removed, &[], &added, RenameOptions::default());
+        assert_eq!(pairings[0].source, 1);
+    }
+
+    #[test]
+    fn empty_files_and_symlinks_are_not_paired_with_files() {
+        let data = text(&[]);
+        let removed = [candidate("empty", b""), Candidate { is_symlink: true, ..candidate("link", &data) }];
+        let added = [candidate("new-empty", b""), candidate("file", &data)];
+        assert!(find_renames(&removed, &[], &added, RenameOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn one_file_split_in_two_is_renamed_once_and_copied_once() {
+        let data = text(&[]);
+        let removed = [candidate("old", &data)];
+        let added = [candidate("a", &data), candidate("b", &data)];
+
+        // Without copies the second new file stays new
+        let pairings = find_renames(&removed, &[], &added, RenameOptions::default());
+        assert_eq!(pairings.len(), 1);
+
+        let copies = RenameOptions { copies: true, ..Default::default() };
+        let pairings = find_renames(&removed, &[], &added, copies);
+        assert_eq!(pairings, [
+            Pairing { source: 0, destination: 0, similarity: 100, copy: true },
+            Pairing { source: 0, destination: 1, similarity: 100, copy: false },
+        ]);
+    }
+
+    #[test]
+    fn kept_files_are_only_sources_of_copies() {
+        let data = text(&[]);
+        let kept = [candidate("kept", &data)];
+        let added = [candidate("copy", &data)];
+        assert!(find_renames(&[], &kept, &added, RenameOptions::default()).is_empty());
+
+        let copies = RenameOptions { copies: true, ..Default::default() };
+        assert_eq!(find_renames(&[], &kept, &added, copies), [
+            Pairing { source: 0, destination: 0, similarity: 100, copy: true },
+        ]);
+    }
+}
@@ -0,0 +1,180 @@
+use regex::Regex;
+use std::ops::Range;
+use super::{Algorithm, Edit, diff};
+
+/// A piece of a word diff, borrowed from the side it was taken from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordChange<'a> {
+    /// Text common to both sides, as it reads on the new side.
+    Same(&'a str),
+    /// Words from the old side, with the whitespace between them.
+    Deleted(&'a str),
+    /// Words from the new side, with the whitespace between them.
+    Inserted(&'a str),
+}
+
+/// Find the words of `text`: the matches of `regex`, or runs of
+/// non-whitespace without one. Words never span lines, and whatever lies
+/// between them is treated as whitespace.
+pub fn words(text: &str, regex: Option<&Regex>) -> Vec<Range<usize>> {
+    let Some(regex) = regex else {
+        let mut words = Vec::new();
+        let mut start = None;
+        for (i, c) in text.char_indices() {
+            match (c.is_whitespace(), start) {
+                (false, None) => start = Some(i),
+                (true, Some(begin)) => {
+                    words.push(begin..i);
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(begin) = start {
+            words.push(begin..text.len());
+        }
+        return words;
+    };
+
+    // A match running into the next line is cut at the newline, and the
+    // search picks up again from there
+    let mut words = Vec::new();
+    let mut at = 0;
+    while let Some(found) = regex.find_at(text, at) {
+        let end = text[found.range()].find('\n').map_or(found.end(), |newline| found.start() + newline);
+        if end > found.start() {
+            words.push(found.start()..end);
+            at = end;
+        } else {
+            at = end + text[end..].chars().next().map_or(1, char::len_utf8);
+        }
+        if at >= text.len() {
+            break;
+        }
+    }
+    words
+}
+
+/// Compare two runs of lines word by word, the way `git diff --word-diff`
+/// does: changed words are reported with the text between them, and
+/// everything in common is taken from the new side, so the result reads as
+/// the new text with the old words spliced in.
+pub fn word_diff<'a>(old: &'a str, new: &'a str, regex: Option<&Regex>, algorithm: Algorithm) -> Vec<WordChange<'a>> {
+    // Nothing is left to line the old words up against
+    if new.is_empty() {
+        return if old.is_empty() { Vec::new() } else { vec![WordChange::Deleted(old)] };
+    }
+
+    let old_words = words(old, regex);
+    let new_words = words(new, regex);
+    let old_text: Vec<&str> = old_words.iter().map(|range| &old[range.clone()]).collect();
+    let new_text: Vec<&str> = new_words.iter().map(|range| &new[range.clone()]).collect();
+    let edits = diff(&old_text, &new_text, algorithm);
+
+    let mut changes = Vec::new();
+    // How much of the new side has been reported so far
+    let mut shown = 0;
+    let mut i = 0;
+    while i < edits.len() {
+        if let Edit::Equal { .. } = edits[i] {
+            i += 1;
+            continue;
+        }
+
+        // A run of changes always follows a common word or the start, so
+        // with nothing inserted the change sits just after that word
+        let insert_at = match i.checked_sub(1).map(|previous| edits[previous]) {
+            Some(Edit::Equal { new, .. }) => new_words[new].end,
+            _ => 0,
+        };
+        let mut deleted: Option<Range<usize>> = None;
+        let mut inserted: Option<Range<usize>> = None;
+        while let Some(&edit) = edits.get(i) {
+            match edit {
+                Edit::Delete { old } => {
+                    let word = &old_words[old];
+                    deleted = Some(deleted.map_or(word.clone(), |range| range.start..word.end));
+                }
+                Edit::Insert { new } => {
+                    let word = &new_words[new];
+                    inserted = Some(inserted.map_or(word.clone(), |range| range.start..word.end));
+                }
+                Edit::Equal { .. } => break,
+            }
+            i += 1;
+        }
+
+        let (start, end) = inserted.as_ref().map_or((insert_at, insert_at), |range| (range.start, range.end));
+        if start > shown {
+            changes.push(WordChange::Same(&new[shown..start]));
+        }
+        if let Some(range) = deleted {
+            changes.push(WordChange::Deleted(&old[range]));
+        }
+        if let Some(range) = inserted {
+            changes.push(WordChange::Inserted(&new[range]));
+        }
+        shown = shown.max(end);
+    }
+    if shown < new.len() {
+        changes.push(WordChange::Same(&new[shown..]));
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Show a word diff the way `git diff --word-diff=plain` does.
+    fn plain(old: &str, new: &str, regex: Option<&str>) -> String {
+        let regex = regex.map(|regex| Regex::new(regex).unwrap());
+        word_diff(old, new, regex.as_ref(), Algorithm::Myers)
+            .into_iter()
+            .map(|change| match change {
+                WordChange::Same(text) => text.to_string(),
+                WordChange::Deleted(text) => format!("[-{}-]", text),
+                WordChange::Inserted(text) => format!("{{+{}+}}", text),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn changed_words_read_like_git() {
+        assert_eq!(plain("the quick brown fox", "the slow brown fox", None), "the [-quick-]{+slow+} brown fox");
+        assert_eq!(plain("a b c", "a b c d", None), "a b c {+d+}");
+        assert_eq!(plain("x y z", "y z", None), "[-x-]y z");
+        assert_eq!(plain("one  two", "one three two", None), "one {+three+} two");
+        assert_eq!(plain("foo(bar, baz)", "foo(bar, qux)", None), "foo(bar, [-baz)-]{+qux)+}");
+    }
+
+    #[test]
+    fn a_regex_decides_what_a_word_is() {
+        let regex = Some("[a-z]+|[^[:space:]]");
+        assert_eq!(plain("foo(bar, baz)", "foo(bar, qux)", regex), "foo(bar, [-baz-]{+qux+})");
+    }
+
+    #[test]
+    fn empty_sides() {
+        assert_eq!(plain("", "", None), "");
+        assert_eq!(plain("gone\n", "", None), "[-gone\n-]");
+        assert_eq!(plain("", "new words", None), "{+new words+}");
+    }
+
+    #[test]
+    fn words_never_span_lines() {
+        let text = "ab\ncd ef";
+        let found: Vec<&str> = words(text, None).into_iter().map(|range| &text[range]).collect();
+        assert_eq!(found, ["ab", "cd", "ef"]);
+
+        let regex = Regex::new("[a-z\n]+").unwrap();
+        let found: Vec<&str> = words(text, Some(&regex)).into_iter().map(|range| &text[range]).collect();
+        assert_eq!(found, ["ab", "cd", "ef"]);
+
+        // Empty matches are skipped a character at a time
+        let text = "é x";
+        let regex = Regex::new("x*").unwrap();
+        let found: Vec<&str> = words(text, Some(&regex)).into_iter().map(|range| &text[range]).collect();
+        assert_eq!(found, ["x"]);
+    }
+}
@@ -0,0 +1,557 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use crate::location::Location;
+
+/// Per-directory ignore files, read in this order so that `.ritignore`
+/// rules win over `.gitignore` rules in the same directory.
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".ritignore"];
+
+/// Directories that are never part of the working tree.
+const METADATA_DIRS: [&str; 2] = [".git", ".rit"];
+
+/// Patterns rit applies before any ignore file. An ignore file can
+/// re-include them with `!target/`.
+const BUILT_IN_PATTERNS: [&str; 2] = ["target/", "node_modules/"];
+
+/// Where a rule came from, for `rit check-ignore`.
+pub const BUILT_IN_SOURCE: &str = "<built-in>";
+
+/// One line of an ignore file.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    /// The file the rule was read from, relative to the repository root.
+    pub source: String,
+    /// 1-based line number within `source` (0 for built-in rules).
+    pub line: usize,
+    /// The pattern as written.
+    pub pattern: String,
+    /// `!pattern`: re-include paths an earlier rule ignored.
+    pub negated: bool,
+    /// `pattern/`: only match directories.
+    dir_only: bool,
+    /// Patterns containing a `/` match the whole path relative to `base`;
+    /// others match the file name at any depth.
+    anchored: bool,
+    glob: Vec<char>,
+    /// Directory of the ignore file, `""` or ending in `/`.
+    base: String,
+}
+
+impl Rule {
+    /// Parse one line of an ignore file. Blank lines and comments yield
+    /// `None`.
+    fn parse(text: &str, source: &str, line: usize, base: &str) -> Option<Rule> {
+        let text = text.strip_suffix('\r').unwrap_or(text);
+        let pattern = trim_trailing_spaces(text);
+        if pattern.is_empty() || pattern.starts_with('#') {
+            return None;
+        }
+
+        let (negated, mut glob) = match pattern.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, pattern),
+        };
+        // `\#` and `\!` escape a leading comment or negation marker
+        if glob.starts_with("\\#") || glob.starts_with("\\!") {
+            glob = &glob[1..];
+        }
+        let dir_only = glob.ends_with('/') && !glob.ends_with("\\/");
+        let glob = glob.trim_end_matches('/');
+        if glob.is_empty() {
+            return None;
+        }
+        let anchored = glob.contains('/');
+        let glob = glob.strip_prefix('/').unwrap_or(glob);
+
+        Some(Rule {
+            source: source.to_string(),
+            line,
+            pattern: pattern.to_string(),
+            negated,
+            dir_only,
+            anchored,
+            glob: glob.chars().collect(),
+            base: base.to_string(),
+        })
+    }
+
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let Some(relative) = path.strip_prefix(&self.base) else {
+            return false;
+        };
+        let text: Vec<char> = if self.anchored {
+            relative.chars().collect()
+        } else {
+            relative.rsplit('/').next().unwrap_or(relative).chars().collect()
+        };
+        wildmatch(&self.glob, &text)
+    }
+}
+
+/// Strip trailing spaces unless they are escaped with a backslash.
+fn trim_trailing_spaces(text: &str) -> &str {
+    let mut end = text.len();
+    while end > 0 && text[..end].ends_with(' ') {
+        if text[..end - 1].ends_with('\\') {
+            break;
+        }
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Match `text` against a gitignore glob: `*` and `?` stop at `/`, `**`
+/// spans directories when it stands alone between slashes, and `[...]`
+/// is a character class.
+pub(crate) fn wildmatch(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    while p < pattern.len() {
+        match pattern[p] {
+            '*' => {
+                let mut end = p;
+                while end < pattern.len() && pattern[end] == '*' {
+                    end += 1;
+                }
+                let whole_component = end - p >= 2
+                    && (p == 0 || pattern[p - 1] == '/')
+                    && (end == pattern.len() || pattern[end] == '/');
+                if whole_component {
+                    // Trailing `**` matches everything below
+                    if end == pattern.len() {
+                        return true;
+                    }
+                    // `**/` matches zero or more directories
+                    let rest = &pattern[end + 1..];
+                    return wildmatch(rest, &text[t..])
+                        || (t..text.len()).any(|i| text[i] == '/' && wildmatch(rest, &text[i + 1..]));
+                }
+
+                let rest = &pattern[end..];
+                for i in t..=text.len() {
+                    if wildmatch(rest, &text[i..]) {
+                        return true;
+                    }
+                    if i < text.len() && text[i] == '/' {
+                        break;
+                    }
+                }
+                return false;
+            }
+            '?' => {
+                if t >= text.len() || text[t] == '/' {
+                    return false;
+                }
+                p += 1;
+                t += 1;
+            }
+            '[' => match match_class(pattern, p, text.get(t).copied()) {
+                Some((true, next)) => {
+                    p = next;
+                    t += 1;
+                }
+                Some((false, _)) => return false,
+                // An unterminated class is a literal `[`
+                None => {
+                    if text.get(t) != Some(&'[') {
+                        return false;
+                    }
+                    p += 1;
+                    t += 1;
+                }
+            },
+            '\\' if p + 1 < pattern.len() => {
+                if text.get(t) != Some(&pattern[p + 1]) {
+                    return false;
+                }
+                p += 2;
+                t += 1;
+            }
+            c => {
+                if text.get(t) != Some(&c) {
+                    return false;
+                }
+                p += 1;
+                t += 1;
+            }
+        }
+    }
+    t == text.len()
+}
+
+/// Match one character against the class starting at `pattern[start]`
+/// (`[`). Returns whether it matched and the index just past the class,
+/// or `None` if the class is never closed.
+fn match_class(pattern: &[char], start: usize, c: Option<char>) -> Option<(bool, usize)> {
+    let mut i = start + 1;
+    let negated = matches!(pattern.get(i), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let current = *pattern.get(i)?;
+        if current == ']' && !first {
+            break;
+        }
+        first = false;
+
+        let low = if current == '\\' {
+            i += 1;
+            *pattern.get(i)?
+        } else {
+            current
+        };
+        if pattern.get(i + 1) == Some(&'-') && pattern.get(i + 2).is_some_and(|&high| high != ']') {
+            let high = pattern[i + 2];
+            if c.is_some_and(|c| low <= c && c <= high) {
+                matched = true;
+            }
+            i += 3;
+        } else {
+            if c == Some(low) {
+                matched = true;
+            }
+            i += 1;
+        }
+    }
+
+    let matched = c.is_some_and(|c| c != '/') && matched != negated;
+    Some((matched, i + 1))
+}
+
+/// Why a path is or isn't ignored.
+#[derive(Debug, Clone)]
+pub enum Decision {
+    /// Inside `.git` or `.rit`.
+    Metadata,
+    /// Decided by a rule; a negated rule means the path is re-included.
+    /// `path` is what the rule matched: the path itself or one of its
+    /// parent directories.
+    Rule { rule: Rule, path: String },
+}
+
+impl Decision {
+    pub fn is_ignored(&self) -> bool {
+        match self {
+            Decision::Metadata => true,
+            Decision::Rule { rule, .. } => !rule.negated,
+        }
+    }
+
+    /// A short description such as `*.log in .ritignore:3`.
+    pub fn describe(&self) -> String {
+        match self {
+            Decision::Metadata => "repository metadata".to_string(),
+            Decision::Rule { rule, .. } if rule.source == BUILT_IN_SOURCE => {
+                format!("{} {}", rule.pattern, BUILT_IN_SOURCE)
+            }
+            Decision::Rule { rule, .. } => format!("{} in {}:{}", rule.pattern, rule.source, rule.line),
+        }
+    }
+}
+
+/// The ignore rules of one working tree: built-in patterns, the
+/// repository's own `info/exclude`, and every `.gitignore` and `.ritignore`
+/// below the root.
+/// Nested ignore files are read the first time a path below them is
+/// checked.
+pub struct IgnoreRules {
+    root: PathBuf,
+    global: Vec<Rule>,
+    per_dir: RefCell<HashMap<String, Rc<Vec<Rule>>>>,
+}
+
+impl IgnoreRules {
+    pub fn load(location: &Location) -> Self {
+        let root = &location.work_tree;
+        let mut global: Vec<Rule> = BUILT_IN_PATTERNS
+            .iter()
+            .filter_map(|pattern| Rule::parse(pattern, BUILT_IN_SOURCE, 0, ""))
+            .collect();
+        // Named relative to the root, as `.rit/info/exclude`, when inside it
+        let exclude = location.metadata_dir().join("info/exclude");
+        let source = exclude.strip_prefix(root).unwrap_or(&exclude).to_string_lossy().replace('\\', "/");
+        global.extend(read_rules(&exclude, &source, ""));
+
+        IgnoreRules {
+            root: root.clone(),
+            global,
+            per_dir: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Whether `path` (relative to the root, `/`-separated) is ignored.
+    pub fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        self.decide(path, is_dir).is_some_and(|decision| decision.is_ignored())
+    }
+
+    /// Find what decides whether `path` is ignored. A path inside an
+    /// ignored directory is ignored no matter what, as in git; otherwise
+    /// the last matching rule wins, with deeper ignore files overriding
+    /// shallower ones.
+    pub fn decide(&self, path: &str, is_dir: bool) -> Option<Decision> {
+        let path = path.trim_matches('/');
+        if path.is_empty() {
+            return None;
+        }
+        if path.split('/').any(|part| METADATA_DIRS.contains(&part)) {
+            return Some(Decision::Metadata);
+        }
+
+        let parts: Vec<&str> = path.split('/').collect();
+        for depth in 1..parts.len() {
+            let parent = parts[..depth].join("/");
+            if let Some(decision) = self.decide_one(&parent, true)
+                && decision.is_ignored()
+            {
+                return Some(decision);
+            }
+        }
+        self.decide_one(path, is_dir)
+    }
+
+    fn decide_one(&self, path: &str, is_dir: bool) -> Option<Decision> {
+        let mut decision = self.global.iter().rev().find(|rule| rule.matches(path, is_dir)).cloned();
+
+        // Ignore files in the root and every directory above the path
+        let parts: Vec<&str> = path.split('/').collect();
+        for depth in 0..parts.len() {
+            let dir = parts[..depth].join("/");
+            let rules = self.rules_in(&dir);
+            if let Some(rule) = rules.iter().rev().find(|rule| rule.matches(path, is_dir)) {
+                decision = Some(rule.clone());
+            }
+        }
+
+        decision.map(|rule| Decision::Rule { rule, path: path.to_string() })
+    }
+
+    /// The rules from the ignore files directly inside `dir`.
+    fn rules_in(&self, dir: &str) -> Rc<Vec<Rule>> {
+        if let Some(rules) = self.per_dir.borrow().get(dir) {
+            return rules.clone();
+        }
+
+        let base = if dir.is_empty() { String::new() } else { format!("{}/", dir) };
+        let mut rules = Vec::new();
+        for name in IGNORE_FILES {
+            let source = format!("{}{}", base, name);
+            rules.extend(read_rules(&self.root.join(&source), &source, &base));
+        }
+        let rules = Rc::new(rules);
+        self.per_dir.borrow_mut().insert(dir.to_string(), rules.clone());
+        rules
+    }
+
+    /// Every file below `start` that isn't ignored, as root-relative paths
+    /// in sorted order, plus the paths that were skipped. Ignored
+    /// directories are not descended into.
+    pub fn walk(&self, start: &Path) -> Walk {
+        let mut walk = Walk::default();
+        let mut ignored = Vec::new();
+
+        let entries = walkdir::WalkDir::new(start)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| {
+                let Some(path) = self.relative(entry.path()) else {
+                    return true;
+                };
+                if path.is_empty() || !self.is_ignored(&path, entry.file_type().is_dir()) {
+                    return true;
+                }
+                if !entry.path().file_name().is_some_and(|name| METADATA_DIRS.iter().any(|dir| name == *dir)) {
+                    ignored.push(path);
+                }
+                false
+            });
+        for entry in entries.flatten() {
+            let file_type = entry.file_type();
+            if (file_type.is_file() || file_type.is_symlink())
+                && let Some(path) = self.relative(entry.path())
+            {
+                walk.files.push(WalkedFile {
+                    path,
+                    full_path: entry.path().to_path_buf(),
+                    is_symlink: file_type.is_symlink(),
+                });
+            }
+        }
+
+        walk.ignored = ignored;
+        walk
+    }
+
+    fn relative(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        Some(relative.to_string_lossy().replace('\\', "/"))
+    }
+}
+
+/// The result of [`IgnoreRules::walk`].
+#[derive(Debug, Default)]
+pub struct Walk {
+    pub files: Vec<WalkedFile>,
+    /// Ignored files and directories, the latter not walked into.
+    pub ignored: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct WalkedFile {
+    /// Relative to the root, `/`-separated.
+    pub path: String,
+    pub full_path: PathBuf,
+    pub is_symlink: bool,
+}
+
+fn read_rules(path: &Path, source: &str, base: &str) -> Vec<Rule> {
+    let Ok(text) = fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    text.lines()
+        .enumerate()
+        .filter_map(|(number, line)| Rule::parse(line, source, number + 1, base))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_repo::TestRepo;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        wildmatch(&pattern.chars().collect::<Vec<_>>(), &text.chars().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn stars_and_question_marks_stop_at_slashes() {
+        assert!(matches("*.log", "debug.log"));
+        assert!(matches("*", ""));
+        assert!(!matches("*.log", "logs/debug.log"));
+        assert!(matches("a*b*c", "aXbYc"));
+        assert!(!matches("a*b", "a/b"));
+        assert!(matches("a?c", "abc"));
+        assert!(!matches("a?c", "a/c"));
+        assert!(!matches("a?c", "ac"));
+    }
+
+    #[test]
+    fn double_stars_span_directories() {
+        assert!(matches("**/foo", "foo"));
+        assert!(matches("**/foo", "a/b/foo"));
+        assert!(!matches("**/foo", "a/xfoo"));
+        assert!(matches("foo/**", "foo/a/b"));
+        assert!(!matches("foo/**", "foo"));
+        assert!(matches("a/**/b", "a/b"));
+        assert!(matches("a/**/b", "a/x/y/b"));
+        assert!(!matches("a/**/b", "a/xb"));
+        // Not standing alone, `**` is just `*`
+        assert!(matches("a**b", "axxb"));
+        assert!(!matches("a**b", "a/b"));
+    }
+
+    #[test]
+    fn character_classes() {
+        assert!(matches("[a-c]x", "bx"));
+        assert!(!matches("[a-c]x", "dx"));
+        assert!(matches("[!a-c]x", "dx"));
+        assert!(matches("[^a-c]x", "dx"));
+        assert!(!matches("[!a-c]x", "ax"));
+        assert!(matches("[]]", "]"));
+        assert!(matches("[a-]", "-"));
+        assert!(matches("[\\]]", "]"));
+        assert!(!matches("[!a]", "/"));
+        // An unterminated class is a literal `[`
+        assert!(matches("[ab", "[ab"));
+    }
+
+    #[test]
+    fn backslashes_escape_the_next_character() {
+        assert!(matches("\\*", "*"));
+        assert!(!matches("\\*", "a"));
+        assert!(matches("a\\?", "a?"));
+        assert!(!matches("a\\?", "ab"));
+    }
+
+    #[test]
+    fn rules_parse_like_gitignore_lines() {
+        assert!(Rule::parse("# comment", ".gitignore", 1, "").is_none());
+        assert!(Rule::parse("   ", ".gitignore", 1, "").is_none());
+
+        let rule = Rule::parse("!build/  ", ".gitignore", 1, "").unwrap();
+        assert!(rule.negated && rule.dir_only && !rule.anchored);
+        assert!(rule.matches("src/build", true));
+        assert!(!rule.matches("src/build", false));
+
+        let rule = Rule::parse("\\#hash\\ ", ".gitignore", 1, "").unwrap();
+        assert!(!rule.negated);
+        assert!(rule.matches("#hash ", false));
+
+        // Patterns with a slash are relative to their ignore file
+        let rule = Rule::parse("/docs/*.md", "sub/.gitignore", 1, "sub/").unwrap();
+        assert!(rule.matches("sub/docs/a.md", false));
+        assert!(!rule.matches("docs/a.md", false));
+        assert!(!rule.matches("sub/x/docs/a.md", false));
+    }
+
+    fn ignored_by(rules: &IgnoreRules, path: &str, is_dir: bool) -> Option<String> {
+        match rules.decide(path, is_dir)? {
+            Decision::Rule { rule, .. } if !rule.negated => Some(format!("{}:{}", rule.source, rule.line)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn nested_ignore_files_override_the_ones_above() {
+        let repo = TestRepo::new();
+        repo.write(".gitignore", "*.log\n*.tmp\n");
+        repo.write(".ritignore", "!keep.tmp\n");
+        repo.write("sub/.gitignore", "!*.log\ncache/\n");
+        repo.write("sub/deep/.ritignore", "*.log\n");
+        let rules = IgnoreRules::load(repo.repo.location());
+
+        assert_eq!(ignored_by(&rules, "debug.log", false).as_deref(), Some(".gitignore:1"));
+        assert_eq!(ignored_by(&rules, "keep.tmp", false), None);
+        assert_eq!(ignored_by(&rules, "sub/keep.tmp", false), None);
+        assert_eq!(ignored_by(&rules, "sub/other.tmp", false).as_deref(), Some(".gitignore:2"));
+        assert_eq!(ignored_by(&rules, "sub/debug.log", false), None);
+        assert_eq!(ignored_by(&rules, "sub/deep/debug.log", false).as_deref(), Some("sub/deep/.ritignore:1"));
+        assert_eq!(ignored_by(&rules, "sub/cache", true).as_deref(), Some("sub/.gitignore:2"));
+        assert_eq!(ignored_by(&rules, "cache", true), None);
+    }
+
+    #[test]
+    fn negation_cannot_reach_inside_an_ignored_directory() {
+        let repo = TestRepo::new();
+        repo.write(".gitignore", "build/\n!build/keep.txt\n");
+        repo.write("build/.gitignore", "!*.txt\n");
+        repo.write("build/keep.txt", "kept?\n");
+        repo.write("src/main.rs", "fn main() {}\n");
+        let rules = IgnoreRules::load(repo.repo.location());
+
+        assert_eq!(ignored_by(&rules, "build/keep.txt", false).as_deref(), Some(".gitignore:1"));
+        let walk = rules.walk(&repo.dir);
+        let files: Vec<&str> = walk.files.iter().map(|file| file.path.as_str()).collect();
+        assert_eq!(files, [".gitignore", "src/main.rs"]);
+        assert_eq!(walk.ignored, ["build"]);
+    }
+
+    #[test]
+    fn info_exclude_comes_from_the_repository() {
+        let repo = TestRepo::new();
+        repo.write(".rit/info/exclude", "# local\nsecret.txt\n");
+        repo.write(".git/info/exclude", "other.txt\n");
+        let rules = IgnoreRules::load(repo.repo.location());
+
+        assert_eq!(ignored_by(&rules, "secret.txt", false).as_deref(), Some(".rit/info/exclude:2"));
+        assert_eq!(ignored_by(&rules, "other.txt", false), None);
+    }
+}
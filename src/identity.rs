@@ -0,0 +1,119 @@
+use anyhow::{Result, anyhow, bail};
+use chrono::{DateTime, FixedOffset, Local, TimeZone};
+use std::env;
+use crate::config::Config;
+use crate::log;
+use crate::objects::{Signature, parse_offset};
+
+/// Which of a commit's two signatures is being resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Whoever wrote the change.
+    Author,
+    /// Whoever recorded it as a commit.
+    Committer,
+}
+
+impl Role {
+    fn name(self) -> &'static str {
+        match self {
+            Role::Author => "author",
+            Role::Committer => "committer",
+        }
+    }
+
+    /// The `RIT_AUTHOR_*` or `RIT_COMMITTER_*` environment variable.
+    fn env(self, field: &str) -> Option<String> {
+        let name = format!("RIT_{}_{}", self.name().to_uppercase(), field);
+        env::var(name).ok().filter(|value| !value.is_empty())
+    }
+}
+
+/// Work out who is signing a commit, the way git does:
+///
+/// - the name comes from `RIT_AUTHOR_NAME` (or `RIT_COMMITTER_NAME`), then
+///   `author.name` (or `committer.name`), then `user.name`;
+/// - the email likewise from `RIT_*_EMAIL`, `author.email` or
+///   `committer.email`, then `user.email`;
+/// - the date from `RIT_*_DATE`, or now.
+///
+/// Nothing is guessed: without a name and an email this fails with a
+/// message saying how to set them.
+pub fn signature(config: &Config, role: Role) -> Result<Signature> {
+    let lookup = |field: &str| {
+        role.env(&field.to_uppercase())
+            .or_else(|| config.get(&format!("{}.{}", role.name(), field)).map(str::to_string))
+            .or_else(|| config.get(&format!("user.{}", field)).map(str::to_string))
+            .filter(|value| !value.trim().is_empty())
+    };
+    let (Some(name), Some(email)) = (lookup("name"), lookup("email")) else {
+        bail!("{} identity unknown\n\n\
+            Tell rit who you are with:\n\n  \
+            rit config --global set user.name \"Your Name\"\n  \
+            rit config --global set user.email \"you@example.com\"\n\n\
+            or set RIT_{}_NAME and RIT_{}_EMAIL for this commit only.",
+            if role == Role::Author { "Author" } else { "Committer" },
+            role.name().to_uppercase(),
+            role.name().to_uppercase()
+        );
+    };
+    checked(name.trim(), email.trim(), date(role)?)
+}
+
+/// When the signature is dated: `RIT_AUTHOR_DATE` (or
+/// `RIT_COMMITTER_DATE`) if set, otherwise now.
+pub fn date(role: Role) -> Result<DateTime<FixedOffset>> {
+    match role.env("DATE") {
+        Some(date) => parse_date(&date),
+        None => Ok(Local::now().fixed_offset()),
+    }
+}
+
+/// Parse `--author "Name <email>"` into a signature at `time`.
+pub fn parse_ident(text: &str, time: DateTime<FixedOffset>) -> Result<Signature> {
+    let (name, email) = text.trim()
+        .strip_suffix('>')
+        .and_then(|rest| rest.split_once('<'))
+        .ok_or_else(|| anyhow!("author '{}' is not in the form 'Name <email>'", text))?;
+    if name.trim().is_empty() {
+        bail!("author '{}' has an empty name", text);
+    }
+    checked(name.trim(), email.trim(), time)
+}
+
+/// Parse a commit date: git's internal `<seconds> <+hhmm>` (optionally
+/// with a leading `@`), RFC 2822, `YYYY-MM-DD HH:MM:SS +hhmm`, or anything
+/// [`log::parse_date`] accepts.
+pub fn parse_date(text: &str) -> Result<DateTime<FixedOffset>> {
+    let text = text.trim();
+    let raw = text.strip_prefix('@').unwrap_or(text);
+    let (seconds, offset) = raw.split_once(' ').unwrap_or((raw, "+0000"));
+    if let Ok(seconds) = seconds.parse::<i64>()
+        && let Some(offset) = parse_offset(offset)
+        && let Some(time) = offset.timestamp_opt(seconds, 0).single()
+    {
+        return Ok(time);
+    }
+    if let Ok(time) = DateTime::parse_from_rfc2822(text) {
+        return Ok(time);
+    }
+    if let Ok(time) = DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S %z") {
+        return Ok(time);
+    }
+    log::parse_date(text)
+}
+
+/// Names and emails are stored between `<` and `>` on one line, so they
+/// can't contain those characters or newlines.
+fn checked(name: &str, email: &str, time: DateTime<FixedOffset>) -> Result<Signature> {
+    for value in [name, email] {
+        if value.contains(['<', '>', '\n']) {
+            bail!("invalid characters in identity '{}'", value);
+        }
+    }
+    Ok(Signature {
+        name: name.to_string(),
+        email: email.to_string(),
+        time,
+    })
+}
@@ -0,0 +1,345 @@
+use anyhow::{Result, bail};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use crate::objects::{MODE_TREE, ObjectFormat, ObjectKind, ObjectStore, Tree, TreeEntry};
+
+// On-disk layout of .rit/index (all integers big-endian):
+//
+//   header   "RIDX" | version: u32 | entry count: u32
+//   entry    mtime secs: u32 | mtime nanos: u32 | mode: u32 | size: u32
+//            | object id: 20 or 32 bytes | path length: u16 | path
+//            | 1-8 NUL bytes padding the entry to a multiple of 8
+//   trailer  hash of everything above
+//
+// Object ids and the trailer use the repository's object format.
+const SIGNATURE: &[u8; 4] = b"RIDX";
+const VERSION: u32 = 1;
+const ENTRY_STAT_BYTES: usize = 4 * 4;
+
+pub const MODE_FILE: u32 = 0o100644;
+pub const MODE_EXECUTABLE: u32 = 0o100755;
+pub const MODE_SYMLINK: u32 = 0o120000;
+
+/// A single staged file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub path: String,
+    pub mode: u32,
+    pub size: u32,
+    pub mtime_secs: u32,
+    pub mtime_nanos: u32,
+    pub id: String,
+}
+
+impl IndexEntry {
+    /// Build an entry for `full_path`, whose contents are stored as blob `id`.
+    pub fn from_file(path: &str, full_path: &Path, id: String) -> Result<Self> {
+        let metadata = fs::symlink_metadata(full_path)?;
+        let (mtime_secs, mtime_nanos) = mtime_of(&metadata);
+        let mode = if metadata.file_type().is_symlink() { MODE_SYMLINK } else { mode_of(&metadata) };
+
+        Ok(IndexEntry {
+            path: path.to_string(),
+            mode,
+            size: metadata.len() as u32,
+            mtime_secs,
+            mtime_nanos,
+            id,
+        })
+    }
+
+    /// Whether the file on disk still has the size and mtime recorded at
+    /// staging time. A match means the contents don't need to be re-hashed.
+    pub fn matches_stat(&self, metadata: &fs::Metadata) -> bool {
+        let (mtime_secs, mtime_nanos) = mtime_of(metadata);
+        self.size == metadata.len() as u32
+            && self.mtime_secs == mtime_secs
+            && self.mtime_nanos == mtime_nanos
+            && self.mode == mode_of(metadata)
+    }
+
+    /// Whether the file at `full_path` still holds the staged contents,
+    /// re-hashing it only when its stat data changed.
+    pub fn matches_file(&self, full_path: &Path, format: ObjectFormat) -> Result<bool> {
+        let Ok(metadata) = fs::symlink_metadata(full_path) else {
+            return Ok(false);
+        };
+        // A symlink's blob holds the path it points to
+        if metadata.file_type().is_symlink() {
+            let target = fs::read_link(full_path)?;
+            let id = crate::objects::hash_object(format, ObjectKind::Blob, target.to_string_lossy().as_bytes());
+            return Ok(self.mode == MODE_SYMLINK && id == self.id);
+        }
+        if self.matches_stat(&metadata) {
+            return Ok(true);
+        }
+        let id = crate::objects::hash_object(format, ObjectKind::Blob, &fs::read(full_path)?);
+        Ok(id == self.id && self.mode == mode_of(&metadata))
+    }
+}
+
+/// The mode a file in the working tree would be staged with, or `None` if
+/// there is no file at `full_path`.
+pub fn worktree_mode(full_path: &Path) -> Option<u32> {
+    let metadata = fs::symlink_metadata(full_path).ok()?;
+    if metadata.file_type().is_symlink() {
+        Some(MODE_SYMLINK)
+    } else if metadata.is_file() {
+        Some(mode_of(&metadata))
+    } else {
+        None
+    }
+}
+
+/// The staging area, kept sorted by path.
+#[derive(Debug)]
+pub struct Index {
+    format: ObjectFormat,
+    entries: BTreeMap<String, IndexEntry>,
+}
+
+impl Index {
+    pub fn new(format: ObjectFormat) -> Self {
+        Index {
+            format,
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Read `.rit/index`, returning an empty index if nothing was staged yet.
+    pub fn load(rit_dir: &Path, format: ObjectFormat) -> Result<Self> {
+        let path = rit_dir.join("index");
+        if !path.exists() {
+            return Ok(Index::new(format));
+        }
+        Self::parse(&fs::read(path)?, format)
+    }
+
+    pub fn save(&self, rit_dir: &Path) -> Result<()> {
+        let data = self.serialize();
+        let tmp_path = rit_dir.join("index.lock");
+        fs::write(&tmp_path, data)?;
+        fs::rename(tmp_path, rit_dir.join("index"))?;
+        Ok(())
+    }
+
+    pub fn add(&mut self, entry: IndexEntry) {
+        self.entries.insert(entry.path.clone(), entry);
+    }
+
+    pub fn remove(&mut self, path: &str) -> Option<IndexEntry> {
+        self.entries.remove(path)
+    }
+
+    pub fn get(&self, path: &str) -> Option<&IndexEntry> {
+        self.entries.get(path)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &IndexEntry> {
+        self.entries.values()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Store the staged files as a hierarchy of tree objects and return the
+    /// id of the root tree.
+    pub fn write_tree(&self, store: &ObjectStore) -> Result<String> {
+        let entries: Vec<&IndexEntry> = self.entries.values().collect();
+        write_subtree(store, &entries, "")
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(SIGNATURE);
+        data.extend_from_slice(&VERSION.to_be_bytes());
+        data.extend_from_slice(&(self.entries.len() as u32).to_be_bytes());
+
+        for entry in self.entries.values() {
+            let start = data.len();
+            data.extend_from_slice(&entry.mtime_secs.to_be_bytes());
+            data.extend_from_slice(&entry.mtime_nanos.to_be_bytes());
+            data.extend_from_slice(&entry.mode.to_be_bytes());
+            data.extend_from_slice(&entry.size.to_be_bytes());
+            data.extend_from_slice(&hex::decode(&entry.id).expect("index ids are valid hex"));
+            data.extend_from_slice(&(entry.path.len() as u16).to_be_bytes());
+            data.extend_from_slice(entry.path.as_bytes());
+
+            let padding = 8 - (data.len() - start) % 8;
+            data.resize(data.len() + padding, 0);
+        }
+
+        let checksum = self.format.digest(&[&data]);
+        data.extend_from_slice(&checksum);
+        data
+    }
+
+    fn parse(data: &[u8], format: ObjectFormat) -> Result<Self> {
+        let oid_bytes = format.raw_len();
+        if data.len() < 12 + oid_bytes || &data[..4] != SIGNATURE {
+            bail!("index file is corrupt: bad signature");
+        }
+        let (body, checksum) = data.split_at(data.len() - oid_bytes);
+        if format.digest(&[body]) != checksum {
+            bail!("index file is corrupt: checksum mismatch");
+        }
+
+        let version = read_u32(body, 4);
+        if version != VERSION {
+            bail!("unsupported index version {}", version);
+        }
+        let count = read_u32(body, 8);
+
+        let entry_fixed_bytes = ENTRY_STAT_BYTES + oid_bytes + 2;
+        let mut index = Index::new(format);
+        let mut offset = 12;
+        for _ in 0..count {
+            if offset + entry_fixed_bytes > body.len() {
+                bail!("index file is corrupt: truncated entry");
+            }
+            let oid_start = offset + ENTRY_STAT_BYTES;
+            let path_len_at = oid_start + oid_bytes;
+            let path_len =
+                u16::from_be_bytes([body[path_len_at], body[path_len_at + 1]]) as usize;
+            let path_start = path_len_at + 2;
+            let Some(path_bytes) = body.get(path_start..path_start + path_len) else {
+                bail!("index file is corrupt: truncated path");
+            };
+
+            index.add(IndexEntry {
+                mtime_secs: read_u32(body, offset),
+                mtime_nanos: read_u32(body, offset + 4),
+                mode: read_u32(body, offset + 8),
+                size: read_u32(body, offset + 12),
+                id: hex::encode(&body[oid_start..path_len_at]),
+                path: String::from_utf8(path_bytes.to_vec())?,
+            });
+
+            let entry_len = entry_fixed_bytes + path_len;
+            offset += entry_len + (8 - entry_len % 8);
+        }
+
+        Ok(index)
+    }
+}
+
+/// Write the tree for the directory `prefix` (either empty or ending in
+/// `/`), given every index entry below it in path order.
+fn write_subtree(store: &ObjectStore, entries: &[&IndexEntry], prefix: &str) -> Result<String> {
+    let mut tree = Tree::default();
+    let mut i = 0;
+    while i < entries.len() {
+        let rest = &entries[i].path[prefix.len()..];
+        match rest.split_once('/') {
+            None => {
+                tree.entries.push(TreeEntry {
+                    mode: entries[i].mode,
+                    name: rest.to_string(),
+                    id: entries[i].id.clone(),
+                });
+                i += 1;
+            }
+            Some((dir, _)) => {
+                let dir_prefix = format!("{}{}/", prefix, dir);
+                let end = i + entries[i..]
+                    .iter()
+                    .take_while(|entry| entry.path.starts_with(&dir_prefix))
+                    .count();
+                tree.entries.push(TreeEntry {
+                    mode: MODE_TREE,
+                    name: dir.to_string(),
+                    id: write_subtree(store, &entries[i..end], &dir_prefix)?,
+                });
+                i = end;
+            }
+        }
+    }
+    store.write(ObjectKind::Tree, &tree.serialize())
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().expect("4-byte slice"))
+}
+
+fn mtime_of(metadata: &fs::Metadata) -> (u32, u32) {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|since| (since.as_secs() as u32, since.subsec_nanos()))
+        .unwrap_or((0, 0))
+}
+
+#[cfg(unix)]
+fn mode_of(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    if metadata.permissions().mode() & 0o111 != 0 {
+        MODE_EXECUTABLE
+    } else {
+        MODE_FILE
+    }
+}
+
+#[cfg(not(unix))]
+fn mode_of(_metadata: &fs::Metadata) -> u32 {
+    MODE_FILE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, id: String) -> IndexEntry {
+        IndexEntry {
+            path: path.to_string(),
+            mode: MODE_FILE,
+            size: path.len() as u32,
+            mtime_secs: 1_700_000_000,
+            mtime_nanos: 123_456_789,
+            id,
+        }
+    }
+
+    #[test]
+    fn entries_round_trip_in_both_formats() {
+        for format in [ObjectFormat::Sha1, ObjectFormat::Sha256] {
+            let id = |byte: u8| hex::encode(vec![byte; format.raw_len()]);
+            let mut index = Index::new(format);
+            // Paths of every length modulo 8 exercise each amount of padding
+            let long = "x".repeat(300);
+            let paths = ["a", "ab", "abc", "dir/abcd", "dir/sub/e", "ünïcode", &long, "z/y"];
+            for (i, path) in paths.iter().enumerate() {
+                index.add(entry(path, id(i as u8)));
+            }
+            index.add(IndexEntry { mode: MODE_SYMLINK, ..entry("link", id(0xab)) });
+            index.add(IndexEntry { mode: MODE_EXECUTABLE, ..entry("run.sh", id(0xff)) });
+
+            let data = index.serialize();
+            assert_eq!(&data[..4], SIGNATURE);
+            let parsed = Index::parse(&data, format).unwrap();
+            assert_eq!(parsed.entries().collect::<Vec<_>>(), index.entries().collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn an_empty_index_round_trips() {
+        let index = Index::new(ObjectFormat::Sha1);
+        assert!(Index::parse(&index.serialize(), ObjectFormat::Sha1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn damaged_files_are_rejected() {
+        let mut index = Index::new(ObjectFormat::Sha1);
+        index.add(entry("file", "11".repeat(20)));
+        let data = index.serialize();
+
+        let mut flipped = data.clone();
+        flipped[20] ^= 1;
+        assert!(Index::parse(&flipped, ObjectFormat::Sha1).unwrap_err().to_string().contains("checksum"));
+        assert!(Index::parse(&data[..10], ObjectFormat::Sha1).unwrap_err().to_string().contains("signature"));
+        assert!(Index::parse(&data, ObjectFormat::Sha256).is_err());
+    }
+}
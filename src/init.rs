@@ -0,0 +1,81 @@
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::config;
+use crate::objects::ObjectFormat;
+
+/// The result of [`init`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InitOutcome {
+    /// `dir` already holds a `.git` repository; nothing was changed.
+    AlreadyGit,
+    /// `dir` already holds a `.rit` repository; nothing was changed.
+    AlreadyRit,
+    Created { rit_dir: PathBuf, created_gitignore: bool },
+}
+
+/// Create an empty `.rit` repository in `dir`, with HEAD on `main`, and a
+/// starter `.gitignore` if there isn't one.
+pub fn init(dir: &Path, object_format: ObjectFormat) -> Result<InitOutcome> {
+    if dir.join(".git").exists() {
+        return Ok(InitOutcome::AlreadyGit);
+    }
+    if dir.join(".rit").exists() {
+        return Ok(InitOutcome::AlreadyRit);
+    }
+
+    // Create .rit directory structure
+    let rit_dir = dir.join(".rit");
+    fs::create_dir_all(&rit_dir)?;
+
+    // Create basic rit structure
+    fs::create_dir_all(rit_dir.join("objects"))?;
+    fs::create_dir_all(rit_dir.join("refs").join("heads"))?;
+    fs::create_dir_all(rit_dir.join("refs").join("tags"))?;
+
+    // Create HEAD file pointing to main branch
+    fs::write(rit_dir.join("HEAD"), "ref: refs/heads/main\n")?;
+
+    // Create config file. Like git, anything other than SHA-1 needs
+    // repository format version 1 and an objectformat extension.
+    let config_path = rit_dir.join("config");
+    let format_version = if object_format == ObjectFormat::Sha1 { "0" } else { "1" };
+    config::set_value(&config_path, "core.repositoryformatversion", format_version)?;
+    config::set_value(&config_path, "core.filemode", "true")?;
+    config::set_value(&config_path, "core.bare", "false")?;
+    config::set_value(&config_path, "core.logallrefupdates", "true")?;
+    if object_format != ObjectFormat::Sha1 {
+        config::set_value(&config_path, "extensions.objectformat", object_format.name())?;
+    }
+    config::set_value(&config_path, "rit.graphical", "true")?;
+    config::set_value(&config_path, "rit.coloroutput", "true")?;
+
+    // Create initial .gitignore if it doesn't exist
+    let mut created_gitignore = false;
+    let gitignore_path = dir.join(".gitignore");
+    if !gitignore_path.exists() {
+        let gitignore_content = r#"# Rit repository
+.rit/
+
+# OS generated files
+.DS_Store
+.DS_Store?
+._*
+.Spotlight-V100
+.Trashes
+ehthumbs.db
+Thumbs.db
+
+# IDE files
+.vscode/
+.idea/
+*.swp
+*.swo
+*~
+"#;
+        fs::write(gitignore_path, gitignore_content)?;
+        created_gitignore = true;
+    }
+
+    Ok(InitOutcome::Created { rit_dir, created_gitignore })
+}
@@ -0,0 +1,36 @@
+//! rit as a library: the storage layer shared by both kinds of repository
+//! and the operations behind every `rit` command, returning structured
+//! data instead of printing it.
+//!
+//! ```no_run
+//! let repo = rit::repository::discover()?.expect("not in a repository");
+//! let status = rit::status::status(repo.as_ref())?;
+//! for entry in &status.entries {
+//!     let (x, y) = entry.xy();
+//!     println!("{}{} {}", x, y, entry.path);
+//! }
+//! # anyhow::Ok(())
+//! ```
+
+pub mod attributes;
+pub mod binary;
+pub mod branch;
+pub mod checkout;
+pub mod commit;
+pub mod config;
+pub mod diff;
+pub mod gitignore;
+pub mod identity;
+pub mod index;
+pub mod init;
+pub mod location;
+pub mod log;
+pub mod objects;
+pub mod porcelain;
+pub mod refs;
+pub mod repository;
+pub mod stage;
+pub mod status;
+#[cfg(test)]
+mod test_repo;
+pub mod tree;
@@ -0,0 +1,149 @@
+use anyhow::{Result, bail};
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Where the repository a command runs against lives, and where the
+/// command was run from.
+#[derive(Debug, Clone)]
+pub struct Location {
+    /// Top of the working tree; index paths are relative to it.
+    pub work_tree: PathBuf,
+    /// The `.rit` directory of a native repository, or `None` when the
+    /// working tree belongs to a git repository.
+    pub rit_dir: Option<PathBuf>,
+    /// The caller's directory, which command-line paths are relative to.
+    pub cwd: PathBuf,
+}
+
+impl Location {
+    /// Find the repository containing the current directory.
+    ///
+    /// `RIT_DIR` names the `.rit` directory outright, and `RIT_WORK_TREE`
+    /// the working tree that goes with it (by default the parent of
+    /// `RIT_DIR`). Otherwise each directory from here up to the filesystem
+    /// root is checked for `.rit`, then `.git`.
+    pub fn discover() -> Result<Option<Location>> {
+        let cwd = canonical(&env::current_dir()?);
+        let work_tree_override = env::var_os("RIT_WORK_TREE")
+            .filter(|value| !value.is_empty())
+            .map(|value| canonical(&cwd.join(value)));
+
+        if let Some(rit_dir) = env::var_os("RIT_DIR").filter(|value| !value.is_empty()) {
+            let rit_dir = canonical(&cwd.join(rit_dir));
+            if !rit_dir.is_dir() {
+                bail!("RIT_DIR '{}' is not a rit repository", rit_dir.display());
+            }
+            let work_tree = match work_tree_override {
+                Some(work_tree) => work_tree,
+                None => rit_dir.parent().unwrap_or(&rit_dir).to_path_buf(),
+            };
+            return Ok(Some(Location { work_tree, rit_dir: Some(rit_dir), cwd }));
+        }
+
+        for dir in cwd.ancestors() {
+            let rit_dir = dir.join(".rit");
+            if rit_dir.is_dir() {
+                return Ok(Some(Location {
+                    work_tree: work_tree_override.unwrap_or_else(|| dir.to_path_buf()),
+                    rit_dir: Some(rit_dir),
+                    cwd,
+                }));
+            }
+            if dir.join(".git").exists() {
+                return Ok(Some(Location {
+                    work_tree: work_tree_override.unwrap_or_else(|| dir.to_path_buf()),
+                    rit_dir: None,
+                    cwd,
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The directory holding the repository's own files, such as
+    /// `info/exclude`: the `.rit` directory, or else the working tree's
+    /// `.git`, following a `gitdir:` file the way linked worktrees and
+    /// submodules use one.
+    pub fn metadata_dir(&self) -> PathBuf {
+        if let Some(rit_dir) = &self.rit_dir {
+            return rit_dir.clone();
+        }
+        let git = self.work_tree.join(".git");
+        match fs::read_to_string(&git) {
+            Ok(text) => match text.trim_end().strip_prefix("gitdir: ") {
+                Some(dir) => self.work_tree.join(dir),
+                None => git,
+            },
+            Err(_) => git,
+        }
+    }
+
+    /// Open the git repository of a working tree that has no `.rit`.
+    pub fn open_git(&self) -> Result<git2::Repository> {
+        let repo = git2::Repository::discover(&self.work_tree)?;
+        if repo.workdir().map(canonical).as_deref() != Some(self.work_tree.as_path()) {
+            repo.set_workdir(&self.work_tree, false)?;
+        }
+        Ok(repo)
+    }
+
+    /// Turn a path given on the command line, relative to the caller's
+    /// directory, into a `/`-separated path relative to the working tree.
+    /// The top of the working tree itself is the empty string.
+    pub fn repo_path(&self, path: &str) -> Result<String> {
+        let full_path = lexical_normalize(&self.cwd.join(path));
+        match full_path.strip_prefix(&self.work_tree) {
+            Ok(relative) => Ok(join_components(relative)),
+            Err(_) => bail!("'{}' is outside the repository at '{}'", path, self.work_tree.display()),
+        }
+    }
+
+    /// Print a path relative to the working tree the way the caller would
+    /// type it from their directory, e.g. `../README.md` from inside `src/`.
+    pub fn display_path(&self, repo_path: &str) -> String {
+        let target = self.work_tree.join(repo_path);
+        let from: Vec<_> = self.cwd.components().collect();
+        let to: Vec<_> = target.components().collect();
+        let shared = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+        let mut parts: Vec<String> = vec!["..".to_string(); from.len() - shared];
+        parts.extend(to[shared..].iter().map(|part| part.as_os_str().to_string_lossy().into_owned()));
+        if parts.is_empty() {
+            ".".to_string()
+        } else {
+            parts.join("/")
+        }
+    }
+}
+
+/// Resolve symlinks where the path exists so prefixes compare reliably.
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| lexical_normalize(path))
+}
+
+/// Resolve `.` and `..` without touching the filesystem, so paths of
+/// deleted files still work.
+fn lexical_normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+fn join_components(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
@@ -0,0 +1,483 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+use crate::refs::Head;
+use crate::repository::{RefKind, Repository};
+
+/// Filters for [`log`].
+#[derive(Debug, Clone, Default)]
+pub struct LogOptions {
+    pub max_count: Option<usize>,
+    /// Dates in any form accepted by [`parse_date`].
+    pub since: Option<String>,
+    pub until: Option<String>,
+    /// Substring matched against `Name <email>`.
+    pub author: Option<String>,
+    /// Start from every branch as well as HEAD.
+    pub all: bool,
+    /// Paths relative to the top of the working tree; only commits that
+    /// change one of them are kept.
+    pub paths: Vec<String>,
+}
+
+/// A commit in the history, independent of where it is stored.
+#[derive(Debug, Clone)]
+pub struct LogCommit {
+    pub id: String,
+    pub parents: Vec<String>,
+    pub tree: String,
+    pub author_name: String,
+    pub author_email: String,
+    pub author_time: DateTime<FixedOffset>,
+    pub committer_name: String,
+    pub committer_email: String,
+    pub commit_time: DateTime<FixedOffset>,
+    pub message: String,
+}
+
+impl LogCommit {
+    pub fn read(repo: &dyn Repository, id: &str) -> Result<Self> {
+        let commit = repo.read_commit(id)?;
+        Ok(LogCommit {
+            id: id.to_string(),
+            parents: commit.parents,
+            tree: commit.tree,
+            author_name: commit.author.name,
+            author_email: commit.author.email,
+            author_time: commit.author.time,
+            committer_name: commit.committer.name,
+            committer_email: commit.committer.email,
+            commit_time: commit.committer.time,
+            message: commit.message,
+        })
+    }
+
+    /// The first line of the message.
+    pub fn summary(&self) -> &str {
+        self.message.lines().next().unwrap_or("")
+    }
+}
+
+/// Ref names to show next to commits, keyed by commit id.
+pub fn labels(repo: &dyn Repository) -> Result<HashMap<String, Vec<String>>> {
+    let head = repo.head()?;
+    let mut labels: HashMap<String, Vec<String>> = HashMap::new();
+
+    if let Head::Detached(id) = &head {
+        labels.entry(id.clone()).or_default().push("HEAD".to_string());
+    }
+    for reference in repo.references()? {
+        let label = match reference.kind {
+            RefKind::Tag => format!("tag: {}", reference.name),
+            RefKind::Branch if head.branch_name() == Some(reference.name.as_str()) => {
+                format!("HEAD -> {}", reference.name)
+            }
+            RefKind::Branch | RefKind::RemoteBranch => reference.name,
+        };
+        labels.entry(reference.target).or_default().push(label);
+    }
+
+    Ok(labels)
+}
+
+/// Walk the history from HEAD (or every branch with `all`), children before
+/// their parents and otherwise newest commits first. Every commit reachable
+/// from the tips is read before this returns, whatever `max_count` is.
+/// Returns `None` before the first commit.
+pub fn log<'a>(repo: &'a dyn Repository, options: &LogOptions) -> Result<Option<Log<'a>>> {
+    let Some(head_id) = repo.head_commit()? else {
+        return Ok(None);
+    };
+
+    let mut tips = vec![head_id];
+    if options.all {
+        tips.extend(repo.references()?
+            .into_iter()
+            .filter(|reference| reference.kind == RefKind::Branch)
+            .map(|reference| reference.target));
+    }
+
+    let mut log = Log {
+        repo,
+        since: options.since.as_deref().map(parse_date).transpose()?,
+        until: options.until.as_deref().map(parse_date).transpose()?,
+        author: options.author.clone(),
+        max_count: options.max_count,
+        path_filter: PathFilter::new(repo, options.paths.iter()
+            .filter(|path| !path.is_empty())
+            .cloned()
+            .collect()),
+        pending: HashMap::new(),
+        ready: BinaryHeap::new(),
+        shown: 0,
+        hidden: Vec::new(),
+    };
+    log.load(tips)?;
+    Ok(Some(log))
+}
+
+/// The history reachable from the tips, yielding the commits that pass the
+/// filters of [`LogOptions`].
+///
+/// Commits come in topological order, as `git log --graph` shows them: a
+/// commit only after all of its children. Of the commits whose children
+/// have all been shown, the newest by committer date comes next, and of
+/// those made in the same second the one found first.
+///
+/// Knowing when every child has been shown means reading the whole history
+/// up front, as `git log --topo-order` does; `max_count` only limits how
+/// many commits are yielded, not how many are read.
+pub struct Log<'a> {
+    repo: &'a dyn Repository,
+    since: Option<DateTime<FixedOffset>>,
+    until: Option<DateTime<FixedOffset>>,
+    author: Option<String>,
+    max_count: Option<usize>,
+    path_filter: PathFilter<'a>,
+    /// Every reachable commit not yet visited.
+    pending: HashMap<String, Pending>,
+    /// Commits whose children have all been visited, by committer date and
+    /// then the order they were found in.
+    ready: BinaryHeap<(i64, Reverse<usize>, String)>,
+    shown: usize,
+    /// Commits skipped by the filters, with their parents.
+    hidden: Vec<(String, Vec<String>)>,
+}
+
+impl Log<'_> {
+    /// Collect the remaining commits, rewriting their parents to skip over
+    /// filtered-out commits so the graph stays connected.
+    pub fn graph(mut self) -> Result<Vec<LogCommit>> {
+        let mut shown = Vec::new();
+        for commit in self.by_ref() {
+            shown.push(commit?);
+        }
+
+        // Hidden commits were visited after their children, so resolving
+        // them oldest first means their own parents are already rewritten.
+        let shown_ids: HashSet<String> = shown.iter().map(|commit| commit.id.clone()).collect();
+        let mut rewritten: HashMap<String, Vec<String>> = HashMap::new();
+        for (id, parents) in self.hidden.into_iter().rev() {
+            let resolved = rewrite_parents(&parents, &shown_ids, &rewritten);
+            rewritten.insert(id, resolved);
+        }
+        for commit in &mut shown {
+            commit.parents = rewrite_parents(&commit.parents, &shown_ids, &rewritten);
+        }
+
+        Ok(shown)
+    }
+
+    /// Read every commit reachable from `tips` and count the children of
+    /// each, so that a commit can wait for all of them.
+    fn load(&mut self, tips: Vec<String>) -> Result<()> {
+        let mut stack: Vec<String> = tips.into_iter().rev().collect();
+        while let Some(id) = stack.pop() {
+            if self.pending.contains_key(&id) {
+                continue;
+            }
+            let commit = LogCommit::read(self.repo, &id)?;
+            // First parents are followed first, so they are found first
+            stack.extend(commit.parents.iter().rev().cloned());
+            let sequence = self.pending.len();
+            self.pending.insert(id, Pending { commit, children: 0, sequence });
+        }
+
+        let parents: Vec<String> = self.pending.values()
+            .flat_map(|pending| unique(&pending.commit.parents))
+            .collect();
+        for parent in parents {
+            if let Some(pending) = self.pending.get_mut(&parent) {
+                pending.children += 1;
+            }
+        }
+        for (id, pending) in &self.pending {
+            if pending.children == 0 {
+                self.ready.push(pending.key(id));
+            }
+        }
+        Ok(())
+    }
+
+    fn matches(&mut self, commit: &LogCommit) -> Result<bool> {
+        Ok(self.since.is_none_or(|since| commit.commit_time >= since)
+            && self.until.is_none_or(|until| commit.commit_time <= until)
+            && self.author.as_ref().is_none_or(|author| {
+                format!("{} <{}>", commit.author_name, commit.author_email).contains(author.as_str())
+            })
+            && self.path_filter.touches(commit)?)
+    }
+
+    fn next_commit(&mut self) -> Result<Option<LogCommit>> {
+        while let Some((_, _, id)) = self.ready.pop() {
+            let commit = self.pending.remove(&id).expect("ready commits are pending").commit;
+            for parent in unique(&commit.parents) {
+                if let Some(pending) = self.pending.get_mut(&parent) {
+                    pending.children -= 1;
+                    if pending.children == 0 {
+                        self.ready.push(pending.key(&parent));
+                    }
+                }
+            }
+
+            if self.matches(&commit)? {
+                self.shown += 1;
+                return Ok(Some(commit));
+            }
+            self.hidden.push((id, commit.parents));
+        }
+        Ok(None)
+    }
+}
+
+impl Iterator for Log<'_> {
+    type Item = Result<LogCommit>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.max_count.is_some_and(|max| self.shown >= max) {
+            return None;
+        }
+        self.next_commit().transpose()
+    }
+}
+
+/// A commit waiting for its children to be visited.
+struct Pending {
+    commit: LogCommit,
+    /// Children not yet visited.
+    children: usize,
+    /// Order of discovery, which breaks ties between commits made in the
+    /// same second.
+    sequence: usize,
+}
+
+impl Pending {
+    fn key(&self, id: &str) -> (i64, Reverse<usize>, String) {
+        (self.commit.commit_time.timestamp(), Reverse(self.sequence), id.to_string())
+    }
+}
+
+/// Parents without repeats, which a hand-made commit could have.
+fn unique(parents: &[String]) -> Vec<String> {
+    let mut unique: Vec<String> = Vec::new();
+    for parent in parents {
+        if !unique.contains(parent) {
+            unique.push(parent.clone());
+        }
+    }
+    unique
+}
+
+fn rewrite_parents(
+    parents: &[String],
+    shown: &HashSet<String>,
+    rewritten: &HashMap<String, Vec<String>>,
+) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    for parent in parents {
+        let resolved = match rewritten.get(parent) {
+            Some(ancestors) if !shown.contains(parent) => ancestors.clone(),
+            _ => vec![parent.clone()],
+        };
+        for id in resolved {
+            if !result.contains(&id) {
+                result.push(id);
+            }
+        }
+    }
+    result
+}
+
+/// Decides whether a commit changed any of the requested paths compared to
+/// its parents. File lists are cached because neighbouring commits are
+/// compared against each other.
+struct PathFilter<'a> {
+    repo: &'a dyn Repository,
+    paths: Vec<String>,
+    cache: HashMap<String, BTreeMap<String, String>>,
+}
+
+impl<'a> PathFilter<'a> {
+    fn new(repo: &'a dyn Repository, paths: Vec<String>) -> Self {
+        PathFilter {
+            repo,
+            paths,
+            cache: HashMap::new(),
+        }
+    }
+
+    fn touches(&mut self, commit: &LogCommit) -> Result<bool> {
+        if self.paths.is_empty() {
+            return Ok(true);
+        }
+
+        let files = self.matching_files(&commit.tree)?;
+        if commit.parents.is_empty() {
+            return Ok(!files.is_empty());
+        }
+
+        // A merge is only interesting if it differs from every parent
+        for parent in &commit.parents {
+            let parent_tree = self.repo.read_commit(parent)?.tree;
+            if self.matching_files(&parent_tree)? == files {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn matching_files(&mut self, tree: &str) -> Result<BTreeMap<String, String>> {
+        if let Some(files) = self.cache.get(tree) {
+            return Ok(files.clone());
+        }
+
+        let files: BTreeMap<String, String> = self.repo.tree_files(tree)?
+            .into_iter()
+            .filter(|(path, _)| self.paths.iter().any(|filter| {
+                path == filter || path.starts_with(&format!("{}/", filter))
+            }))
+            .map(|(path, (_, id))| (path, id))
+            .collect();
+        self.cache.insert(tree.to_string(), files.clone());
+        Ok(files)
+    }
+}
+
+/// Parse the dates accepted by `--since`/`--until`: RFC 3339, `YYYY-MM-DD`,
+/// `YYYY-MM-DD HH:MM[:SS]`, `yesterday` and relative forms such as
+/// `2 weeks ago`.
+pub fn parse_date(text: &str) -> Result<DateTime<FixedOffset>> {
+    let text = text.trim();
+    let now = Local::now().fixed_offset();
+
+    if let Ok(date) = DateTime::parse_from_rfc3339(text) {
+        return Ok(date);
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(date) = NaiveDateTime::parse_from_str(text, format) {
+            return local_date(date);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return local_date(date.and_hms_opt(0, 0, 0).expect("midnight is valid"));
+    }
+    match text {
+        "now" => return Ok(now),
+        "yesterday" => return Ok(now - Duration::days(1)),
+        _ => {}
+    }
+
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if let [count, unit, "ago"] = words[..]
+        && let Ok(count) = count.parse::<i64>()
+    {
+        let unit_duration = match unit.trim_end_matches('s') {
+            "second" => Some(Duration::seconds(1)),
+            "minute" => Some(Duration::minutes(1)),
+            "hour" => Some(Duration::hours(1)),
+            "day" => Some(Duration::days(1)),
+            "week" => Some(Duration::weeks(1)),
+            "month" => Some(Duration::days(30)),
+            "year" => Some(Duration::days(365)),
+            _ => None,
+        };
+        if let Some(unit_duration) = unit_duration {
+            return i32::try_from(count)
+                .ok()
+                .and_then(|count| unit_duration.checked_mul(count))
+                .and_then(|duration| now.checked_sub_signed(duration))
+                .ok_or_else(|| anyhow!("date '{}' is out of range", text));
+        }
+    }
+
+    Err(anyhow!("invalid date '{}'", text))
+}
+
+fn local_date(date: NaiveDateTime) -> Result<DateTime<FixedOffset>> {
+    Local.from_local_datetime(&date)
+        .earliest()
+        .map(|date| date.fixed_offset())
+        .ok_or_else(|| anyhow!("invalid local time '{}'", date))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_repo::TestRepo;
+    use crate::{branch, checkout};
+
+    fn summaries(repo: &TestRepo, options: &LogOptions) -> Vec<String> {
+        log(repo.repo.as_ref(), options).unwrap().unwrap()
+            .map(|commit| commit.unwrap().summary().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn relative_dates_count_back_from_now() {
+        let before = Local::now().fixed_offset();
+        let date = parse_date("2 weeks ago").unwrap();
+        let after = Local::now().fixed_offset();
+        assert!(date >= before - Duration::weeks(2) && date <= after - Duration::weeks(2));
+        assert!(parse_date("yesterday").unwrap() < before);
+    }
+
+    #[test]
+    fn absolute_dates() {
+        assert_eq!(parse_date("2024-03-01T12:00:00+02:00").unwrap().timestamp(), 1709287200);
+        assert!(parse_date("2024-03-01").is_ok());
+        assert!(parse_date("2024-03-01 08:30").is_ok());
+        assert!(parse_date("someday").is_err());
+    }
+
+    #[test]
+    fn huge_relative_dates_are_errors() {
+        for text in ["99999999999 weeks ago", "3000000000 seconds ago", "9999999 years ago", "99999999999999999999 days ago"] {
+            assert!(parse_date(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn children_come_before_parents_made_in_the_same_second() {
+        let repo = TestRepo::new();
+        repo.write("a", "1");
+        repo.add(&["a"]);
+        repo.commit("first", 1000);
+        repo.write("a", "2");
+        repo.add(&["a"]);
+        repo.commit("tip", 1000);
+        branch::create(repo.repo.as_ref(), "feat", None).unwrap();
+        checkout::switch(repo.repo.as_ref(), "feat", false).unwrap();
+        repo.write("a", "3");
+        repo.add(&["a"]);
+        repo.commit("feature", 1000);
+        checkout::switch(repo.repo.as_ref(), "main", false).unwrap();
+
+        let all = LogOptions { all: true, ..Default::default() };
+        assert_eq!(summaries(&repo, &all), ["feature", "tip", "first"]);
+        assert_eq!(summaries(&repo, &LogOptions::default()), ["tip", "first"]);
+    }
+
+    #[test]
+    fn dates_only_break_ties_between_branches() {
+        let repo = TestRepo::new();
+        repo.write("a", "1");
+        repo.add(&["a"]);
+        repo.commit("root", 1000);
+        branch::create(repo.repo.as_ref(), "old", None).unwrap();
+        repo.write("a", "2");
+        repo.add(&["a"]);
+        repo.commit("main", 3000);
+        checkout::switch(repo.repo.as_ref(), "old", false).unwrap();
+        repo.write("b", "1");
+        repo.add(&["b"]);
+        // Dated before its parent, as with a skewed clock
+        repo.commit("skewed", 500);
+        repo.write("b", "2");
+        repo.add(&["b"]);
+        repo.commit("old", 2000);
+
+        let all = LogOptions { all: true, ..Default::default() };
+        assert_eq!(summaries(&repo, &all), ["main", "old", "skewed", "root"]);
+    }
+}
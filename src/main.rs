@@ -1,14 +1,25 @@
 use clap::{Parser, Subcommand};
-use anyhow::Result;
-use colored::*;
+use anyhow::{Context, Result, bail};
 
 mod commands;
 
+use commands::output::{ColorChoice, Format};
+
 #[derive(Parser)]
 #[command(name = "rit")]
 #[command(about = "A graphical git-like version control system")]
 #[command(version = "0.1.0")]
 struct Cli {
+    /// Run as if rit was started in this directory
+    #[arg(short = 'C', global = true, value_name = "PATH")]
+    directory: Option<std::path::PathBuf>,
+    /// When to color output (default: auto, only on a terminal)
+    #[arg(long, global = true, value_enum, value_name = "WHEN", num_args = 0..=1,
+        require_equals = true, default_missing_value = "always")]
+    color: Option<ColorChoice>,
+    /// Output format; `json` is supported by status, add, commit, log and branch
+    #[arg(long, global = true, value_enum, default_value = "text")]
+    format: Format,
     #[command(subcommand)]
     command: Commands,
 }
@@ -16,38 +27,390 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Initialize a new rit repository
-    Init,
+    Init {
+        /// Hash function used to name objects
+        #[arg(long, value_name = "FORMAT", default_value = "sha1", value_parser = ["sha1", "sha256"])]
+        object_format: String,
+    },
     /// Show the working tree status in a graphical format
-    Status,
+    Status {
+        /// Directory levels to expand before summarizing ("src/ (+142 files)")
+        #[arg(long, value_name = "LEVELS", conflicts_with = "all")]
+        depth: Option<usize>,
+        /// Lines to show per section before summarizing the rest
+        #[arg(long, value_name = "COUNT", conflicts_with = "all")]
+        limit: Option<usize>,
+        /// Show every file without collapsing directories
+        #[arg(long)]
+        all: bool,
+        /// Print git's machine-readable format instead of trees
+        #[arg(long, value_name = "VERSION", num_args = 0..=1, require_equals = true,
+            default_missing_value = "v1", value_parser = ["v1", "v2"])]
+        porcelain: Option<String>,
+        /// End porcelain lines with NUL and don't quote paths (implies --porcelain)
+        #[arg(short = 'z')]
+        null: bool,
+        /// Show the branch headers in porcelain output
+        #[arg(short = 'b', long)]
+        branch: bool,
+        /// Find staged renames at least this similar (default 50%)
+        #[arg(long = "find-renames", value_name = "N", num_args = 0..=1, require_equals = true,
+            default_missing_value = "", conflicts_with = "no_renames")]
+        find_renames: Option<String>,
+        /// Show renames as a deletion and an addition
+        #[arg(long)]
+        no_renames: bool,
+    },
     /// Add file contents to the index
     Add { files: Vec<String> },
     /// Record changes to the repository
     Commit { 
         #[arg(short, long)]
-        message: String 
+        message: String,
+        /// Override the commit author ("Name <email>")
+        #[arg(long, value_name = "AUTHOR")]
+        author: Option<String>,
+        /// Override the author date ("2024-01-15 10:30", "@1700000000 +0100", RFC 2822)
+        #[arg(long, value_name = "DATE")]
+        date: Option<String>,
+    },
+    /// Show changes between the working tree, the index and commits
+    Diff {
+        /// Compare the index with HEAD (or the given commit)
+        #[arg(long, visible_alias = "cached")]
+        staged: bool,
+        /// Lines of context around each change
+        #[arg(short = 'U', long = "unified", value_name = "LINES")]
+        context: Option<usize>,
+        /// Line diff algorithm
+        #[arg(long, value_name = "ALGORITHM", value_parser = ["myers", "histogram", "patience"],
+            conflicts_with_all = ["histogram", "patience"])]
+        diff_algorithm: Option<String>,
+        /// Shorthand for --diff-algorithm=histogram
+        #[arg(long, conflicts_with = "patience")]
+        histogram: bool,
+        /// Shorthand for --diff-algorithm=patience
+        #[arg(long)]
+        patience: bool,
+        /// Show old and new side by side, sized to the terminal
+        #[arg(long, short = 'y', conflicts_with = "stat")]
+        side_by_side: bool,
+        /// Show a tree of the changed files with a bar of changed lines each
+        #[arg(long, conflicts_with = "word_diff")]
+        stat: bool,
+        /// Mark changed words instead of whole lines
+        #[arg(long, value_name = "MODE", num_args = 0..=1, require_equals = true, default_missing_value = "plain",
+            value_parser = ["color", "plain", "porcelain"], conflicts_with = "side_by_side")]
+        word_diff: Option<String>,
+        /// What counts as a word for --word-diff (implies --word-diff)
+        #[arg(long, value_name = "REGEX", conflicts_with_all = ["side_by_side", "stat"])]
+        word_diff_regex: Option<String>,
+        /// Pair deleted and added files up as renames when at least this similar (default 50%)
+        #[arg(short = 'M', long = "find-renames", value_name = "N", num_args = 0..=1, require_equals = true,
+            default_missing_value = "", conflicts_with = "no_renames")]
+        find_renames: Option<String>,
+        /// Also find copies of modified files, at least this similar
+        #[arg(long = "find-copies", value_name = "N", num_args = 0..=1, require_equals = true,
+            default_missing_value = "", conflicts_with = "no_renames")]
+        find_copies: Option<String>,
+        /// Show renames as a deletion and an addition
+        #[arg(long)]
+        no_renames: bool,
+        /// Commits to compare (one, two, or `a..b`), then paths to limit the diff to
+        args: Vec<String>,
+        /// Paths to limit the diff to
+        #[arg(last = true)]
+        paths: Vec<String>,
     },
     /// Show commit logs
-    Log,
+    Log {
+        /// Limit the number of commits to show
+        #[arg(short = 'n', long = "max-count", value_name = "NUMBER")]
+        max_count: Option<usize>,
+        /// Show commits more recent than a date ("2024-01-15", "2 weeks ago")
+        #[arg(long, value_name = "DATE")]
+        since: Option<String>,
+        /// Show commits older than a date
+        #[arg(long, value_name = "DATE")]
+        until: Option<String>,
+        /// Only show commits whose author matches this text
+        #[arg(long, value_name = "PATTERN")]
+        author: Option<String>,
+        /// Show the history of every branch, not just HEAD
+        #[arg(long)]
+        all: bool,
+        /// Only show commits that touch these paths
+        paths: Vec<String>,
+    },
+    /// List, create, delete or rename branches
+    Branch {
+        /// Branch to create, delete, rename or configure
+        name: Option<String>,
+        /// Commit the new branch starts at (defaults to HEAD), or the new
+        /// name when renaming
+        start_point: Option<String>,
+        /// Delete a branch that is fully merged into HEAD
+        #[arg(short = 'd', long, conflicts_with_all = ["force_delete", "rename"])]
+        delete: bool,
+        /// Delete a branch even if it is not merged
+        #[arg(short = 'D', conflicts_with = "rename")]
+        force_delete: bool,
+        /// Rename a branch (`-m new` renames the current branch)
+        #[arg(short = 'm', long = "move")]
+        rename: bool,
+        /// Track another branch as this branch's upstream
+        #[arg(short = 'u', long, value_name = "UPSTREAM", conflicts_with_all = ["delete", "force_delete", "rename"])]
+        set_upstream_to: Option<String>,
+        /// Stop tracking an upstream branch
+        #[arg(long, conflicts_with_all = ["delete", "force_delete", "rename", "set_upstream_to"])]
+        unset_upstream: bool,
+    },
+    /// Show which ignore rule, if any, applies to each path
+    CheckIgnore {
+        /// Paths to check
+        #[arg(required = true)]
+        paths: Vec<String>,
+        /// Show the file, line number and pattern that decided each path
+        #[arg(short, long)]
+        verbose: bool,
+        /// Also list paths that aren't ignored
+        #[arg(short, long)]
+        non_matching: bool,
+    },
+    /// Switch branches, updating the working tree and index
+    Switch {
+        /// Branch to switch to
+        branch: String,
+        /// Switch to a commit instead, leaving HEAD detached
+        #[arg(short = 'd', long)]
+        detach: bool,
+    },
+    /// Switch to a commit, or restore files from it with `-- <paths>`
+    Checkout {
+        /// Branch or commit to check out
+        target: String,
+        /// Only restore these files, leaving HEAD where it is
+        #[arg(last = true)]
+        paths: Vec<String>,
+        /// Overwrite local changes to the restored files
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Get and set options in the repository, global or system config
+    Config {
+        /// Use the repository's config file (.rit/config)
+        #[arg(long, global = true, conflicts_with_all = ["global", "system"])]
+        local: bool,
+        /// Use the current user's config file (~/.ritconfig)
+        #[arg(long, global = true, conflicts_with = "system")]
+        global: bool,
+        /// Use the system-wide config file (/etc/ritconfig)
+        #[arg(long, global = true)]
+        system: bool,
+        /// Check and show values as this type
+        #[arg(long = "type", global = true, value_name = "TYPE", value_parser = ["bool", "int", "path"])]
+        value_type: Option<String>,
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the value of a key
+    Get {
+        key: String,
+        /// Print every value of a key that is set more than once
+        #[arg(long)]
+        all: bool,
+    },
+    /// Set a key
+    Set { key: String, value: String },
+    /// Remove a key
+    Unset { key: String },
+    /// List every key and value
+    List,
+}
+
+/// Git takes the similarity for `-M` written straight after it (`-M50%`),
+/// which clap would read as a cluster of short flags; spell those out as
+/// `--find-renames=50%`. Arguments after `--` are left alone.
+fn expand_similarity_args(args: impl Iterator<Item = std::ffi::OsString>) -> Vec<std::ffi::OsString> {
+    let mut after_dashes = false;
+    args.map(|arg| {
+        if arg == "--" {
+            after_dashes = true;
+        }
+        match arg.to_str().and_then(|text| text.strip_prefix("-M")) {
+            Some(threshold) if !after_dashes && !threshold.is_empty() && !threshold.starts_with('=') => {
+                format!("--find-renames={}", threshold).into()
+            }
+            _ => arg,
+        }
+    })
+    .collect()
+}
+
+/// Rename detection as asked for on the command line: `Some(None)` for
+/// `--no-renames`, `None` to leave it to the config.
+fn rename_options(
+    no_renames: bool,
+    find_renames: Option<String>,
+    find_copies: Option<String>,
+) -> Result<Option<Option<rit::diff::RenameOptions>>> {
+    let options = |threshold: &str, copies: bool| -> Result<_> {
+        Ok(Some(Some(rit::diff::RenameOptions { threshold: rit::diff::parse_threshold(threshold)?, copies })))
+    };
+    match (no_renames, find_copies, find_renames) {
+        (true, _, _) => Ok(Some(None)),
+        (_, Some(threshold), _) => options(&threshold, true),
+        (_, None, Some(threshold)) => options(&threshold, false),
+        (false, None, None) => Ok(None),
+    }
 }
 
 fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let cli = Cli::parse_from(expand_similarity_args(std::env::args_os()));
+    
+    if let Some(directory) = &cli.directory {
+        std::env::set_current_dir(directory)
+            .with_context(|| format!("cannot change to '{}'", directory.display()))?;
+    }
+    
+    commands::output::configure(cli.color)?;
+    
+    let format = cli.format;
+    if format.is_json() && !matches!(cli.command,
+        Commands::Status { .. } | Commands::Add { .. } | Commands::Commit { .. } | Commands::Log { .. } | Commands::Branch { .. })
+    {
+        bail!("--format json is only supported by status, add, commit, log and branch");
+    }
     
     match cli.command {
-        Commands::Init => {
-            commands::init::run()?;
+        Commands::Init { object_format } => {
+            commands::init::run(rit::objects::ObjectFormat::parse(&object_format)?)?;
+        }
+        Commands::Status { porcelain, null, branch, find_renames, no_renames, .. } if porcelain.is_some() || null => {
+            if format.is_json() {
+                bail!("--porcelain can't be combined with --format json");
+            }
+            commands::status::porcelain(rit::porcelain::Options {
+                version: match porcelain.as_deref() {
+                    Some("v2") => rit::porcelain::Version::V2,
+                    _ => rit::porcelain::Version::V1,
+                },
+                nul: null,
+                branch,
+            }, rename_options(no_renames, find_renames, None)?)?;
         }
-        Commands::Status => {
-            commands::status::run()?;
+        Commands::Status { depth, limit, all, find_renames, no_renames, .. } => {
+            let limits = if all {
+                rit::tree::TreeLimits::unlimited()
+            } else {
+                rit::tree::TreeLimits {
+                    depth: depth.or(Some(rit::tree::TreeLimits::DEFAULT_DEPTH)),
+                    limit: limit.or(Some(rit::tree::TreeLimits::DEFAULT_LIMIT)),
+                }
+            };
+            commands::status::run(limits, rename_options(no_renames, find_renames, None)?, format)?;
         }
         Commands::Add { files } => {
-            commands::add::run(files)?;
+            commands::add::run(files, format)?;
+        }
+        Commands::Commit { message, author, date } => {
+            commands::commit::run(message, author, date, format)?;
+        }
+        Commands::Diff { staged, context, diff_algorithm, histogram, patience, side_by_side, stat, word_diff, word_diff_regex, find_renames, find_copies, no_renames, args, paths } => {
+            let algorithm = if histogram {
+                Some(rit::diff::Algorithm::Histogram)
+            } else if patience {
+                Some(rit::diff::Algorithm::Patience)
+            } else {
+                diff_algorithm.as_deref().map(rit::diff::Algorithm::parse).transpose()?
+            };
+            let view = if side_by_side {
+                commands::diff::View::SideBySide
+            } else if stat {
+                commands::diff::View::Stat
+            } else if word_diff.is_some() || word_diff_regex.is_some() {
+                commands::diff::View::Words(match word_diff.as_deref() {
+                    Some("color") => commands::diff::WordStyle::Color,
+                    Some("porcelain") => commands::diff::WordStyle::Porcelain,
+                    _ => commands::diff::WordStyle::Plain,
+                })
+            } else {
+                commands::diff::View::Unified
+            };
+            let renames = rename_options(no_renames, find_renames, find_copies)?;
+            commands::diff::run(commands::diff::DiffArgs {
+                staged,
+                args,
+                paths,
+                context,
+                algorithm,
+                view,
+                word_regex: word_diff_regex,
+                renames,
+            })?;
+        }
+        Commands::Log { max_count, since, until, author, all, paths } => {
+            commands::log::run(rit::log::LogOptions {
+                max_count,
+                since,
+                until,
+                author,
+                all,
+                paths,
+            }, format)?;
+        }
+        Commands::Branch { name, start_point, delete, force_delete, rename, set_upstream_to, unset_upstream } => {
+            use commands::branch::BranchAction;
+            let action = if delete || force_delete {
+                let Some(name) = name else {
+                    bail!("branch name required");
+                };
+                BranchAction::Delete { name, force: force_delete }
+            } else if rename {
+                match (name, start_point) {
+                    (Some(old), Some(new)) => BranchAction::Rename { old: Some(old), new },
+                    (Some(new), None) => BranchAction::Rename { old: None, new },
+                    _ => bail!("new branch name required"),
+                }
+            } else if set_upstream_to.is_some() || unset_upstream {
+                BranchAction::SetUpstream { branch: name, upstream: set_upstream_to }
+            } else {
+                match name {
+                    Some(name) => BranchAction::Create { name, start_point },
+                    None => BranchAction::List,
+                }
+            };
+            commands::branch::run(action, format)?;
+        }
+        Commands::CheckIgnore { paths, verbose, non_matching } => {
+            commands::check_ignore::run(paths, verbose, non_matching)?;
+        }
+        Commands::Switch { branch, detach } => {
+            commands::checkout::switch(branch, detach)?;
         }
-        Commands::Commit { message } => {
-            commands::commit::run(message)?;
+        Commands::Checkout { target, paths, force } => {
+            commands::checkout::checkout(target, paths, force)?;
         }
-        Commands::Log => {
-            commands::log::run()?;
+        Commands::Config { local, global, system, value_type, action } => {
+            use commands::config::ConfigAction;
+            use rit::config::{Level, ValueType};
+            let level = match (local, global, system) {
+                (true, _, _) => Some(Level::Local),
+                (_, true, _) => Some(Level::Global),
+                (_, _, true) => Some(Level::System),
+                _ => None,
+            };
+            let action = match action {
+                ConfigCommand::Get { key, all } => ConfigAction::Get { key, all },
+                ConfigCommand::Set { key, value } => ConfigAction::Set { key, value },
+                ConfigCommand::Unset { key } => ConfigAction::Unset { key },
+                ConfigCommand::List => ConfigAction::List,
+            };
+            commands::config::run(action, level, value_type.as_deref().map(ValueType::parse).transpose()?)?;
         }
     }
     
@@ -0,0 +1,446 @@
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, FixedOffset, TimeZone};
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use crate::config::Config;
+
+pub const MODE_TREE: u32 = 0o40000;
+
+/// The hash function used to name objects, chosen once at `rit init` and
+/// recorded as `extensions.objectformat` in `.rit/config`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ObjectFormat {
+    /// Git-compatible 40 hex character ids.
+    #[default]
+    Sha1,
+    /// 64 hex character ids, as in git's SHA-256 repositories.
+    Sha256,
+}
+
+impl ObjectFormat {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "sha1" => Ok(ObjectFormat::Sha1),
+            "sha256" => Ok(ObjectFormat::Sha256),
+            other => bail!("unknown object format '{}'", other),
+        }
+    }
+
+    /// Read the object format a repository was initialized with.
+    pub fn load(rit_dir: &Path) -> Result<Self> {
+        let config = Config::load(&rit_dir.join("config"))?;
+        match config.get("extensions.objectformat") {
+            Some(name) => Self::parse(name),
+            None => Ok(ObjectFormat::Sha1),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ObjectFormat::Sha1 => "sha1",
+            ObjectFormat::Sha256 => "sha256",
+        }
+    }
+
+    /// Length of an id in bytes, as stored in trees and the index.
+    pub fn raw_len(&self) -> usize {
+        match self {
+            ObjectFormat::Sha1 => 20,
+            ObjectFormat::Sha256 => 32,
+        }
+    }
+
+    /// Hash the concatenation of `chunks`.
+    pub fn digest(&self, chunks: &[&[u8]]) -> Vec<u8> {
+        match self {
+            ObjectFormat::Sha1 => {
+                let mut hasher = Sha1::new();
+                chunks.iter().for_each(|chunk| hasher.update(chunk));
+                hasher.finalize().to_vec()
+            }
+            ObjectFormat::Sha256 => {
+                let mut hasher = Sha256::new();
+                chunks.iter().for_each(|chunk| hasher.update(chunk));
+                hasher.finalize().to_vec()
+            }
+        }
+    }
+}
+
+/// The kinds of objects stored in `.rit/objects`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectKind {
+    Blob,
+    Tree,
+    Commit,
+}
+
+impl ObjectKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ObjectKind::Blob => "blob",
+            ObjectKind::Tree => "tree",
+            ObjectKind::Commit => "commit",
+        }
+    }
+
+    fn parse(name: &str) -> Result<Self> {
+        match name {
+            "blob" => Ok(ObjectKind::Blob),
+            "tree" => Ok(ObjectKind::Tree),
+            "commit" => Ok(ObjectKind::Commit),
+            other => bail!("unknown object type '{}'", other),
+        }
+    }
+}
+
+/// Loose object storage using git's layout: every object is zlib-compressed
+/// and stored under `objects/<first two hex chars>/<remaining hex chars>`.
+pub struct ObjectStore {
+    root: PathBuf,
+    format: ObjectFormat,
+}
+
+impl ObjectStore {
+    /// Open the object store of a native rit repository, using the object
+    /// format recorded in its config.
+    pub fn open(rit_dir: &Path) -> Result<Self> {
+        Ok(ObjectStore {
+            root: rit_dir.join("objects"),
+            format: ObjectFormat::load(rit_dir)?,
+        })
+    }
+
+    pub fn format(&self) -> ObjectFormat {
+        self.format
+    }
+
+    pub fn write_blob(&self, data: &[u8]) -> Result<String> {
+        self.write(ObjectKind::Blob, data)
+    }
+
+    /// Store an object and return its hex id. Writing an object that
+    /// already exists is a no-op.
+    pub fn write(&self, kind: ObjectKind, data: &[u8]) -> Result<String> {
+        let id = hash_object(self.format, kind, data);
+        let path = self.object_path(&id);
+        if path.exists() {
+            return Ok(id);
+        }
+
+        let dir = path.parent().expect("object path always has a fan-out directory");
+        fs::create_dir_all(dir)?;
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&object_header(kind, data.len()))?;
+        encoder.write_all(data)?;
+        let compressed = encoder.finish()?;
+
+        // Write to a temporary file first so a crash never leaves a
+        // truncated object behind under its final name.
+        let tmp_path = dir.join(format!("tmp_obj_{}", std::process::id()));
+        fs::write(&tmp_path, compressed)?;
+        fs::rename(&tmp_path, &path)?;
+
+        Ok(id)
+    }
+
+    pub fn read(&self, id: &str) -> Result<(ObjectKind, Vec<u8>)> {
+        let path = self.object_path(id);
+        let compressed = fs::read(&path).with_context(|| format!("object {} not found", id))?;
+
+        let mut raw = Vec::new();
+        ZlibDecoder::new(&compressed[..])
+            .read_to_end(&mut raw)
+            .with_context(|| format!("object {} is corrupt", id))?;
+
+        let nul = raw
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| anyhow!("object {} has no header", id))?;
+        let header = std::str::from_utf8(&raw[..nul])?;
+        let (kind, size) = header
+            .split_once(' ')
+            .ok_or_else(|| anyhow!("object {} has a malformed header", id))?;
+        let kind = ObjectKind::parse(kind)?;
+        let size: usize = size.parse()?;
+
+        let data = raw[nul + 1..].to_vec();
+        if data.len() != size {
+            bail!("object {} has wrong size ({} != {})", id, data.len(), size);
+        }
+
+        Ok((kind, data))
+    }
+
+    pub fn read_tree(&self, id: &str) -> Result<Tree> {
+        match self.read(id)? {
+            (ObjectKind::Tree, data) => Tree::parse(&data, self.format),
+            (kind, _) => bail!("object {} is a {}, not a tree", id, kind.as_str()),
+        }
+    }
+
+    pub fn read_commit(&self, id: &str) -> Result<Commit> {
+        match self.read(id)? {
+            (ObjectKind::Commit, data) => Commit::parse(&data),
+            (kind, _) => bail!("object {} is a {}, not a commit", id, kind.as_str()),
+        }
+    }
+
+    /// Walk a tree and all of its subtrees, returning every file keyed by
+    /// its `/`-separated path together with its mode and blob id.
+    pub fn flatten_tree(&self, id: &str) -> Result<BTreeMap<String, (u32, String)>> {
+        let mut files = BTreeMap::new();
+        self.flatten_tree_into(id, "", &mut files)?;
+        Ok(files)
+    }
+
+    fn flatten_tree_into(
+        &self,
+        id: &str,
+        prefix: &str,
+        files: &mut BTreeMap<String, (u32, String)>,
+    ) -> Result<()> {
+        for entry in self.read_tree(id)?.entries {
+            let path = format!("{}{}", prefix, entry.name);
+            if entry.mode == MODE_TREE {
+                self.flatten_tree_into(&entry.id, &format!("{}/", path), files)?;
+            } else {
+                files.insert(path, (entry.mode, entry.id));
+            }
+        }
+        Ok(())
+    }
+
+    /// Expand an abbreviated object id (at least 4 hex characters) to the
+    /// full id, failing if it matches nothing or more than one object.
+    pub fn resolve_prefix(&self, prefix: &str) -> Result<String> {
+        let prefix = prefix.to_lowercase();
+        if prefix.len() < 4 || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+            bail!("'{}' is not a valid object id", prefix);
+        }
+
+        let (fan_out, rest) = prefix.split_at(2);
+        let mut matches = Vec::new();
+        if let Ok(entries) = fs::read_dir(self.root.join(fan_out)) {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if name.starts_with(rest) && name.len() + 2 == self.format.raw_len() * 2 {
+                    matches.push(format!("{}{}", fan_out, name));
+                }
+            }
+        }
+
+        match matches.len() {
+            0 => bail!("no object matches '{}'", prefix),
+            1 => Ok(matches.remove(0)),
+            _ => bail!("object id '{}' is ambiguous", prefix),
+        }
+    }
+
+    /// Whether `ancestor` can be reached from `descendant` by following
+    /// parent links (a commit counts as its own ancestor).
+    pub fn is_ancestor(&self, ancestor: &str, descendant: &str) -> Result<bool> {
+        let mut pending = vec![descendant.to_string()];
+        let mut seen = std::collections::HashSet::new();
+        while let Some(id) = pending.pop() {
+            if id == ancestor {
+                return Ok(true);
+            }
+            if seen.insert(id.clone()) {
+                pending.extend(self.read_commit(&id)?.parents);
+            }
+        }
+        Ok(false)
+    }
+
+    fn object_path(&self, id: &str) -> PathBuf {
+        let (fan_out, rest) = id.split_at(2);
+        self.root.join(fan_out).join(rest)
+    }
+}
+
+/// Compute the id an object would be stored under without writing it.
+pub fn hash_object(format: ObjectFormat, kind: ObjectKind, data: &[u8]) -> String {
+    hex::encode(format.digest(&[&object_header(kind, data.len()), data]))
+}
+
+fn object_header(kind: ObjectKind, size: usize) -> Vec<u8> {
+    format!("{} {}\0", kind.as_str(), size).into_bytes()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeEntry {
+    pub mode: u32,
+    pub name: String,
+    pub id: String,
+}
+
+/// A directory listing. Each entry is encoded as `<octal mode> <name>\0`
+/// followed by the raw bytes of the object id.
+#[derive(Debug, Clone, Default)]
+pub struct Tree {
+    pub entries: Vec<TreeEntry>,
+}
+
+impl Tree {
+    pub fn serialize(&self) -> Vec<u8> {
+        // Git orders tree entries as if directory names ended in '/'
+        let mut entries: Vec<&TreeEntry> = self.entries.iter().collect();
+        entries.sort_by_key(|entry| {
+            let mut key = entry.name.clone().into_bytes();
+            if entry.mode == MODE_TREE {
+                key.push(b'/');
+            }
+            key
+        });
+
+        let mut data = Vec::new();
+        for entry in entries {
+            data.extend_from_slice(format!("{:o} {}\0", entry.mode, entry.name).as_bytes());
+            data.extend_from_slice(&hex::decode(&entry.id).expect("object ids are valid hex"));
+        }
+        data
+    }
+
+    pub fn parse(data: &[u8], format: ObjectFormat) -> Result<Self> {
+        let mut entries = Vec::new();
+        let mut rest = data;
+        while !rest.is_empty() {
+            let nul = rest
+                .iter()
+                .position(|&b| b == 0)
+                .ok_or_else(|| anyhow!("tree entry is missing its name terminator"))?;
+            let header = std::str::from_utf8(&rest[..nul])?;
+            let (mode, name) = header
+                .split_once(' ')
+                .ok_or_else(|| anyhow!("malformed tree entry '{}'", header))?;
+            let id_end = nul + 1 + format.raw_len();
+            if rest.len() < id_end {
+                bail!("tree entry '{}' is truncated", name);
+            }
+
+            entries.push(TreeEntry {
+                mode: u32::from_str_radix(mode, 8)?,
+                name: name.to_string(),
+                id: hex::encode(&rest[nul + 1..id_end]),
+            });
+            rest = &rest[id_end..];
+        }
+        Ok(Tree { entries })
+    }
+}
+
+/// An author or committer line: `Name <email> <unix time> <+hhmm>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+    pub time: DateTime<FixedOffset>,
+}
+
+impl Signature {
+    fn serialize(&self) -> String {
+        format!(
+            "{} <{}> {} {}",
+            self.name,
+            self.email,
+            self.time.timestamp(),
+            self.time.format("%z")
+        )
+    }
+
+    fn parse(line: &str) -> Result<Self> {
+        let malformed = || anyhow!("malformed signature '{}'", line);
+        let (name, rest) = line.split_once(" <").ok_or_else(malformed)?;
+        let (email, rest) = rest.split_once("> ").ok_or_else(malformed)?;
+        let (timestamp, offset) = rest.split_once(' ').ok_or_else(malformed)?;
+
+        let offset = parse_offset(offset).ok_or_else(malformed)?;
+        let time = offset
+            .timestamp_opt(timestamp.parse()?, 0)
+            .single()
+            .ok_or_else(malformed)?;
+
+        Ok(Signature {
+            name: name.to_string(),
+            email: email.to_string(),
+            time,
+        })
+    }
+}
+
+/// Parse a `+hhmm`/`-hhmm` timezone offset.
+pub(crate) fn parse_offset(offset: &str) -> Option<FixedOffset> {
+    if offset.len() != 5 {
+        return None;
+    }
+    let sign = match &offset[..1] {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let hours: i32 = offset[1..3].parse().ok()?;
+    let minutes: i32 = offset[3..5].parse().ok()?;
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+#[derive(Debug, Clone)]
+pub struct Commit {
+    pub tree: String,
+    pub parents: Vec<String>,
+    pub author: Signature,
+    pub committer: Signature,
+    pub message: String,
+}
+
+impl Commit {
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut text = format!("tree {}\n", self.tree);
+        for parent in &self.parents {
+            text.push_str(&format!("parent {}\n", parent));
+        }
+        text.push_str(&format!("author {}\n", self.author.serialize()));
+        text.push_str(&format!("committer {}\n", self.committer.serialize()));
+        text.push('\n');
+        text.push_str(&self.message);
+        if !self.message.ends_with('\n') {
+            text.push('\n');
+        }
+        text.into_bytes()
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let text = std::str::from_utf8(data)?;
+        let (headers, message) = text.split_once("\n\n").unwrap_or((text, ""));
+
+        let mut tree = None;
+        let mut parents = Vec::new();
+        let mut author = None;
+        let mut committer = None;
+        for line in headers.lines() {
+            match line.split_once(' ') {
+                Some(("tree", id)) => tree = Some(id.to_string()),
+                Some(("parent", id)) => parents.push(id.to_string()),
+                Some(("author", sig)) => author = Some(Signature::parse(sig)?),
+                Some(("committer", sig)) => committer = Some(Signature::parse(sig)?),
+                _ => {}
+            }
+        }
+
+        Ok(Commit {
+            tree: tree.ok_or_else(|| anyhow!("commit has no tree"))?,
+            parents,
+            author: author.ok_or_else(|| anyhow!("commit has no author"))?,
+            committer: committer.ok_or_else(|| anyhow!("commit has no committer"))?,
+            message: message.to_string(),
+        })
+    }
+}
@@ -0,0 +1,322 @@
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use crate::branch;
+use crate::index::{self, Index};
+use crate::refs::Head;
+use crate::repository::{Conflict, Repository};
+use crate::status::{Change, Status, StatusEntry};
+
+/// The `git status --porcelain` format version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    V1,
+    V2,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    pub version: Version,
+    /// End every line with NUL instead of a newline, and leave paths
+    /// unquoted (`-z`).
+    pub nul: bool,
+    /// Start with the branch headers (`--branch`).
+    pub branch: bool,
+}
+
+/// Where HEAD is, as reported by the branch headers.
+struct BranchState {
+    /// The HEAD commit, `None` before the first commit.
+    oid: Option<String>,
+    /// The checked-out branch, `None` when detached.
+    head: Option<String>,
+    upstream: Option<String>,
+    /// Commits ahead of and behind the upstream, when it exists.
+    ahead_behind: Option<(usize, usize)>,
+}
+
+/// Format `status` the way `git status --porcelain` does, so tools that
+/// parse git's output can read it. As in git, version 1 paths are relative
+/// to the top of the working tree and version 2 paths to the current
+/// directory, unless `-z` is given. Untracked files come after everything
+/// else, with wholly untracked directories shown as `dir/`. Renames and
+/// copies show where they came from: `R  old -> new` in version 1 and a
+/// `2` line with the similarity in version 2.
+pub fn render(repo: &dyn Repository, status: &Status, options: Options) -> Result<String> {
+    let eol = if options.nul { '\0' } else { '\n' };
+    let path = |path: &str| {
+        let shown = match options.version {
+            Version::V1 => path.to_string(),
+            // With -z git leaves even version 2 paths as they are
+            Version::V2 if options.nul => path.to_string(),
+            Version::V2 => {
+                let relative = repo.location().display_path(path.trim_end_matches('/'));
+                if path.ends_with('/') { format!("{}/", relative) } else { relative }
+            }
+        };
+        if options.nul { shown } else { quote(&shown, options.version == Version::V1) }
+    };
+    let conflicts: HashMap<String, Conflict> = repo.conflicts()?
+        .into_iter()
+        .map(|conflict| (conflict.path.clone(), conflict))
+        .collect();
+    let (untracked, tracked): (Vec<&StatusEntry>, Vec<&StatusEntry>) = status.entries
+        .iter()
+        .partition(|entry| entry.is_untracked());
+
+    let index = repo.load_index()?;
+    let mut out = String::new();
+    if options.branch {
+        let state = branch_state(repo, &status.head)?;
+        match options.version {
+            Version::V1 => write!(out, "{}{}", branch_line(&state), eol)?,
+            Version::V2 => {
+                write!(out, "# branch.oid {}{}", state.oid.as_deref().unwrap_or("(initial)"), eol)?;
+                write!(out, "# branch.head {}{}", state.head.as_deref().unwrap_or("(detached)"), eol)?;
+                if let Some(upstream) = &state.upstream {
+                    write!(out, "# branch.upstream {}{}", upstream, eol)?;
+                }
+                if let Some((ahead, behind)) = state.ahead_behind {
+                    write!(out, "# branch.ab +{} -{}{}", ahead, behind, eol)?;
+                }
+            }
+        }
+    }
+
+    match options.version {
+        Version::V1 => {
+            for entry in tracked {
+                let (x, y) = match conflicts.get(&entry.path) {
+                    Some(conflict) => conflict.xy(),
+                    None => entry.xy(),
+                };
+                match &entry.origin {
+                    // With -z the new path comes first and needs no arrow
+                    Some(origin) if options.nul => write!(out, "{}{} {}{}{}{}", x, y, path(&entry.path), eol, path(&origin.path), eol)?,
+                    Some(origin) => write!(out, "{}{} {} -> {}{}", x, y, path(&origin.path), path(&entry.path), eol)?,
+                    None => write!(out, "{}{} {}{}", x, y, path(&entry.path), eol)?,
+                }
+            }
+        }
+        Version::V2 => {
+            let zero_id = "0".repeat(repo.format().raw_len() * 2);
+            let head_files = repo.head_files()?;
+            let root = &repo.location().work_tree;
+            let mode = |mode: Option<u32>| format!("{:06o}", mode.unwrap_or(0));
+
+            // Ordinary entries come first, then unmerged ones, as in git
+            let (unmerged, ordinary): (Vec<&StatusEntry>, Vec<&StatusEntry>) = tracked
+                .into_iter()
+                .partition(|entry| conflicts.contains_key(&entry.path));
+            for entry in ordinary {
+                // A rename's HEAD side is the file it came from
+                let head = head_files.get(entry.origin.as_ref().map_or(&entry.path, |origin| &origin.path));
+                let staged = index.get(&entry.path);
+                let code = |change: Option<Change>| change.map_or('.', Change::code);
+                let kind = if entry.origin.is_some() { '2' } else { '1' };
+                write!(out, "{} {}{} N... {} {} {} {} {} ",
+                    kind,
+                    code(entry.staged),
+                    code(entry.unstaged),
+                    mode(head.map(|(mode, _)| *mode)),
+                    mode(staged.map(|entry| entry.mode)),
+                    // Only the index's files are compared with the working tree
+                    mode(staged.and_then(|_| index::worktree_mode(&root.join(&entry.path)))),
+                    head.map_or(zero_id.as_str(), |(_, id)| id),
+                    staged.map_or(zero_id.as_str(), |entry| &entry.id)
+                )?;
+                match &entry.origin {
+                    Some(origin) => write!(out, "{}{} {}{}{}{}",
+                        code(entry.staged),
+                        origin.similarity,
+                        path(&entry.path),
+                        if options.nul { '\0' } else { '\t' },
+                        path(&origin.path),
+                        eol
+                    )?,
+                    None => write!(out, "{}{}", path(&entry.path), eol)?,
+                }
+            }
+            for entry in unmerged {
+                let conflict = &conflicts[&entry.path];
+                let (x, y) = conflict.xy();
+                let [m1, m2, m3] = conflict.stages.each_ref().map(|stage| mode(stage.as_ref().map(|(mode, _)| *mode)));
+                let [h1, h2, h3] = conflict.stages.each_ref().map(|stage| stage.as_ref().map_or(zero_id.as_str(), |(_, id)| id));
+                write!(out, "u {}{} N... {} {} {} {} {} {} {} {}{}",
+                    x, y, m1, m2, m3,
+                    mode(index::worktree_mode(&root.join(&entry.path))),
+                    h1, h2, h3,
+                    path(&entry.path),
+                    eol
+                )?;
+            }
+        }
+    }
+
+    let marker = match options.version {
+        Version::V1 => "??",
+        Version::V2 => "?",
+    };
+    for untracked in collapse_untracked(&index, &untracked) {
+        write!(out, "{} {}{}", marker, path(&untracked), eol)?;
+    }
+    Ok(out)
+}
+
+/// Replace untracked files with their topmost directory that holds no
+/// tracked files, like git's default `--untracked-files=normal`.
+fn collapse_untracked(index: &Index, untracked: &[&StatusEntry]) -> Vec<String> {
+    let tracked_dirs: HashSet<&str> = index.entries()
+        .flat_map(|entry| entry.path.match_indices('/').map(|(end, _)| &entry.path[..end]))
+        .collect();
+
+    let mut shown: Vec<String> = Vec::new();
+    for entry in untracked {
+        let untracked_dir = entry.path.match_indices('/')
+            .map(|(end, _)| &entry.path[..end])
+            .find(|dir| !tracked_dirs.contains(dir));
+        let path = match untracked_dir {
+            Some(dir) => format!("{}/", dir),
+            None => entry.path.clone(),
+        };
+        if shown.last() != Some(&path) {
+            shown.push(path);
+        }
+    }
+    shown
+}
+
+fn branch_state(repo: &dyn Repository, head: &Head) -> Result<BranchState> {
+    let oid = repo.head_commit()?;
+    let name = head.branch_name().map(str::to_string);
+    let upstream = match (&name, &oid) {
+        (Some(name), Some(_)) => repo.upstream(name)?,
+        _ => None,
+    };
+    let ahead_behind = match (&oid, &upstream) {
+        (Some(local), Some(upstream)) => match repo.resolve(upstream) {
+            Ok(upstream) => Some(branch::ahead_behind(repo, local, &upstream)?),
+            Err(_) => None,
+        },
+        _ => None,
+    };
+    Ok(BranchState { oid, head: name, upstream, ahead_behind })
+}
+
+/// The `## branch...upstream [ahead 1, behind 2]` header of version 1.
+fn branch_line(state: &BranchState) -> String {
+    let Some(name) = &state.head else {
+        return "## HEAD (no branch)".to_string();
+    };
+    if state.oid.is_none() {
+        return format!("## No commits yet on {}", name);
+    }
+
+    let mut line = format!("## {}", name);
+    if let Some(upstream) = &state.upstream {
+        write!(line, "...{}", upstream).expect("writing to a String");
+        match state.ahead_behind {
+            None => line.push_str(" [gone]"),
+            Some((0, 0)) => {}
+            Some((ahead, 0)) => write!(line, " [ahead {}]", ahead).expect("writing to a String"),
+            Some((0, behind)) => write!(line, " [behind {}]", behind).expect("writing to a String"),
+            Some((ahead, behind)) => write!(line, " [ahead {}, behind {}]", ahead, behind).expect("writing to a String"),
+        }
+    }
+    line
+}
+
+/// Quote a path the way git does when it holds characters that would be
+/// ambiguous in line-based output: C-style escapes inside double quotes,
+/// with bytes outside printable ASCII written in octal. Version 1 also
+/// quotes paths with a space, which `old -> new` would make ambiguous.
+fn quote(path: &str, space: bool) -> String {
+    let special = |byte: u8| !(0x20..0x7f).contains(&byte) || byte == b'"' || byte == b'\\' || (space && byte == b' ');
+    if !path.bytes().any(special) {
+        return path.to_string();
+    }
+
+    let mut quoted = String::from("\"");
+    for byte in path.bytes() {
+        match byte {
+            b'"' => quoted.push_str("\\\""),
+            b'\\' => quoted.push_str("\\\\"),
+            0x07 => quoted.push_str("\\a"),
+            0x08 => quoted.push_str("\\b"),
+            b'\t' => quoted.push_str("\\t"),
+            b'\n' => quoted.push_str("\\n"),
+            0x0b => quoted.push_str("\\v"),
+            0x0c => quoted.push_str("\\f"),
+            b'\r' => quoted.push_str("\\r"),
+            0x20..0x7f => quoted.push(byte as char),
+            _ => quoted.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::{IndexEntry, MODE_FILE};
+    use crate::objects::ObjectFormat;
+
+    #[test]
+    fn paths_are_quoted_only_when_needed() {
+        assert_eq!(quote("src/main.rs", true), "src/main.rs");
+        assert_eq!(quote("a b.txt", true), "\"a b.txt\"");
+        assert_eq!(quote("a b.txt", false), "a b.txt");
+        assert_eq!(quote("say \"hi\"\\", false), "\"say \\\"hi\\\"\\\\\"");
+        assert_eq!(quote("tab\there\n", false), "\"tab\\there\\n\"");
+        assert_eq!(quote("café", false), "\"caf\\303\\251\"");
+    }
+
+    fn state(head: Option<&str>, oid: bool, upstream: Option<&str>, ahead_behind: Option<(usize, usize)>) -> BranchState {
+        BranchState {
+            oid: oid.then(|| "1".repeat(40)),
+            head: head.map(str::to_string),
+            upstream: upstream.map(str::to_string),
+            ahead_behind,
+        }
+    }
+
+    #[test]
+    fn branch_line_shows_where_head_is() {
+        assert_eq!(branch_line(&state(Some("main"), true, None, None)), "## main");
+        assert_eq!(branch_line(&state(Some("main"), true, Some("origin/main"), Some((0, 0)))), "## main...origin/main");
+        assert_eq!(branch_line(&state(Some("main"), true, Some("origin/main"), Some((2, 0)))), "## main...origin/main [ahead 2]");
+        assert_eq!(branch_line(&state(Some("main"), true, Some("origin/main"), Some((0, 3)))), "## main...origin/main [behind 3]");
+        assert_eq!(branch_line(&state(Some("main"), true, Some("origin/main"), Some((2, 3)))), "## main...origin/main [ahead 2, behind 3]");
+        assert_eq!(branch_line(&state(Some("main"), true, Some("origin/main"), None)), "## main...origin/main [gone]");
+        assert_eq!(branch_line(&state(Some("main"), false, None, None)), "## No commits yet on main");
+        assert_eq!(branch_line(&state(None, true, None, None)), "## HEAD (no branch)");
+    }
+
+    #[test]
+    fn untracked_directories_without_tracked_files_are_collapsed() {
+        let mut index = Index::new(ObjectFormat::Sha1);
+        for path in ["src/main.rs", "docs/api/index.md"] {
+            index.add(IndexEntry {
+                path: path.to_string(),
+                mode: MODE_FILE,
+                size: 0,
+                mtime_secs: 0,
+                mtime_nanos: 0,
+                id: "0".repeat(40),
+            });
+        }
+        let entries: Vec<StatusEntry> = ["build/out/a.o", "build/out/b.o", "docs/api/new.md", "docs/guide/intro.md", "notes.txt", "src/lib.rs"]
+            .iter()
+            .map(|path| StatusEntry {
+                path: path.to_string(),
+                staged: Some(Change::Untracked),
+                unstaged: Some(Change::Untracked),
+                origin: None,
+            })
+            .collect();
+        let untracked: Vec<&StatusEntry> = entries.iter().collect();
+
+        assert_eq!(collapse_untracked(&index, &untracked), ["build/", "docs/api/new.md", "docs/guide/", "notes.txt", "src/lib.rs"]);
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::{Result, bail};
+use std::fs;
+use std::path::Path;
+use crate::objects::ObjectStore;
+
+/// What `.rit/HEAD` points at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Head {
+    /// A symbolic ref such as `refs/heads/main`.
+    Branch(String),
+    /// A commit id checked out directly.
+    Detached(String),
+}
+
+impl Head {
+    /// The short branch name, or `None` for a detached HEAD.
+    pub fn branch_name(&self) -> Option<&str> {
+        match self {
+            Head::Branch(name) => Some(name.strip_prefix("refs/heads/").unwrap_or(name)),
+            Head::Detached(_) => None,
+        }
+    }
+}
+
+pub fn read_head(rit_dir: &Path) -> Result<Head> {
+    let content = fs::read_to_string(rit_dir.join("HEAD"))?;
+    let content = content.trim();
+    match content.strip_prefix("ref: ") {
+        Some(name) => Ok(Head::Branch(name.to_string())),
+        None if !content.is_empty() => Ok(Head::Detached(content.to_string())),
+        None => bail!("HEAD is empty"),
+    }
+}
+
+/// Point HEAD at a branch or, detached, directly at a commit.
+pub fn write_head(rit_dir: &Path, head: &Head) -> Result<()> {
+    let content = match head {
+        Head::Branch(name) => format!("ref: {}\n", name),
+        Head::Detached(id) => format!("{}\n", id),
+    };
+    fs::write(rit_dir.join("HEAD"), content)?;
+    Ok(())
+}
+
+/// Read the commit id a ref such as `refs/heads/main` points at. Returns
+/// `None` for a branch that has no commits yet.
+pub fn read_ref(rit_dir: &Path, name: &str) -> Result<Option<String>> {
+    let path = rit_dir.join(name);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let id = fs::read_to_string(path)?.trim().to_string();
+    Ok(if id.is_empty() { None } else { Some(id) })
+}
+
+pub fn write_ref(rit_dir: &Path, name: &str, id: &str) -> Result<()> {
+    let path = rit_dir.join(name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("lock");
+    fs::write(&tmp_path, format!("{}\n", id))?;
+    fs::rename(tmp_path, path)?;
+    Ok(())
+}
+
+/// Delete a ref and any directories left empty by it (branch names such
+/// as `feature/login` live in subdirectories).
+pub fn delete_ref(rit_dir: &Path, name: &str) -> Result<()> {
+    let path = rit_dir.join(name);
+    fs::remove_file(&path)?;
+
+    let refs_dir = rit_dir.join("refs");
+    let mut dir = path.parent();
+    while let Some(current) = dir {
+        if current == refs_dir || fs::remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+    Ok(())
+}
+
+/// Check a branch name against git's ref naming rules.
+pub fn is_valid_branch_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "HEAD"
+        && !name.starts_with('-')
+        && !name.starts_with('/')
+        && !name.ends_with('/')
+        && !name.ends_with('.')
+        && !name.ends_with(".lock")
+        && !name.contains("..")
+        && !name.contains("//")
+        && !name.contains("@{")
+        && !name.split('/').any(|part| part.starts_with('.'))
+        && !name.chars().any(|c| c.is_whitespace() || c.is_control() || "~^:?*[\\".contains(c))
+}
+
+/// Resolve `HEAD`, a branch name or a full or abbreviated commit id to a
+/// commit id.
+pub fn resolve_revision(rit_dir: &Path, store: &ObjectStore, revision: &str) -> Result<String> {
+    if revision == "HEAD" {
+        return match head_commit(rit_dir)? {
+            Some(id) => Ok(id),
+            None => bail!("HEAD does not point at a commit yet"),
+        };
+    }
+    if let Some(id) = read_ref(rit_dir, &format!("refs/heads/{}", revision))? {
+        return Ok(id);
+    }
+    if let Ok(id) = store.resolve_prefix(revision) {
+        store.read_commit(&id)?;
+        return Ok(id);
+    }
+    bail!("not a valid revision: '{}'", revision)
+}
+
+/// Every branch under `refs/heads` with the commit it points at, sorted by
+/// name. Branch names may contain `/`.
+pub fn list_branches(rit_dir: &Path) -> Result<Vec<(String, String)>> {
+    let heads_dir = rit_dir.join("refs").join("heads");
+    let mut branches = Vec::new();
+    if !heads_dir.exists() {
+        return Ok(branches);
+    }
+
+    for entry in walkdir::WalkDir::new(&heads_dir).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.file_type().is_file() || entry.path().extension().is_some_and(|ext| ext == "lock") {
+            continue;
+        }
+        let name = entry.path().strip_prefix(&heads_dir)?.to_string_lossy().replace('\\', "/");
+        if let Some(id) = read_ref(rit_dir, &format!("refs/heads/{}", name))? {
+            branches.push((name, id));
+        }
+    }
+
+    Ok(branches)
+}
+
+/// The commit HEAD currently resolves to, if any.
+pub fn head_commit(rit_dir: &Path) -> Result<Option<String>> {
+    match read_head(rit_dir)? {
+        Head::Branch(name) => read_ref(rit_dir, &name),
+        Head::Detached(id) => Ok(Some(id)),
+    }
+}
+
+/// Move the current branch (or a detached HEAD) to `id`.
+pub fn advance_head(rit_dir: &Path, id: &str) -> Result<()> {
+    match read_head(rit_dir)? {
+        Head::Branch(name) => write_ref(rit_dir, &name, id),
+        Head::Detached(_) => write_head(rit_dir, &Head::Detached(id.to_string())),
+    }
+}
@@ -0,0 +1,318 @@
+use anyhow::{Result, anyhow, bail};
+use chrono::{DateTime, FixedOffset, TimeZone};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use crate::index::{Index, IndexEntry};
+use crate::location::Location;
+use crate::objects::{Commit, ObjectFormat, Signature};
+use crate::refs::Head;
+use super::{Conflict, Files, RefKind, Reference, Repository};
+
+/// A git repository, read and written through libgit2.
+pub struct GitRepository {
+    location: Location,
+    repo: git2::Repository,
+}
+
+impl GitRepository {
+    pub fn open(location: Location) -> Result<Self> {
+        let repo = location.open_git()?;
+        Ok(GitRepository { location, repo })
+    }
+
+    fn find_branch(&self, name: &str) -> Result<git2::Branch<'_>> {
+        Ok(self.repo.find_branch(name, git2::BranchType::Local)?)
+    }
+
+    fn find_commit(&self, id: &str) -> Result<git2::Commit<'_>> {
+        Ok(self.repo.find_commit(git2::Oid::from_str(id)?)?)
+    }
+}
+
+impl Repository for GitRepository {
+    fn location(&self) -> &Location {
+        &self.location
+    }
+
+    fn format(&self) -> ObjectFormat {
+        ObjectFormat::Sha1
+    }
+
+    fn config_path(&self) -> PathBuf {
+        self.repo.path().join("config")
+    }
+
+    fn head(&self) -> Result<Head> {
+        let head = self.repo.find_reference("HEAD")?;
+        match head.symbolic_target() {
+            Some(target) => Ok(Head::Branch(target.to_string())),
+            None => match head.target() {
+                Some(id) => Ok(Head::Detached(id.to_string())),
+                None => bail!("HEAD is empty"),
+            },
+        }
+    }
+
+    fn set_head(&self, head: &Head) -> Result<()> {
+        match head {
+            Head::Branch(name) => self.repo.set_head(name)?,
+            Head::Detached(id) => self.repo.set_head_detached(git2::Oid::from_str(id)?)?,
+        }
+        Ok(())
+    }
+
+    fn head_commit(&self) -> Result<Option<String>> {
+        // An unborn branch has no HEAD commit yet
+        Ok(self.repo.head().ok().and_then(|head| head.target()).map(|id| id.to_string()))
+    }
+
+    fn advance_head(&self, id: &str) -> Result<()> {
+        let oid = git2::Oid::from_str(id)?;
+        let summary = self.find_commit(id)?.summary().unwrap_or("").to_string();
+        match self.head()? {
+            Head::Branch(name) => {
+                self.repo.reference(&name, oid, true, &format!("commit: {}", summary))?;
+            }
+            Head::Detached(_) => self.repo.set_head_detached(oid)?,
+        }
+        Ok(())
+    }
+
+    fn resolve(&self, revision: &str) -> Result<String> {
+        match self.repo.revparse_single(revision).and_then(|object| object.peel_to_commit()) {
+            Ok(commit) => Ok(commit.id().to_string()),
+            Err(_) => bail!("not a valid revision: '{}'", revision),
+        }
+    }
+
+    fn references(&self) -> Result<Vec<Reference>> {
+        let mut references = Vec::new();
+        for reference in self.repo.references()? {
+            let reference = reference?;
+            let kind = if reference.is_branch() {
+                RefKind::Branch
+            } else if reference.is_remote() {
+                RefKind::RemoteBranch
+            } else if reference.is_tag() {
+                RefKind::Tag
+            } else {
+                continue;
+            };
+            let (Some(name), Ok(commit)) = (reference.shorthand(), reference.peel_to_commit()) else {
+                continue;
+            };
+            references.push(Reference {
+                name: name.to_string(),
+                kind,
+                target: commit.id().to_string(),
+            });
+        }
+        references.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(references)
+    }
+
+    fn create_branch(&self, name: &str, id: &str) -> Result<()> {
+        self.repo.branch(name, &self.find_commit(id)?, false)?;
+        Ok(())
+    }
+
+    fn delete_branch(&self, name: &str) -> Result<()> {
+        self.find_branch(name)?.delete()?;
+        Ok(())
+    }
+
+    fn rename_branch(&self, old: &str, new: &str) -> Result<()> {
+        // libgit2 moves HEAD and the branch's config section along with it
+        self.find_branch(old)?.rename(new, false)?;
+        Ok(())
+    }
+
+    fn upstream(&self, name: &str) -> Result<Option<String>> {
+        Ok(self.find_branch(name)?.upstream().ok()
+            .and_then(|upstream| upstream.name().ok().flatten().map(str::to_string)))
+    }
+
+    fn set_upstream(&self, name: &str, upstream: Option<&str>) -> Result<()> {
+        self.find_branch(name)?.set_upstream(upstream)?;
+        Ok(())
+    }
+
+    fn read_commit(&self, id: &str) -> Result<Commit> {
+        let commit = self.find_commit(id)?;
+        Ok(Commit {
+            tree: commit.tree_id().to_string(),
+            parents: commit.parent_ids().map(|parent| parent.to_string()).collect(),
+            author: from_git_signature(&commit.author())?,
+            committer: from_git_signature(&commit.committer())?,
+            message: commit.message().unwrap_or("").to_string(),
+        })
+    }
+
+    fn write_commit(&self, commit: &Commit) -> Result<String> {
+        let tree = self.repo.find_tree(git2::Oid::from_str(&commit.tree)?)?;
+        let parents = commit.parents.iter()
+            .map(|id| self.find_commit(id))
+            .collect::<Result<Vec<_>>>()?;
+        let parents: Vec<&git2::Commit> = parents.iter().collect();
+        // Messages end in a newline, as `git commit` and the native store
+        // write them
+        let mut message = commit.message.clone();
+        if !message.ends_with('\n') {
+            message.push('\n');
+        }
+        let id = self.repo.commit(
+            None,
+            &to_git_signature(&commit.author)?,
+            &to_git_signature(&commit.committer)?,
+            &message,
+            &tree,
+            &parents,
+        )?;
+        Ok(id.to_string())
+    }
+
+    fn read_blob(&self, id: &str) -> Result<Vec<u8>> {
+        Ok(self.repo.find_blob(git2::Oid::from_str(id)?)?.content().to_vec())
+    }
+
+    fn write_blob(&self, data: &[u8]) -> Result<String> {
+        Ok(self.repo.blob(data)?.to_string())
+    }
+
+    fn tree_files(&self, tree: &str) -> Result<Files> {
+        let tree = self.repo.find_tree(git2::Oid::from_str(tree)?)?;
+        let mut files = Files::new();
+        tree.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
+            if entry.kind() == Some(git2::ObjectType::Blob) {
+                files.insert(
+                    format!("{}{}", dir, entry.name().unwrap_or("")),
+                    (entry.filemode() as u32, entry.id().to_string()),
+                );
+            }
+            git2::TreeWalkResult::Ok
+        })?;
+        Ok(files)
+    }
+
+    fn is_ancestor(&self, ancestor: &str, descendant: &str) -> Result<bool> {
+        let (ancestor, descendant) = (git2::Oid::from_str(ancestor)?, git2::Oid::from_str(descendant)?);
+        Ok(ancestor == descendant || self.repo.graph_descendant_of(descendant, ancestor)?)
+    }
+
+    fn load_index(&self) -> Result<Index> {
+        let mut index = Index::new(self.format());
+        for entry in self.repo.index()?.iter().filter(|entry| stage(entry) == 0) {
+            index.add(IndexEntry {
+                path: String::from_utf8_lossy(&entry.path).to_string(),
+                mode: entry.mode,
+                size: entry.file_size,
+                mtime_secs: entry.mtime.seconds() as u32,
+                mtime_nanos: entry.mtime.nanoseconds(),
+                id: entry.id.to_string(),
+            });
+        }
+        Ok(index)
+    }
+
+    /// Bring git's index in line with `index`, keeping the full stat data
+    /// git recorded for entries that didn't change.
+    fn save_index(&self, index: &Index) -> Result<()> {
+        let mut git_index = self.repo.index()?;
+        let conflicts: Vec<String> = self.conflicts()?
+            .into_iter()
+            .map(|conflict| conflict.path)
+            .collect();
+
+        let removed: Vec<Vec<u8>> = git_index.iter()
+            .filter(|entry| stage(entry) == 0)
+            .map(|entry| entry.path)
+            .filter(|path| index.get(&String::from_utf8_lossy(path)).is_none())
+            .collect();
+        for path in removed {
+            git_index.remove(Path::new(&*String::from_utf8_lossy(&path)), 0)?;
+        }
+
+        for entry in index.entries() {
+            let path = Path::new(&entry.path);
+            if let Some(existing) = git_index.get_path(path, 0)
+                && existing.id.to_string() == entry.id
+                && existing.mode == entry.mode
+                && existing.file_size == entry.size
+                && existing.mtime.seconds() as u32 == entry.mtime_secs
+            {
+                continue;
+            }
+            // Staging a conflicted path resolves it
+            if conflicts.contains(&entry.path) {
+                for stage in 1..=3 {
+                    // Not every stage exists for every conflict
+                    let _ = git_index.remove(path, stage);
+                }
+            }
+            git_index.add(&to_git_entry(entry)?)?;
+        }
+
+        git_index.write()?;
+        Ok(())
+    }
+
+    fn write_tree(&self, index: &Index) -> Result<String> {
+        let mut tree_index = git2::Index::new()?;
+        for entry in index.entries() {
+            tree_index.add(&to_git_entry(entry)?)?;
+        }
+        Ok(tree_index.write_tree_to(&self.repo)?.to_string())
+    }
+
+    fn conflicts(&self) -> Result<Vec<Conflict>> {
+        let mut conflicts: BTreeMap<String, Conflict> = BTreeMap::new();
+        for entry in self.repo.index()?.iter().filter(|entry| stage(entry) != 0) {
+            let path = String::from_utf8_lossy(&entry.path).to_string();
+            let conflict = conflicts.entry(path.clone())
+                .or_insert_with(|| Conflict { path, stages: [None, None, None] });
+            conflict.stages[stage(&entry) as usize - 1] = Some((entry.mode, entry.id.to_string()));
+        }
+        Ok(conflicts.into_values().collect())
+    }
+}
+
+/// The merge stage of an index entry: 0 when resolved, 1-3 for the
+/// ancestor, ours and theirs versions of a conflicted path.
+fn stage(entry: &git2::IndexEntry) -> u16 {
+    (entry.flags >> 12) & 0x3
+}
+
+fn to_git_entry(entry: &IndexEntry) -> Result<git2::IndexEntry> {
+    let time = git2::IndexTime::new(entry.mtime_secs as i32, entry.mtime_nanos);
+    Ok(git2::IndexEntry {
+        ctime: time,
+        mtime: time,
+        dev: 0,
+        ino: 0,
+        mode: entry.mode,
+        uid: 0,
+        gid: 0,
+        file_size: entry.size,
+        id: git2::Oid::from_str(&entry.id)?,
+        flags: entry.path.len().min(0xfff) as u16,
+        flags_extended: 0,
+        path: entry.path.as_bytes().to_vec(),
+    })
+}
+
+fn from_git_signature(signature: &git2::Signature) -> Result<Signature> {
+    let when = signature.when();
+    let time: DateTime<FixedOffset> = FixedOffset::east_opt(when.offset_minutes() * 60)
+        .and_then(|offset| offset.timestamp_opt(when.seconds(), 0).single())
+        .ok_or_else(|| anyhow!("invalid commit time"))?;
+    Ok(Signature {
+        name: signature.name().unwrap_or("").to_string(),
+        email: signature.email().unwrap_or("").to_string(),
+        time,
+    })
+}
+
+fn to_git_signature(signature: &Signature) -> Result<git2::Signature<'static>> {
+    let time = git2::Time::new(signature.time.timestamp(), signature.time.offset().local_minus_utc() / 60);
+    Ok(git2::Signature::new(&signature.name, &signature.email, &time)?)
+}
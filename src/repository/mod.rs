@@ -0,0 +1,141 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use crate::config::Config;
+use crate::index::Index;
+use crate::location::Location;
+use crate::objects::{Commit, ObjectFormat};
+use crate::refs::Head;
+
+mod git;
+mod native;
+
+pub use git::GitRepository;
+pub use native::NativeRepository;
+
+/// Files tracked by a tree, keyed by path, with their mode and blob id.
+pub type Files = BTreeMap<String, (u32, String)>;
+
+/// A path with an unresolved merge conflict, and the mode and blob id of
+/// each version recorded for it: the common ancestor, ours and theirs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub path: String,
+    pub stages: [Option<(u32, String)>; 3],
+}
+
+impl Conflict {
+    /// The two-letter code `git status` shows for the conflict, such as
+    /// `UU` when both sides modified the path or `DU` when we deleted it.
+    pub fn xy(&self) -> (char, char) {
+        match self.stages.each_ref().map(Option::is_some) {
+            [true, false, false] => ('D', 'D'),
+            [false, true, false] => ('A', 'U'),
+            [true, true, false] => ('U', 'D'),
+            [false, false, true] => ('U', 'A'),
+            [true, false, true] => ('D', 'U'),
+            [false, true, true] => ('A', 'A'),
+            _ => ('U', 'U'),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefKind {
+    Branch,
+    RemoteBranch,
+    Tag,
+}
+
+/// A named ref and the commit it resolves to.
+#[derive(Debug, Clone)]
+pub struct Reference {
+    /// Short name, such as `main`, `origin/main` or `v1.0`.
+    pub name: String,
+    pub kind: RefKind,
+    pub target: String,
+}
+
+/// Storage for one repository: its refs, objects and index. Native `.rit`
+/// repositories and git repositories both implement it, so every command
+/// runs the same code against either.
+pub trait Repository {
+    /// Where the working tree is and where the command was run from.
+    fn location(&self) -> &Location;
+    /// The hash function naming this repository's objects.
+    fn format(&self) -> ObjectFormat;
+    /// The repository's own config file, `.rit/config` or `.git/config`.
+    fn config_path(&self) -> PathBuf;
+    /// Settings from the system, global and repository config files, the
+    /// most specific winning.
+    fn config(&self) -> Result<Config> {
+        Config::load_all(Some(&self.config_path()))
+    }
+
+    fn head(&self) -> Result<Head>;
+    fn set_head(&self, head: &Head) -> Result<()>;
+    /// The commit HEAD resolves to, or `None` on a branch with no commits.
+    fn head_commit(&self) -> Result<Option<String>>;
+    /// Move the current branch (or a detached HEAD) to `id`.
+    fn advance_head(&self, id: &str) -> Result<()>;
+    /// Resolve `HEAD`, a branch name or a full or abbreviated commit id to
+    /// a commit id.
+    fn resolve(&self, revision: &str) -> Result<String>;
+    /// Every branch, remote branch and tag that points at a commit, sorted
+    /// by name.
+    fn references(&self) -> Result<Vec<Reference>>;
+
+    fn create_branch(&self, name: &str, id: &str) -> Result<()>;
+    fn delete_branch(&self, name: &str) -> Result<()>;
+    /// Rename a branch, keeping HEAD and its upstream pointing at it.
+    fn rename_branch(&self, old: &str, new: &str) -> Result<()>;
+    fn upstream(&self, name: &str) -> Result<Option<String>>;
+    fn set_upstream(&self, name: &str, upstream: Option<&str>) -> Result<()>;
+
+    fn read_commit(&self, id: &str) -> Result<Commit>;
+    fn write_commit(&self, commit: &Commit) -> Result<String>;
+    fn read_blob(&self, id: &str) -> Result<Vec<u8>>;
+    fn write_blob(&self, data: &[u8]) -> Result<String>;
+    /// Every file below a tree.
+    fn tree_files(&self, tree: &str) -> Result<Files>;
+    /// Whether `ancestor` is reachable from `descendant`.
+    fn is_ancestor(&self, ancestor: &str, descendant: &str) -> Result<bool>;
+
+    /// The staged files. Paths with unresolved merge conflicts are left
+    /// out; see [`Repository::conflicts`].
+    fn load_index(&self) -> Result<Index>;
+    fn save_index(&self, index: &Index) -> Result<()>;
+    /// Store the staged files as trees and return the root tree's id.
+    fn write_tree(&self, index: &Index) -> Result<String>;
+    /// Paths with unresolved merge conflicts, sorted by path.
+    fn conflicts(&self) -> Result<Vec<Conflict>>;
+
+    /// The files of a commit's tree.
+    fn commit_files(&self, id: &str) -> Result<Files> {
+        self.tree_files(&self.read_commit(id)?.tree)
+    }
+
+    /// The files of HEAD's tree, empty before the first commit.
+    fn head_files(&self) -> Result<Files> {
+        match self.head_commit()? {
+            Some(id) => self.commit_files(&id),
+            None => Ok(Files::new()),
+        }
+    }
+}
+
+/// Open the repository around the current directory, or `None` outside
+/// of one.
+pub fn discover() -> Result<Option<Box<dyn Repository>>> {
+    let Some(location) = Location::discover()? else {
+        return Ok(None);
+    };
+    Ok(Some(open(location)?))
+}
+
+pub fn open(location: Location) -> Result<Box<dyn Repository>> {
+    Ok(match location.rit_dir.clone() {
+        Some(rit_dir) => Box::new(NativeRepository::open(location, &rit_dir)?),
+        None => Box::new(GitRepository::open(location)?),
+    })
+}
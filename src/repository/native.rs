@@ -0,0 +1,174 @@
+use anyhow::{Result, bail};
+use std::path::{Path, PathBuf};
+use crate::config::{self, Config};
+use crate::index::Index;
+use crate::location::Location;
+use crate::objects::{Commit, ObjectFormat, ObjectKind, ObjectStore};
+use crate::refs::{self, Head};
+use super::{Conflict, Files, RefKind, Reference, Repository};
+
+/// A repository stored in `.rit`: loose objects, plain-file refs and the
+/// binary `.rit/index`.
+pub struct NativeRepository {
+    location: Location,
+    rit_dir: PathBuf,
+    store: ObjectStore,
+}
+
+impl NativeRepository {
+    pub fn open(location: Location, rit_dir: &Path) -> Result<Self> {
+        Ok(NativeRepository {
+            location,
+            rit_dir: rit_dir.to_path_buf(),
+            store: ObjectStore::open(rit_dir)?,
+        })
+    }
+
+    fn branch_ref(name: &str) -> String {
+        format!("refs/heads/{}", name)
+    }
+}
+
+impl Repository for NativeRepository {
+    fn location(&self) -> &Location {
+        &self.location
+    }
+
+    fn format(&self) -> ObjectFormat {
+        self.store.format()
+    }
+
+    fn config_path(&self) -> PathBuf {
+        self.rit_dir.join("config")
+    }
+
+    fn head(&self) -> Result<Head> {
+        refs::read_head(&self.rit_dir)
+    }
+
+    fn set_head(&self, head: &Head) -> Result<()> {
+        refs::write_head(&self.rit_dir, head)
+    }
+
+    fn head_commit(&self) -> Result<Option<String>> {
+        refs::head_commit(&self.rit_dir)
+    }
+
+    fn advance_head(&self, id: &str) -> Result<()> {
+        refs::advance_head(&self.rit_dir, id)
+    }
+
+    fn resolve(&self, revision: &str) -> Result<String> {
+        refs::resolve_revision(&self.rit_dir, &self.store, revision)
+    }
+
+    fn references(&self) -> Result<Vec<Reference>> {
+        Ok(refs::list_branches(&self.rit_dir)?
+            .into_iter()
+            .map(|(name, target)| Reference { name, kind: RefKind::Branch, target })
+            .collect())
+    }
+
+    fn create_branch(&self, name: &str, id: &str) -> Result<()> {
+        refs::write_ref(&self.rit_dir, &Self::branch_ref(name), id)
+    }
+
+    fn delete_branch(&self, name: &str) -> Result<()> {
+        refs::delete_ref(&self.rit_dir, &Self::branch_ref(name))?;
+        self.set_upstream(name, None)
+    }
+
+    fn rename_branch(&self, old: &str, new: &str) -> Result<()> {
+        let Some(id) = refs::read_ref(&self.rit_dir, &Self::branch_ref(old))? else {
+            bail!("branch '{}' not found", old);
+        };
+        refs::write_ref(&self.rit_dir, &Self::branch_ref(new), &id)?;
+        refs::delete_ref(&self.rit_dir, &Self::branch_ref(old))?;
+
+        if refs::read_head(&self.rit_dir)?.branch_name() == Some(old) {
+            refs::write_head(&self.rit_dir, &Head::Branch(Self::branch_ref(new)))?;
+        }
+
+        if let Some(upstream) = self.upstream(old)? {
+            self.set_upstream(old, None)?;
+            self.set_upstream(new, Some(&upstream))?;
+        }
+        Ok(())
+    }
+
+    /// Native repositories have no remotes, so an upstream is always a
+    /// local branch, recorded the way git records one (`remote = .`).
+    fn upstream(&self, name: &str) -> Result<Option<String>> {
+        let config = Config::load(&self.config_path())?;
+        let Some(merge) = config.get(&format!("branch.{}.merge", name)) else {
+            return Ok(None);
+        };
+        let merge = merge.strip_prefix("refs/heads/").unwrap_or(merge);
+        Ok(Some(match config.get(&format!("branch.{}.remote", name)) {
+            Some(".") | None => merge.to_string(),
+            Some(remote) => format!("{}/{}", remote, merge),
+        }))
+    }
+
+    fn set_upstream(&self, name: &str, upstream: Option<&str>) -> Result<()> {
+        let path = self.config_path();
+        match upstream {
+            Some(upstream) => {
+                if refs::read_ref(&self.rit_dir, &Self::branch_ref(upstream))?.is_none() {
+                    bail!("upstream branch '{}' not found", upstream);
+                }
+                config::set_value(&path, &format!("branch.{}.remote", name), ".")?;
+                config::set_value(&path, &format!("branch.{}.merge", name), &Self::branch_ref(upstream))?;
+            }
+            None => {
+                config::unset_value(&path, &format!("branch.{}.remote", name))?;
+                config::unset_value(&path, &format!("branch.{}.merge", name))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_commit(&self, id: &str) -> Result<Commit> {
+        self.store.read_commit(id)
+    }
+
+    fn write_commit(&self, commit: &Commit) -> Result<String> {
+        self.store.write(ObjectKind::Commit, &commit.serialize())
+    }
+
+    fn read_blob(&self, id: &str) -> Result<Vec<u8>> {
+        match self.store.read(id)? {
+            (ObjectKind::Blob, data) => Ok(data),
+            (kind, _) => bail!("object {} is a {}, not a blob", id, kind.as_str()),
+        }
+    }
+
+    fn write_blob(&self, data: &[u8]) -> Result<String> {
+        self.store.write_blob(data)
+    }
+
+    fn tree_files(&self, tree: &str) -> Result<Files> {
+        self.store.flatten_tree(tree)
+    }
+
+    fn is_ancestor(&self, ancestor: &str, descendant: &str) -> Result<bool> {
+        self.store.is_ancestor(ancestor, descendant)
+    }
+
+    fn load_index(&self) -> Result<Index> {
+        Index::load(&self.rit_dir, self.store.format())
+    }
+
+    fn save_index(&self, index: &Index) -> Result<()> {
+        index.save(&self.rit_dir)
+    }
+
+    fn write_tree(&self, index: &Index) -> Result<String> {
+        index.write_tree(&self.store)
+    }
+
+    /// Native repositories can't merge yet, so nothing is ever conflicted.
+    fn conflicts(&self) -> Result<Vec<Conflict>> {
+        Ok(Vec::new())
+    }
+}
@@ -0,0 +1,207 @@
+use anyhow::Result;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use crate::gitignore::{Decision, IgnoreRules};
+use crate::index::{Index, IndexEntry};
+use crate::repository::Repository;
+
+/// A change made to the index for one file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Staged {
+    /// The file's contents were stored as blob `id` and staged.
+    Added { path: String, id: String },
+    /// The file is gone from the working tree, so its removal was staged.
+    Removed { path: String },
+}
+
+impl Staged {
+    pub fn path(&self) -> &str {
+        match self {
+            Staged::Added { path, .. } | Staged::Removed { path } => path,
+        }
+    }
+}
+
+/// What `add` did with one of the paths it was given.
+#[derive(Debug)]
+pub enum AddOutcome {
+    /// A single file was staged, or its deletion was.
+    File(Staged),
+    /// A directory was walked. `ignored` lists the untracked paths below
+    /// it that ignore rules kept out.
+    Directory { path: String, staged: Vec<Staged>, ignored: Vec<String> },
+    /// An untracked path matched an ignore rule and was left alone.
+    Ignored { path: String, decision: Decision },
+    /// Nothing exists at the path, in the working tree or the index.
+    NotFound { path: String },
+}
+
+/// Stage files and directories, given as paths relative to the top of the
+/// working tree (`""` for all of it), the way `git add` does: new and
+/// changed files are added, deleted tracked files are removed, and
+/// untracked ignored files are skipped. The index is saved once at the end.
+pub fn add(repo: &dyn Repository, paths: &[String]) -> Result<Vec<AddOutcome>> {
+    let root = repo.location().work_tree.clone();
+    let rules = IgnoreRules::load(repo.location());
+    let mut index = repo.load_index()?;
+
+    let mut outcomes = Vec::new();
+    for path in paths {
+        outcomes.push(add_path(repo, &mut index, &rules, &root, path)?);
+    }
+
+    repo.save_index(&index)?;
+    Ok(outcomes)
+}
+
+fn add_path(repo: &dyn Repository, index: &mut Index, rules: &IgnoreRules, root: &Path, path: &str) -> Result<AddOutcome> {
+    let full_path = root.join(path);
+
+    // Symlinks are staged as links, never followed
+    let Ok(metadata) = full_path.symlink_metadata() else {
+        // Adding a deleted file stages its removal
+        return Ok(match index.remove(path) {
+            Some(_) => AddOutcome::File(Staged::Removed { path: path.to_string() }),
+            None => AddOutcome::NotFound { path: path.to_string() },
+        });
+    };
+
+    // Ignored files can't be added, but files that are already tracked
+    // can still be updated
+    let tracked = index.get(path).is_some();
+    if !path.is_empty()
+        && !tracked
+        && let Some(decision) = rules.decide(path, metadata.is_dir())
+        && decision.is_ignored()
+    {
+        return Ok(AddOutcome::Ignored { path: path.to_string(), decision });
+    }
+
+    if metadata.is_dir() {
+        return add_directory(repo, index, rules, root, path);
+    }
+    let id = stage_file(repo, index, path, &full_path)?;
+    Ok(AddOutcome::File(Staged::Added { path: path.to_string(), id }))
+}
+
+fn add_directory(repo: &dyn Repository, index: &mut Index, rules: &IgnoreRules, root: &Path, dir: &str) -> Result<AddOutcome> {
+    let mut staged = Vec::new();
+    let mut seen = HashSet::new();
+
+    // A tracked file replaced by this directory is gone, and would
+    // otherwise sit in the tree next to the directory of the same name
+    if !dir.is_empty() && index.remove(dir).is_some() {
+        staged.push(Staged::Removed { path: dir.to_string() });
+    }
+
+    let walk = rules.walk(&root.join(dir));
+    for file in &walk.files {
+        let id = stage_file(repo, index, &file.path, &file.full_path)?;
+        staged.push(Staged::Added { path: file.path.clone(), id });
+        seen.insert(file.path.clone());
+    }
+
+    // Tracked files the walk skipped are either ignored, which doesn't stop
+    // them from being updated, or deleted, which stages their removal, the
+    // same way `git add` does.
+    let prefix = if dir.is_empty() { String::new() } else { format!("{}/", dir) };
+    let unseen: Vec<String> = index.entries()
+        .map(|entry| entry.path.clone())
+        .filter(|path| path.starts_with(&prefix) && !seen.contains(path))
+        .collect();
+    for path in unseen {
+        let full_path = root.join(&path);
+        if full_path.symlink_metadata().is_ok_and(|metadata| !metadata.is_dir()) {
+            let id = stage_file(repo, index, &path, &full_path)?;
+            staged.push(Staged::Added { path, id });
+        } else {
+            index.remove(&path);
+            staged.push(Staged::Removed { path });
+        }
+    }
+
+    Ok(AddOutcome::Directory { path: dir.to_string(), staged, ignored: walk.ignored })
+}
+
+/// Write the file's contents as a blob and record it in the index. A
+/// symlink's blob holds the path it points to. Returns the new object id.
+fn stage_file(repo: &dyn Repository, index: &mut Index, path: &str, full_path: &Path) -> Result<String> {
+    let data = if fs::symlink_metadata(full_path)?.file_type().is_symlink() {
+        fs::read_link(full_path)?.to_string_lossy().as_bytes().to_vec()
+    } else {
+        fs::read(full_path)?
+    };
+    let id = repo.write_blob(&data)?;
+    index.add(IndexEntry::from_file(path, full_path, id.clone())?);
+    Ok(id)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::{AddOutcome, Staged, add};
+    use crate::index::MODE_SYMLINK;
+    use crate::status;
+    use crate::test_repo::TestRepo;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn symlinks_are_staged_as_links() {
+        let repo = TestRepo::new();
+        repo.write("dir/file", "contents\n");
+        symlink("dir/file", repo.path("link")).unwrap();
+        symlink("dir", repo.path("dir-link")).unwrap();
+        repo.add(&["link", "dir-link", "dir/file"]);
+
+        let index = repo.repo.load_index().unwrap();
+        for (path, target) in [("link", "dir/file"), ("dir-link", "dir")] {
+            let entry = index.get(path).unwrap();
+            assert_eq!(entry.mode, MODE_SYMLINK);
+            assert_eq!(repo.repo.read_blob(&entry.id).unwrap(), target.as_bytes());
+        }
+        assert!(index.get("dir-link/file").is_none());
+
+        repo.commit("links", 1000);
+        assert!(status::status(repo.repo.as_ref()).unwrap().entries.is_empty());
+    }
+
+    #[test]
+    fn adding_a_directory_stages_the_symlinks_in_it() {
+        let repo = TestRepo::new();
+        repo.write("file", "contents\n");
+        symlink("file", repo.path("link")).unwrap();
+        symlink("missing", repo.path("dangling")).unwrap();
+        repo.add(&[""]);
+
+        let index = repo.repo.load_index().unwrap();
+        let paths: Vec<&str> = index.entries().map(|entry| entry.path.as_str()).collect();
+        assert_eq!(paths, ["dangling", "file", "link"]);
+        assert_eq!(index.get("dangling").unwrap().mode, MODE_SYMLINK);
+
+        // A tracked dangling link is kept, not taken for a deleted file
+        repo.add(&[""]);
+        assert!(repo.repo.load_index().unwrap().get("dangling").is_some());
+    }
+
+    #[test]
+    fn a_tracked_file_replaced_by_a_directory_is_removed() {
+        let repo = TestRepo::new();
+        repo.write("a", "file\n");
+        repo.add(&["a"]);
+        repo.commit("file", 1000);
+
+        std::fs::remove_file(repo.path("a")).unwrap();
+        repo.write("a/inner", "nested\n");
+        let outcomes = add(repo.repo.as_ref(), &["a".to_string()]).unwrap();
+        let AddOutcome::Directory { staged, .. } = &outcomes[0] else {
+            panic!("expected a directory, got {:?}", outcomes);
+        };
+        assert!(staged.contains(&Staged::Removed { path: "a".to_string() }));
+
+        let index = repo.repo.load_index().unwrap();
+        let paths: Vec<&str> = index.entries().map(|entry| entry.path.as_str()).collect();
+        assert_eq!(paths, ["a/inner"]);
+        repo.commit("directory", 2000);
+        assert!(status::status(repo.repo.as_ref()).unwrap().entries.is_empty());
+    }
+}
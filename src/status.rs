@@ -0,0 +1,361 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use crate::diff::{Candidate, Origin, RenameOptions, find_renames};
+use crate::gitignore::IgnoreRules;
+use crate::index::{Index, IndexEntry, MODE_SYMLINK};
+use crate::objects::ObjectFormat;
+use crate::refs::Head;
+use crate::repository::Repository;
+
+/// One side of a file's status: how the index differs from HEAD, or how
+/// the working tree differs from the index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Added,
+    Modified,
+    Deleted,
+    Typechange,
+    /// Staged as a move from another path; see [`StatusEntry::origin`].
+    Renamed,
+    /// Staged as a copy of another file.
+    Copied,
+    Untracked,
+    Conflicted,
+}
+
+impl Change {
+    /// The letter used for this change in a two-column `XY` code.
+    pub fn code(self) -> char {
+        match self {
+            Change::Added => 'A',
+            Change::Modified => 'M',
+            Change::Deleted => 'D',
+            Change::Typechange => 'T',
+            Change::Renamed => 'R',
+            Change::Copied => 'C',
+            Change::Untracked => '?',
+            Change::Conflicted => 'U',
+        }
+    }
+}
+
+/// A path with its staged (`X`) and unstaged (`Y`) state, like a line of
+/// `git status --short`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusEntry {
+    /// Path relative to the top of the working tree.
+    pub path: String,
+    pub staged: Option<Change>,
+    pub unstaged: Option<Change>,
+    /// Where a staged rename or copy came from.
+    pub origin: Option<Origin>,
+}
+
+impl StatusEntry {
+    fn new(path: String, staged: Option<Change>, unstaged: Option<Change>) -> Self {
+        StatusEntry { path, staged, unstaged, origin: None }
+    }
+
+    fn untracked(path: String) -> Self {
+        StatusEntry::new(path, Some(Change::Untracked), Some(Change::Untracked))
+    }
+
+    pub fn is_untracked(&self) -> bool {
+        self.unstaged == Some(Change::Untracked)
+    }
+
+    pub fn is_conflicted(&self) -> bool {
+        self.unstaged == Some(Change::Conflicted)
+    }
+
+    pub fn xy(&self) -> (char, char) {
+        (
+            self.staged.map_or(' ', Change::code),
+            self.unstaged.map_or(' ', Change::code),
+        )
+    }
+}
+
+/// The state of a working tree: where HEAD is and every changed path,
+/// sorted by path.
+#[derive(Debug, Clone)]
+pub struct Status {
+    pub head: Head,
+    pub entries: Vec<StatusEntry>,
+}
+
+/// Compare HEAD with the index and the index with the working tree,
+/// giving one entry per changed path. Staged renames are found as
+/// `status.renames` (or else `diff.renames`) says, which is on by default.
+pub fn status(repo: &dyn Repository) -> Result<Status> {
+    let config = repo.config()?;
+    let key = if config.get("status.renames").is_some() { "status.renames" } else { "diff.renames" };
+    let renames = RenameOptions::from_config(key, config.get(key))?;
+    status_with_renames(repo, renames)
+}
+
+/// [`status`], finding staged renames as `renames` says rather than as
+/// configured; `None` turns rename detection off.
+pub fn status_with_renames(repo: &dyn Repository, renames: Option<RenameOptions>) -> Result<Status> {
+    let index = repo.load_index()?;
+    let mut staged = staged_changes(repo, &index)?;
+    let mut origins = match renames {
+        Some(options) => staged_renames(repo, &index, &mut staged, options)?,
+        None => HashMap::new(),
+    };
+    let unstaged = worktree_changes(repo, &index)?;
+
+    // Merge both comparisons into one entry per path
+    let mut merged: BTreeMap<String, StatusEntry> = BTreeMap::new();
+    for (path, change) in staged {
+        let entry = merged.entry(path.clone()).or_insert(StatusEntry::new(path.clone(), None, None));
+        entry.staged = Some(change);
+        entry.origin = origins.remove(&path);
+    }
+    // A path whose deletion is staged can be back on disk as an untracked
+    // file, so untracked entries are kept apart rather than merged
+    let mut untracked = Vec::new();
+    for status in unstaged {
+        if status.is_untracked() {
+            untracked.push(status);
+        } else {
+            merged.entry(status.path.clone())
+                .or_insert(StatusEntry::new(status.path, None, None))
+                .unstaged = status.unstaged;
+        }
+    }
+
+    // Conflicted paths have no resolved index entry, so both comparisons
+    // above get them wrong; they are reported on their own
+    for conflict in repo.conflicts()? {
+        let path = conflict.path;
+        merged.insert(path.clone(), StatusEntry::new(path, Some(Change::Conflicted), Some(Change::Conflicted)));
+    }
+
+    let mut entries: Vec<StatusEntry> = merged.into_values().chain(untracked).collect();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(Status {
+        head: repo.head()?,
+        entries,
+    })
+}
+
+/// Compare the index against the tree of the HEAD commit. Before the first
+/// commit everything in the index is staged as new.
+fn staged_changes(repo: &dyn Repository, index: &Index) -> Result<Vec<(String, Change)>> {
+    let head_files = repo.head_files()?;
+
+    let mut changes = Vec::new();
+    for entry in index.entries() {
+        match head_files.get(&entry.path) {
+            None => changes.push((entry.path.clone(), Change::Added)),
+            Some((mode, _)) if is_symlink_mode(*mode) != is_symlink_mode(entry.mode) => {
+                changes.push((entry.path.clone(), Change::Typechange));
+            }
+            Some((mode, id)) if *mode != entry.mode || *id != entry.id => {
+                changes.push((entry.path.clone(), Change::Modified));
+            }
+            Some(_) => {}
+        }
+    }
+    for path in head_files.keys() {
+        if index.get(path).is_none() {
+            changes.push((path.clone(), Change::Deleted));
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Turn staged deletions and additions that look alike into renames (and
+/// with copies on, additions that look like a changed file into copies),
+/// returning where each came from.
+fn staged_renames(
+    repo: &dyn Repository,
+    index: &Index,
+    staged: &mut Vec<(String, Change)>,
+    options: RenameOptions,
+) -> Result<HashMap<String, Origin>> {
+    let head_files = repo.head_files()?;
+    let paths = |wanted: Change| -> Vec<String> {
+        staged.iter().filter(|(_, change)| *change == wanted).map(|(path, _)| path.clone()).collect()
+    };
+    let (removed, kept, added) = (paths(Change::Deleted), paths(Change::Modified), paths(Change::Added));
+    if added.is_empty() || (removed.is_empty() && !options.copies) {
+        return Ok(HashMap::new());
+    }
+
+    // Sources as they were in HEAD, destinations as they are staged
+    let load = |paths: &[String], from_head: bool| -> Result<Vec<(u32, String, Vec<u8>)>> {
+        paths.iter()
+            .map(|path| {
+                let (mode, id) = if from_head {
+                    head_files[path].clone()
+                } else {
+                    let entry = index.get(path).expect("added files are staged");
+                    (entry.mode, entry.id.clone())
+                };
+                let data = repo.read_blob(&id)?;
+                Ok((mode, id, data))
+            })
+            .collect()
+    };
+    let removed_blobs = load(&removed, true)?;
+    let kept_blobs = if options.copies { load(&kept, true)? } else { Vec::new() };
+    let added_blobs = load(&added, false)?;
+    let kept_paths = if options.copies { kept.as_slice() } else { &[] };
+    let pairings = find_renames(
+        &candidates(&removed, &removed_blobs),
+        &candidates(kept_paths, &kept_blobs),
+        &candidates(&added, &added_blobs),
+        options,
+    );
+
+    let mut origins = HashMap::new();
+    let mut renamed_away = HashSet::new();
+    for pairing in pairings {
+        let source = removed.get(pairing.source).unwrap_or_else(|| &kept[pairing.source - removed.len()]);
+        let destination = &added[pairing.destination];
+        if !pairing.copy {
+            renamed_away.insert(source.clone());
+        }
+        if let Some((_, change)) = staged.iter_mut().find(|(path, _)| path == destination) {
+            *change = if pairing.copy { Change::Copied } else { Change::Renamed };
+        }
+        origins.insert(destination.clone(), Origin { path: source.clone(), similarity: pairing.similarity });
+    }
+    staged.retain(|(path, change)| !(*change == Change::Deleted && renamed_away.contains(path)));
+    Ok(origins)
+}
+
+fn candidates<'a>(paths: &'a [String], blobs: &'a [(u32, String, Vec<u8>)]) -> Vec<Candidate<'a>> {
+    paths.iter()
+        .zip(blobs)
+        .map(|(path, (mode, id, data))| Candidate { path, id, data, is_symlink: is_symlink_mode(*mode) })
+        .collect()
+}
+
+/// Compare the working tree against the index: files missing from the
+/// index are untracked, and files whose contents differ from their staged
+/// blob are modified.
+fn worktree_changes(repo: &dyn Repository, index: &Index) -> Result<Vec<StatusEntry>> {
+    let root = repo.location().work_tree.as_path();
+    let mut entries = Vec::new();
+    let mut seen = HashSet::new();
+
+    for file in IgnoreRules::load(repo.location()).walk(root).files {
+        match index.get(&file.path) {
+            None => entries.push(StatusEntry::untracked(file.path.clone())),
+            Some(staged) => {
+                if let Some(change) = worktree_change(staged, &file.full_path, file.is_symlink, repo.format())? {
+                    entries.push(StatusEntry::new(file.path.clone(), None, Some(change)));
+                }
+            }
+        }
+        seen.insert(file.path);
+    }
+
+    // Tracked files are still reported when an ignore pattern kept the
+    // walk away from them
+    for entry in index.entries().filter(|entry| !seen.contains(&entry.path)) {
+        let full_path = root.join(&entry.path);
+        let change = match full_path.symlink_metadata() {
+            // A directory in its place leaves the file deleted, as in diff
+            Err(_) => Some(Change::Deleted),
+            Ok(metadata) if metadata.is_dir() => Some(Change::Deleted),
+            Ok(metadata) => worktree_change(entry, &full_path, metadata.file_type().is_symlink(), repo.format())?,
+        };
+        if let Some(change) = change {
+            entries.push(StatusEntry::new(entry.path.clone(), None, Some(change)));
+        }
+    }
+
+    Ok(entries)
+}
+
+/// How a tracked file on disk differs from its index entry, if at all.
+fn worktree_change(staged: &IndexEntry, full_path: &Path, is_symlink: bool, format: ObjectFormat) -> Result<Option<Change>> {
+    if is_symlink != is_symlink_mode(staged.mode) {
+        return Ok(Some(Change::Typechange));
+    }
+    if !staged.matches_file(full_path, format)? {
+        return Ok(Some(Change::Modified));
+    }
+    Ok(None)
+}
+
+fn is_symlink_mode(mode: u32) -> bool {
+    mode & 0o170000 == MODE_SYMLINK
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_repo::TestRepo;
+    use std::fs;
+
+    /// Each changed path with its staged and unstaged change.
+    fn changes(repo: &TestRepo) -> Vec<(String, Option<Change>, Option<Change>)> {
+        status_with_renames(repo.repo.as_ref(), None).unwrap()
+            .entries
+            .into_iter()
+            .map(|entry| (entry.path, entry.staged, entry.unstaged))
+            .collect()
+    }
+
+    #[test]
+    fn added_files_are_staged_until_committed() {
+        let repo = TestRepo::new();
+        repo.write("a", "1\n");
+        repo.write("dir/b", "2\n");
+        assert_eq!(changes(&repo), [
+            ("a".to_string(), Some(Change::Untracked), Some(Change::Untracked)),
+            ("dir/b".to_string(), Some(Change::Untracked), Some(Change::Untracked)),
+        ]);
+
+        repo.add(&[""]);
+        assert_eq!(changes(&repo), [
+            ("a".to_string(), Some(Change::Added), None),
+            ("dir/b".to_string(), Some(Change::Added), None),
+        ]);
+
+        repo.commit("files", 1000);
+        assert!(changes(&repo).is_empty());
+    }
+
+    #[test]
+    fn staged_and_unstaged_edits_are_told_apart() {
+        let repo = TestRepo::new();
+        repo.write("a", "1\n");
+        repo.write("b", "2\n");
+        repo.add(&["a", "b"]);
+        repo.commit("files", 1000);
+
+        repo.write("a", "staged\n");
+        repo.add(&["a"]);
+        repo.write("a", "and then edited\n");
+        fs::remove_file(repo.path("b")).unwrap();
+        assert_eq!(changes(&repo), [
+            ("a".to_string(), Some(Change::Modified), Some(Change::Modified)),
+            ("b".to_string(), None, Some(Change::Deleted)),
+        ]);
+    }
+
+    #[test]
+    fn a_tracked_file_replaced_by_a_directory_is_deleted() {
+        let repo = TestRepo::new();
+        repo.write("c", "file\n");
+        repo.add(&["c"]);
+        repo.commit("file", 1000);
+
+        fs::remove_file(repo.path("c")).unwrap();
+        repo.write("c/x", "in a directory\n");
+        assert_eq!(changes(&repo), [
+            ("c".to_string(), None, Some(Change::Deleted)),
+            ("c/x".to_string(), Some(Change::Untracked), Some(Change::Untracked)),
+        ]);
+    }
+}
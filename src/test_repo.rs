@@ -0,0 +1,73 @@
+//! Throwaway native repositories for unit tests.
+
+use chrono::{DateTime, FixedOffset};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::commit::{self, CommitOutcome};
+use crate::init;
+use crate::location::Location;
+use crate::objects::{ObjectFormat, Signature};
+use crate::repository::{self, Repository};
+use crate::stage;
+
+static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+/// A freshly initialized `.rit` repository in a temporary directory,
+/// removed again when dropped.
+pub struct TestRepo {
+    pub dir: PathBuf,
+    pub repo: Box<dyn Repository>,
+}
+
+impl TestRepo {
+    pub fn new() -> Self {
+        let name = format!("rit-test-{}-{}", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed));
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let dir = fs::canonicalize(&dir).unwrap();
+        init::init(&dir, ObjectFormat::Sha1).unwrap();
+        // Leave out the starter .gitignore so tests see only their own files
+        fs::remove_file(dir.join(".gitignore")).unwrap();
+
+        let location = Location { work_tree: dir.clone(), rit_dir: Some(dir.join(".rit")), cwd: dir.clone() };
+        let repo = repository::open(location).unwrap();
+        TestRepo { dir, repo }
+    }
+
+    pub fn path(&self, path: &str) -> PathBuf {
+        self.dir.join(path)
+    }
+
+    /// Write a file, creating its directories.
+    pub fn write(&self, path: &str, contents: &str) {
+        let full_path = self.path(path);
+        fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+        fs::write(full_path, contents).unwrap();
+    }
+
+    pub fn add(&self, paths: &[&str]) {
+        let paths: Vec<String> = paths.iter().map(|path| path.to_string()).collect();
+        stage::add(self.repo.as_ref(), &paths).unwrap();
+    }
+
+    /// Commit the index as `message`, dated `time` seconds after the epoch.
+    pub fn commit(&self, message: &str, time: i64) -> String {
+        let signature = Signature {
+            name: "Test".to_string(),
+            email: "test@example.com".to_string(),
+            time: DateTime::from_timestamp(time, 0).unwrap().with_timezone(&FixedOffset::east_opt(0).unwrap()),
+        };
+        match commit::commit(self.repo.as_ref(), message.to_string(), signature.clone(), signature).unwrap() {
+            CommitOutcome::Created(created) => created.id,
+            _ => panic!("nothing to commit"),
+        }
+    }
+}
+
+impl Drop for TestRepo {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
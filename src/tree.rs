@@ -0,0 +1,198 @@
+use colored::*;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ASCII: AtomicBool = AtomicBool::new(false);
+
+/// Draw trees, graphs and markers with plain ASCII instead of box-drawing
+/// characters, for terminals and logs that can't show them.
+pub fn use_ascii(ascii: bool) {
+    ASCII.store(ascii, Ordering::Relaxed);
+}
+
+/// The symbol to draw for `symbol`, one of the box-drawing pieces and
+/// markers used in rit's output: itself, or its ASCII stand-in when
+/// [`use_ascii`] is on. Other text is returned unchanged.
+pub fn glyph(symbol: &'static str) -> &'static str {
+    if !ASCII.load(Ordering::Relaxed) {
+        return symbol;
+    }
+    match symbol {
+        "├──" => "|--",
+        "└──" => "`--",
+        "│   " => "|   ",
+        "├─" => "|-",
+        "└─" => "`-",
+        "┌─" => ",-",
+        "│" | "├" | "┤" => "|",
+        "─" => "-",
+        "┼" | "┴" | "┬" => "+",
+        "╮" | "╭" => ".",
+        "╯" | "╰" => "'",
+        "●" => "*",
+        "○" => "o",
+        "◆" => "#",
+        "→" => "->",
+        "✓" => "+",
+        "✗" => "x",
+        "…" => "...",
+        _ => symbol,
+    }
+}
+
+/// How much of a file tree to draw before folding the rest into summary
+/// lines such as `src/ (+142 files)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TreeLimits {
+    /// Directory levels to expand; deeper directories become one line.
+    pub depth: Option<usize>,
+    /// Lines to draw in total before summarizing the remaining files.
+    pub limit: Option<usize>,
+}
+
+impl TreeLimits {
+    pub const DEFAULT_DEPTH: usize = 3;
+    pub const DEFAULT_LIMIT: usize = 50;
+
+    /// Draw every file, however deep or numerous.
+    pub fn unlimited() -> Self {
+        TreeLimits { depth: None, limit: None }
+    }
+}
+
+impl Default for TreeLimits {
+    fn default() -> Self {
+        TreeLimits {
+            depth: Some(Self::DEFAULT_DEPTH),
+            limit: Some(Self::DEFAULT_LIMIT),
+        }
+    }
+}
+
+/// A directory's entries, keyed by name and whether the entry is a
+/// directory: a file and a directory of the same name can both appear, as
+/// when one replaces the other.
+type Children<'a, T> = BTreeMap<(&'a str, bool), Node<'a, T>>;
+
+enum Node<'a, T> {
+    File(&'a T),
+    Dir(Children<'a, T>),
+}
+
+impl<T> Node<'_, T> {
+    fn file_count(&self) -> usize {
+        match self {
+            Node::File(_) => 1,
+            Node::Dir(children) => children.values().map(Node::file_count).sum(),
+        }
+    }
+}
+
+/// Lay out `/`-separated paths as a nested box-drawing tree. `label` draws
+/// a file's line from its item and file name. Nothing is dropped silently:
+/// collapsed directories and files past the limit are counted in summary
+/// lines.
+pub fn render<T>(items: &[(String, T)], limits: TreeLimits, label: impl Fn(&T, &str) -> String) -> Vec<String> {
+    let mut root: Children<T> = BTreeMap::new();
+    for (path, item) in items {
+        let mut dir = &mut root;
+        let mut parts = path.split('/').filter(|part| !part.is_empty()).peekable();
+        while let Some(part) = parts.next() {
+            if parts.peek().is_none() {
+                dir.insert((part, false), Node::File(item));
+                break;
+            }
+            let Node::Dir(children) = dir.entry((part, true)).or_insert_with(|| Node::Dir(BTreeMap::new())) else {
+                unreachable!("directory entries are always directories");
+            };
+            dir = children;
+        }
+    }
+
+    let mut lines = Vec::new();
+    let mut budget = limits.limit.unwrap_or(usize::MAX);
+    render_dir(&root, "", 0, limits.depth, &mut budget, &label, &mut lines);
+    lines
+}
+
+fn render_dir<T>(
+    children: &Children<T>,
+    prefix: &str,
+    level: usize,
+    depth: Option<usize>,
+    budget: &mut usize,
+    label: &impl Fn(&T, &str) -> String,
+    lines: &mut Vec<String>,
+) {
+    let entries: Vec<_> = children.iter().collect();
+    for (i, ((name, _), node)) in entries.iter().enumerate() {
+        if *budget == 0 {
+            let remaining: usize = entries[i..].iter().map(|(_, node)| node.file_count()).sum();
+            lines.push(format!("{}{} {}",
+                prefix,
+                glyph("└──").cyan(),
+                format!("{} (+{} more {})", glyph("…"), remaining, files_word(remaining)).bright_black()
+            ));
+            return;
+        }
+        *budget -= 1;
+
+        let is_last = i == entries.len() - 1;
+        let connector = glyph(if is_last { "└──" } else { "├──" });
+        match node {
+            Node::File(item) => {
+                lines.push(format!("{}{} {}", prefix, connector.cyan(), label(item, name)));
+            }
+            Node::Dir(_) if depth.is_some_and(|depth| level >= depth) => {
+                let count = node.file_count();
+                lines.push(format!("{}{} {} {}",
+                    prefix,
+                    connector.cyan(),
+                    format!("{}/", name).bright_blue().bold(),
+                    format!("(+{} {})", count, files_word(count)).bright_black()
+                ));
+            }
+            Node::Dir(grandchildren) => {
+                lines.push(format!("{}{} {}", prefix, connector.cyan(), format!("{}/", name).bright_blue().bold()));
+                let child_prefix = format!("{}{}", prefix, if is_last { "    " } else { glyph("│   ") });
+                render_dir(grandchildren, &child_prefix, level + 1, depth, budget, label, lines);
+            }
+        }
+    }
+}
+
+fn files_word(count: usize) -> &'static str {
+    if count == 1 { "file" } else { "files" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw(paths: &[&str], limits: TreeLimits) -> Vec<String> {
+        colored::control::set_override(false);
+        use_ascii(false);
+        let items: Vec<(String, ())> = paths.iter().map(|path| (path.to_string(), ())).collect();
+        render(&items, limits, |_, name| name.to_string())
+    }
+
+    #[test]
+    fn a_file_and_a_directory_can_share_a_name() {
+        assert_eq!(draw(&["a/b", "a"], TreeLimits::unlimited()), [
+            "├── a",
+            "└── a/",
+            "    └── b",
+        ]);
+    }
+
+    #[test]
+    fn deep_directories_and_extra_files_are_counted() {
+        let limits = TreeLimits { depth: Some(1), limit: Some(3) };
+        assert_eq!(draw(&["a", "b/c/d", "b/c/e", "c", "d"], limits), [
+            "├── a",
+            "├── b/",
+            "│   └── c/ (+2 files)",
+            "└── … (+2 more files)",
+        ]);
+    }
+}